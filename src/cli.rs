@@ -1,5 +1,6 @@
 //! Command-line interface for the zvar compiler
 
+use crate::{docs::DocsFormat, vm::value::OverflowMode};
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
@@ -35,6 +36,26 @@ pub enum Commands {
         /// Show debug information
         #[arg(long)]
         debug: bool,
+
+        /// Strip unreachable code, remove redundant instruction pairs and hoist loop invariants
+        #[arg(long)]
+        optimize: bool,
+
+        /// Print each executed instruction with the top of the stack
+        #[arg(long)]
+        trace: bool,
+
+        /// Seed for random(), making runs reproducible
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// What integer +, - and * do on overflow
+        #[arg(long, value_enum, default_value = "checked")]
+        overflow: OverflowMode,
+
+        /// Print integers with thousands separators (1,000,000)
+        #[arg(long)]
+        group_digits: bool,
     },
 
     /// Compile a zvar program to bytecode
@@ -49,12 +70,60 @@ pub enum Commands {
         /// Show bytecode disassembly
         #[arg(long)]
         disasm: bool,
+
+        /// Show bytecode disassembly grouped by function
+        #[arg(long)]
+        disasm_functions: bool,
+
+        /// Show bytecode disassembly next to the source lines it was generated from
+        #[arg(long)]
+        disasm_source: bool,
+
+        /// Strip unreachable code, remove redundant instruction pairs and hoist loop invariants
+        #[arg(long)]
+        optimize: bool,
+
+        /// Omit debug info (spans, docs) from the output
+        #[arg(long)]
+        strip: bool,
+
+        /// Print the parsed syntax tree
+        #[arg(long)]
+        emit_ast: bool,
+
+        /// Write the control flow graph in Graphviz DOT format
+        #[arg(long, value_name = "PATH")]
+        emit_cfg: Option<PathBuf>,
     },
 
     /// Check syntax without compiling
     Check {
         /// Input file to check (.zvar or .0var)
         file: PathBuf,
+
+        /// Warn about redundant empty statements (stray ';')
+        #[arg(long)]
+        strict_semicolons: bool,
+
+        /// Show bytecode disassembly (the program is not run)
+        #[arg(long)]
+        disasm: bool,
+
+        /// Show bytecode disassembly grouped by function (the program is not run)
+        #[arg(long)]
+        disasm_functions: bool,
+
+        /// Print the parsed syntax tree
+        #[arg(long)]
+        emit_ast: bool,
+
+        /// Report the share of functions that have documentation
+        #[arg(long)]
+        check_docs_coverage: bool,
+
+        /// Print the token stream with the span of every token
+        #[arg(long)]
+        dump_tokens: bool,
     },
 
     /// Show information about entities in a program
@@ -65,6 +134,38 @@ pub enum Commands {
         /// Show only documentation
         #[arg(long)]
         docs_only: bool,
+
+        /// Show every scope and its entries as a tree
+        #[arg(long)]
+        dump_symbols: bool,
+    },
+
+    /// Generate API documentation for a program
+    Docs {
+        /// Input file to document (.zvar or .0var)
+        file: PathBuf,
+
+        /// Output format
+        #[arg(long, value_enum, default_value = "markdown")]
+        format: DocsFormat,
+
+        /// Output file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Run a program repeatedly and report execution times
+    Bench {
+        /// Input file to benchmark (.zvar or .0var)
+        file: PathBuf,
+
+        /// Number of times to execute the program
+        #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+        iterations: u32,
+
+        /// Strip unreachable code, remove redundant instruction pairs and hoist loop invariants
+        #[arg(long)]
+        optimize: bool,
     },
 
     /// Interactive REPL mode
@@ -86,8 +187,10 @@ impl Cli {
         match &self.command {
             Commands::Run { file, .. } => Some(file),
             Commands::Compile { file, .. } => Some(file),
-            Commands::Check { file } => Some(file),
+            Commands::Check { file, .. } => Some(file),
             Commands::Info { file, .. } => Some(file),
+            Commands::Docs { file, .. } => Some(file),
+            Commands::Bench { file, .. } => Some(file),
             Commands::Repl { .. } => None,
         }
     }
@@ -101,7 +204,21 @@ impl Cli {
     pub fn show_disasm(&self) -> bool {
         matches!(
             &self.command,
-            Commands::Run { disasm: true, .. } | Commands::Compile { disasm: true, .. }
+            Commands::Run { disasm: true, .. }
+                | Commands::Compile { disasm: true, .. }
+                | Commands::Compile {
+                    disasm_functions: true,
+                    ..
+                }
+                | Commands::Compile {
+                    disasm_source: true,
+                    ..
+                }
+                | Commands::Check { disasm: true, .. }
+                | Commands::Check {
+                    disasm_functions: true,
+                    ..
+                }
         )
     }
 
@@ -147,6 +264,11 @@ mod tests {
                 file: PathBuf::from("test.zvar"),
                 disasm: false,
                 debug: false,
+                optimize: false,
+                trace: false,
+                seed: None,
+                overflow: OverflowMode::Checked,
+                group_digits: false,
             },
             verbose: false,
             no_color: false,
@@ -165,6 +287,11 @@ mod tests {
                 file: PathBuf::from("test.zvar"),
                 disasm: false,
                 debug: false,
+                optimize: false,
+                trace: false,
+                seed: None,
+                overflow: OverflowMode::Checked,
+                group_digits: false,
             },
             verbose: false,
             no_color: false,
@@ -176,6 +303,11 @@ mod tests {
                 file: PathBuf::from("test.0var"),
                 disasm: false,
                 debug: false,
+                optimize: false,
+                trace: false,
+                seed: None,
+                overflow: OverflowMode::Checked,
+                group_digits: false,
             },
             verbose: false,
             no_color: false,
@@ -187,6 +319,11 @@ mod tests {
                 file: PathBuf::from("test.txt"),
                 disasm: false,
                 debug: false,
+                optimize: false,
+                trace: false,
+                seed: None,
+                overflow: OverflowMode::Checked,
+                group_digits: false,
             },
             verbose: false,
             no_color: false,
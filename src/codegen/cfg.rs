@@ -0,0 +1,167 @@
+//! Basic-block control flow graph of generated bytecode
+
+use super::instruction::{Bytecode, Instruction};
+use crate::prelude::*;
+
+/// A straight-line run of instructions, `start..end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Basic blocks and the control transfers between them
+///
+/// Blocks are split at jump targets, function starts and the entry point,
+/// and after every jump, return or halt. Calls do not end a block, so every
+/// function forms its own subgraph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlFlowGraph {
+    pub blocks: Vec<BasicBlock>,
+    /// Edges as `(from, to)` block indices
+    pub edges: Vec<(usize, usize)>,
+}
+
+impl ControlFlowGraph {
+    /// Build the graph of a bytecode program
+    pub fn build(bytecode: &Bytecode) -> Self {
+        let len = bytecode.len();
+
+        let mut leaders: BTreeSet<usize> = bytecode.jump_targets().into_iter().collect();
+        leaders.insert(0);
+        leaders.insert(bytecode.entry_point);
+        leaders.extend(bytecode.function_table.values().copied());
+        for (addr, instruction) in bytecode.instructions.iter().enumerate() {
+            if ends_block(instruction) {
+                leaders.insert(addr + 1);
+            }
+        }
+
+        let starts: Vec<usize> = leaders.into_iter().filter(|&addr| addr < len).collect();
+        let blocks: Vec<BasicBlock> = starts
+            .iter()
+            .enumerate()
+            .map(|(i, &start)| BasicBlock {
+                start,
+                end: starts.get(i + 1).copied().unwrap_or(len),
+            })
+            .collect();
+
+        let block_at = |addr: usize| blocks.iter().position(|block| block.start == addr);
+
+        let mut edges = Vec::new();
+        for (index, block) in blocks.iter().enumerate() {
+            let last_addr = block.end - 1;
+            let last = &bytecode.instructions[last_addr];
+            let target = last.jump_target(last_addr);
+            let successors = match last {
+                Instruction::Jump(_) | Instruction::JumpRel(_) => target.into_iter().collect(),
+                Instruction::JumpIfFalse(_)
+                | Instruction::JumpIfTrue(_)
+                | Instruction::JumpIfFalseRel(_)
+                | Instruction::JumpIfTrueRel(_) => target.into_iter().chain([block.end]).collect(),
+                Instruction::Return | Instruction::ReturnValue | Instruction::Halt => vec![],
+                _ => vec![block.end],
+            };
+
+            for successor in successors {
+                if let Some(to) = block_at(successor) {
+                    edges.push((index, to));
+                }
+            }
+        }
+
+        ControlFlowGraph { blocks, edges }
+    }
+
+    /// Render the graph in Graphviz DOT format, listing each block's instructions
+    pub fn to_dot(&self, bytecode: &Bytecode) -> String {
+        let mut dot =
+            String::from("digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n");
+
+        for (index, block) in self.blocks.iter().enumerate() {
+            let mut label = String::new();
+            for addr in block.start..block.end {
+                label.push_str(&format!("{:04} {}\\l", addr, bytecode.instructions[addr]));
+            }
+            dot.push_str(&format!(
+                "    b{} [label=\"{}\"];\n",
+                index,
+                label.replace('"', "\\\"")
+            ));
+        }
+
+        for (from, to) in &self.edges {
+            dot.push_str(&format!("    b{} -> b{};\n", from, to));
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Whether control can leave a block after this instruction other than by falling through
+fn ends_block(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Jump(_)
+            | Instruction::JumpIfFalse(_)
+            | Instruction::JumpIfTrue(_)
+            | Instruction::JumpRel(_)
+            | Instruction::JumpIfFalseRel(_)
+            | Instruction::JumpIfTrueRel(_)
+            | Instruction::Return
+            | Instruction::ReturnValue
+            | Instruction::Halt
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_if_else_graph() {
+        let source = r#"
+        main {
+            int v$0 = 1;
+            if (v$0 > 0) {
+                print(1);
+            } else {
+                print(2);
+            }
+            print(3);
+        }
+        "#;
+
+        let (bytecode, _) = crate::compile_source(source).unwrap();
+        let cfg = ControlFlowGraph::build(&bytecode);
+
+        // Condition, then branch, else branch and the join block
+        assert_eq!(cfg.blocks.len(), 4);
+        assert_eq!(cfg.edges, vec![(0, 2), (0, 1), (1, 3), (2, 3)]);
+
+        let dot = cfg.to_dot(&bytecode);
+        assert!(dot.starts_with("digraph cfg {"));
+        assert_eq!(dot.matches("[label=").count(), 4);
+        assert_eq!(dot.matches(" -> ").count(), 4);
+    }
+
+    #[test]
+    fn test_relative_jumps_keep_their_edges() {
+        let source = "main { int v$0 = 0; while (v$0 < 3) { v$0 = v$0 + 1; } }";
+        let (program, symbol_table) = crate::parse(source).unwrap();
+
+        let mut codegen = crate::codegen::CodeGenerator::new();
+        codegen.set_relative_jumps(true);
+        let (relative, _) = codegen.generate(&program, &symbol_table).unwrap();
+        assert!(relative.has_relative_jumps());
+
+        let (absolute, _) = crate::generate(&program, &symbol_table).unwrap();
+        assert_eq!(
+            ControlFlowGraph::build(&relative),
+            ControlFlowGraph::build(&absolute)
+        );
+        assert!(ControlFlowGraph::build(&relative).edges.len() > 2);
+    }
+}
@@ -0,0 +1,407 @@
+//! Compile-time evaluation of constant initializers
+
+use super::instruction::Value;
+use crate::{
+    error::{ZvarError, ZvarResult},
+    parser::ast::*,
+    prelude::*,
+    vm::value::Value as RuntimeValue,
+    Spanned,
+};
+use alloc::borrow::Cow;
+
+/// Evaluate the constants declared in a function or main body
+///
+/// Initializers built from literals and other constants are folded into
+/// values; anything else (variables, calls, runtime errors such as division
+/// by zero) is left for the VM. Constants may refer to constants declared
+/// later in the same body, so a chain that leads back to itself is reported
+/// as `RecursiveConstant` instead of being followed forever.
+pub fn evaluate_constants(body: &Block) -> ZvarResult<BTreeMap<String, Value>> {
+    let mut evaluator = ConstantEvaluator::default();
+    evaluator.collect_block(body);
+
+    let mut names: Vec<String> = evaluator.initializers.keys().cloned().collect();
+    names.sort();
+    for name in names {
+        evaluator.evaluate(&name)?;
+    }
+
+    Ok(evaluator
+        .values
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| (name, value)))
+        .collect())
+}
+
+/// Fold an expression built from literals and already known constants
+///
+/// Used where constant values are needed before a whole body is available,
+/// e.g. by the parser. `None` if the expression depends on anything else.
+pub fn evaluate_with(expr: &Expression, constants: &BTreeMap<String, Value>) -> Option<Value> {
+    let mut evaluator = ConstantEvaluator {
+        values: constants
+            .iter()
+            .map(|(name, value)| (name.clone(), Some(value.clone())))
+            .collect(),
+        ..ConstantEvaluator::default()
+    };
+
+    // Without initializers there is nothing that could recurse
+    evaluator.evaluate_expression(expr).ok().flatten()
+}
+
+/// Apply boolean identities to a `!`, `&&` or `||` expression
+///
+/// `!true` becomes `false`, `x && true` and `x || false` become `x`, and an
+/// operand that decides the result on its own replaces the whole
+/// expression. An operand is only dropped when short-circuiting would skip
+/// it anyway or when evaluating it has no effect, and only boolean-valued
+/// operands are kept alone, since `&&`/`||` on other values behave
+/// differently at runtime. `constants` holds the folded constants in scope.
+pub fn simplify_boolean<'e>(
+    expr: &'e Expression,
+    constants: &BTreeMap<String, Value>,
+) -> Option<Cow<'e, Expression>> {
+    let literal = |value: bool| {
+        Cow::Owned(Expression::Boolean(Spanned::new(
+            BooleanLiteral { value },
+            expr.span(),
+        )))
+    };
+
+    match expr {
+        Expression::Unary(unary) => match unary.operator {
+            UnaryOperator::Not => {
+                known_bool(&unary.operand, constants).map(|value| literal(!value))
+            }
+        },
+        Expression::Logical(logical) => {
+            // The value that decides the result: false for `&&`, true for `||`
+            let deciding = logical.operator == LogicalOperator::Or;
+            let (left, right) = (&*logical.left, &*logical.right);
+
+            match (known_bool(left, constants), known_bool(right, constants)) {
+                // `false && x`, `true || x`: x is never evaluated
+                (Some(value), _) if value == deciding => Some(literal(value)),
+                // `true && x`, `false || x`
+                (Some(_), _) if is_boolean(right, constants) => Some(Cow::Borrowed(right)),
+                // `x && false`, `x || true`: x only needs to be free of effects
+                (_, Some(value))
+                    if value == deciding && is_boolean(left, constants) && has_no_effects(left) =>
+                {
+                    Some(literal(value))
+                }
+                // `x && true`, `x || false`
+                (_, Some(value)) if value != deciding && is_boolean(left, constants) => {
+                    Some(Cow::Borrowed(left))
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Boolean value of a literal, a folded constant or a `!` of one
+fn known_bool(expr: &Expression, constants: &BTreeMap<String, Value>) -> Option<bool> {
+    match expr {
+        Expression::Boolean(boolean) => Some(boolean.value),
+        Expression::Variable(var) => match constants.get(&var.name) {
+            Some(Value::Bool(value)) => Some(*value),
+            _ => None,
+        },
+        Expression::Unary(unary) => match unary.operator {
+            UnaryOperator::Not => known_bool(&unary.operand, constants).map(|value| !value),
+        },
+        _ => None,
+    }
+}
+
+/// Check if an expression always produces a boolean when it succeeds
+fn is_boolean(expr: &Expression, constants: &BTreeMap<String, Value>) -> bool {
+    match expr {
+        Expression::Binary(binary) => matches!(
+            binary.operator,
+            BinaryOperator::Equal
+                | BinaryOperator::NotEqual
+                | BinaryOperator::Less
+                | BinaryOperator::Greater
+                | BinaryOperator::LessEqual
+                | BinaryOperator::GreaterEqual
+        ),
+        Expression::Logical(_) | Expression::Unary(_) => true,
+        _ => known_bool(expr, constants).is_some(),
+    }
+}
+
+/// Check if evaluating an expression can neither fail nor have side effects
+///
+/// Comparisons are included: the type checker rejects ordering values of
+/// different types, and equality is defined for any two values.
+fn has_no_effects(expr: &Expression) -> bool {
+    match expr {
+        Expression::Integer(_)
+        | Expression::String(_)
+        | Expression::Boolean(_)
+        | Expression::Variable(_) => true,
+        Expression::Binary(binary) => {
+            matches!(
+                binary.operator,
+                BinaryOperator::Equal
+                    | BinaryOperator::NotEqual
+                    | BinaryOperator::Less
+                    | BinaryOperator::Greater
+                    | BinaryOperator::LessEqual
+                    | BinaryOperator::GreaterEqual
+            ) && has_no_effects(&binary.left)
+                && has_no_effects(&binary.right)
+        }
+        Expression::Logical(logical) => {
+            has_no_effects(&logical.left) && has_no_effects(&logical.right)
+        }
+        // `!` fails on non-booleans
+        Expression::Unary(_)
+        | Expression::FunctionCall(_)
+        | Expression::Conditional(_)
+        | Expression::Assignment(_) => false,
+    }
+}
+
+#[derive(Default)]
+struct ConstantEvaluator<'a> {
+    // Constant names mapped to their initializer
+    initializers: BTreeMap<String, &'a ConstantDeclaration>,
+    // Results so far; `None` means the constant is only known at runtime
+    values: BTreeMap<String, Option<Value>>,
+    // Constants whose initializer is currently being evaluated
+    in_progress: BTreeSet<String>,
+}
+
+impl<'a> ConstantEvaluator<'a> {
+    /// Record every constant declaration, including those in nested blocks
+    fn collect_block(&mut self, block: &'a Block) {
+        for stmt in &block.statements {
+            match stmt {
+                Statement::ConstantDeclaration(const_decl) => {
+                    self.initializers
+                        .insert(const_decl.name.clone(), const_decl);
+                }
+                Statement::If(if_stmt) => {
+                    self.collect_block(&if_stmt.then_block);
+                    if let Some(else_block) = &if_stmt.else_block {
+                        self.collect_block(else_block);
+                    }
+                }
+                Statement::While(while_stmt) => {
+                    self.collect_block(&while_stmt.body);
+                    if let Some(else_block) = &while_stmt.else_block {
+                        self.collect_block(else_block);
+                    }
+                }
+                Statement::Match(match_stmt) => {
+                    for arm in &match_stmt.arms {
+                        self.collect_block(&arm.body);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Evaluate a single constant by name
+    fn evaluate(&mut self, name: &str) -> ZvarResult<Option<Value>> {
+        if let Some(value) = self.values.get(name) {
+            return Ok(value.clone());
+        }
+
+        let Some(const_decl) = self.initializers.get(name).copied() else {
+            // Not declared in this body (e.g. a REPL constant from an earlier line)
+            return Ok(None);
+        };
+
+        if !self.in_progress.insert(name.to_string()) {
+            return Err(ZvarError::RecursiveConstant {
+                span: const_decl.span,
+                name: name.to_string(),
+            });
+        }
+
+        let value = self.evaluate_expression(&const_decl.initializer)?;
+
+        self.in_progress.remove(name);
+        self.values.insert(name.to_string(), value.clone());
+        Ok(value)
+    }
+
+    /// Fold an expression into a value if it only depends on constants
+    fn evaluate_expression(&mut self, expr: &Expression) -> ZvarResult<Option<Value>> {
+        let value = match expr {
+            Expression::Integer(int) => Some(Value::Int(int.value)),
+            Expression::String(string) => Some(Value::Str(string.value.clone())),
+            Expression::Boolean(boolean) => Some(Value::Bool(boolean.value)),
+            Expression::Variable(var) if var.name.starts_with("c$") => self.evaluate(&var.name)?,
+            Expression::Variable(_) | Expression::FunctionCall(_) | Expression::Assignment(_) => {
+                None
+            }
+            Expression::Binary(binary) => {
+                let left = self.evaluate_expression(&binary.left)?;
+                let right = self.evaluate_expression(&binary.right)?;
+
+                match (left, right) {
+                    (Some(left), Some(right)) => {
+                        let (left, right) = (RuntimeValue::from(left), RuntimeValue::from(right));
+                        let result = match binary.operator {
+                            BinaryOperator::Add => left.add(&right),
+                            BinaryOperator::Subtract => left.sub(&right),
+                            BinaryOperator::Multiply => left.mul(&right),
+                            BinaryOperator::Divide => left.div(&right),
+                            BinaryOperator::Power => left.pow(&right),
+                            BinaryOperator::Equal => left.equal(&right),
+                            BinaryOperator::NotEqual => left.not_equal(&right),
+                            BinaryOperator::Less => left.less(&right),
+                            BinaryOperator::Greater => left.greater(&right),
+                            BinaryOperator::LessEqual => left.less_equal(&right),
+                            BinaryOperator::GreaterEqual => left.greater_equal(&right),
+                        };
+                        result.ok().map(Value::from)
+                    }
+                    _ => None,
+                }
+            }
+            Expression::Logical(logical) => {
+                let left = self.evaluate_expression(&logical.left)?;
+                let right = self.evaluate_expression(&logical.right)?;
+
+                match (left, right) {
+                    (Some(left), Some(right)) => {
+                        let (left, right) = (RuntimeValue::from(left), RuntimeValue::from(right));
+                        let result = match logical.operator {
+                            LogicalOperator::And => left.logical_and(&right),
+                            LogicalOperator::Or => left.logical_or(&right),
+                        };
+                        result.ok().map(Value::from)
+                    }
+                    _ => None,
+                }
+            }
+            Expression::Unary(unary) => match self.evaluate_expression(&unary.operand)? {
+                Some(operand) => match unary.operator {
+                    UnaryOperator::Not => RuntimeValue::from(operand)
+                        .logical_not()
+                        .ok()
+                        .map(Value::from),
+                },
+                None => None,
+            },
+            Expression::Conditional(cond) => {
+                let condition = self.evaluate_expression(&cond.condition)?;
+                let then_value = self.evaluate_expression(&cond.then_expr)?;
+                let else_value = self.evaluate_expression(&cond.else_expr)?;
+
+                match condition {
+                    Some(condition) if condition.is_truthy() => then_value,
+                    Some(_) => else_value,
+                    None => None,
+                }
+            }
+        };
+
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, symbol_table::SymbolTable};
+
+    fn main_body(source: &str) -> Block {
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        let program = parser.parse_program().unwrap();
+
+        match program.items.into_iter().next() {
+            Some(Item::MainBlock(main)) => main.body,
+            _ => panic!("Expected main block"),
+        }
+    }
+
+    #[test]
+    fn test_folds_constant_expressions() {
+        let body = main_body(
+            r#"
+            main {
+                int c$0 = 10;
+                int c$1 = c$0 * 2 + 1;
+                int v$0 = 5;
+                int c$2 = v$0 + c$1;
+            }
+            "#,
+        );
+
+        let values = evaluate_constants(&body).unwrap();
+        assert_eq!(values.get("c$0"), Some(&Value::Int(10)));
+        assert_eq!(values.get("c$1"), Some(&Value::Int(21)));
+        // Depends on a variable, so it stays a runtime value
+        assert_eq!(values.get("c$2"), None);
+    }
+
+    /// Simplify a single expression; a kept operand is shown as its source text
+    fn simplified(source: &str) -> Option<String> {
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        let expr = parser.parse_expression_only().unwrap();
+
+        let constants = BTreeMap::from([("c$0".to_string(), Value::Bool(true))]);
+        simplify_boolean(&expr, &constants).map(|expr| match &*expr {
+            Expression::Boolean(boolean) => boolean.value.to_string(),
+            kept => {
+                let span = kept.span();
+                source[span.start_column as usize - 1..span.end_column as usize].to_string()
+            }
+        })
+    }
+
+    #[test]
+    fn test_boolean_simplifications() {
+        assert_eq!(simplified("!true").as_deref(), Some("false"));
+        assert_eq!(simplified("!c$0").as_deref(), Some("false"));
+        assert_eq!(simplified("v$0 > 1 && true").as_deref(), Some("v$0 > 1"));
+        assert_eq!(simplified("v$0 == 1 || false").as_deref(), Some("v$0 == 1"));
+        assert_eq!(simplified("c$0 && v$0 < 2").as_deref(), Some("v$0 < 2"));
+        assert_eq!(simplified("false && f$0()").as_deref(), Some("false"));
+        assert_eq!(simplified("true || f$0()").as_deref(), Some("true"));
+        assert_eq!(simplified("v$0 != 3 && false").as_deref(), Some("false"));
+        assert_eq!(simplified("v$0 < v$1 || !false").as_deref(), Some("true"));
+    }
+
+    #[test]
+    fn test_boolean_simplification_keeps_effects() {
+        // The call runs before `false` is looked at, so it has to stay
+        assert_eq!(simplified("f$0() == 1 && false"), None);
+        assert_eq!(simplified("f$0() == 1 || true"), None);
+
+        // On a non-boolean `v$0`, `&&` gives back `v$0` itself or fails at runtime
+        assert_eq!(simplified("v$0 && true"), None);
+        assert_eq!(simplified("v$0 && false"), None);
+        assert_eq!(simplified("!v$0"), None);
+    }
+
+    #[test]
+    fn test_constant_cycle_errors() {
+        let body = main_body(
+            r#"
+            main {
+                int c$0 = c$1 + 1;
+                int c$1 = c$0 + 1;
+            }
+            "#,
+        );
+
+        match evaluate_constants(&body) {
+            Err(ZvarError::RecursiveConstant { name, .. }) => assert_eq!(name, "c$0"),
+            other => panic!("Expected recursive constant error, got {:?}", other),
+        }
+    }
+}
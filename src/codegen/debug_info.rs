@@ -1,17 +1,14 @@
 //! Debug information for bytecode
 
-use crate::span::Span;
-use std::collections::HashMap;
+use crate::{prelude::*, span::Span};
 
 /// Debug information for a bytecode program
 #[derive(Debug, Clone)]
 pub struct DebugInfo {
     /// Maps instruction index to source span
-    pub instruction_spans: HashMap<usize, Span>,
+    pub instruction_spans: BTreeMap<usize, Span>,
     /// Maps entity names to their documentation
-    pub entity_docs: HashMap<String, String>,
-    /// Maps function names to their start instruction
-    pub function_starts: HashMap<String, usize>,
+    pub entity_docs: BTreeMap<String, String>,
     /// Original source code
     pub source: Option<String>,
 }
@@ -19,9 +16,8 @@ pub struct DebugInfo {
 impl DebugInfo {
     pub fn new() -> Self {
         DebugInfo {
-            instruction_spans: HashMap::new(),
-            entity_docs: HashMap::new(),
-            function_starts: HashMap::new(),
+            instruction_spans: BTreeMap::new(),
+            entity_docs: BTreeMap::new(),
             source: None,
         }
     }
@@ -41,11 +37,6 @@ impl DebugInfo {
         self.entity_docs.insert(entity, doc);
     }
 
-    /// Mark the start of a function
-    pub fn mark_function_start(&mut self, name: String, instruction_index: usize) {
-        self.function_starts.insert(name, instruction_index);
-    }
-
     /// Get span for instruction
     pub fn get_instruction_span(&self, instruction_index: usize) -> Option<Span> {
         self.instruction_spans.get(&instruction_index).copied()
@@ -55,11 +46,6 @@ impl DebugInfo {
     pub fn get_entity_doc(&self, entity: &str) -> Option<&String> {
         self.entity_docs.get(entity)
     }
-
-    /// Get function start instruction
-    pub fn get_function_start(&self, name: &str) -> Option<usize> {
-        self.function_starts.get(name).copied()
-    }
 }
 
 impl Default for DebugInfo {
@@ -1,6 +1,12 @@
 //! Bytecode instruction set for the zvar virtual machine
 
-use std::fmt;
+use super::debug_info::DebugInfo;
+use crate::{
+    error::{ZvarError, ZvarResult},
+    prelude::*,
+    vm::builtins::Builtins,
+};
+use core::{fmt, ops::Range};
 
 /// Bytecode instructions for the zvar VM
 #[derive(Debug, Clone, PartialEq)]
@@ -9,12 +15,14 @@ pub enum Instruction {
     Push(Value), // Push value onto stack
     Pop,         // Pop value from stack
     Dup,         // Duplicate top of stack
+    Swap,        // Exchange the top two stack values
 
     // Arithmetic operations
     Add, // Pop two values, push sum
     Sub, // Pop two values, push difference (second - first)
     Mul, // Pop two values, push product
     Div, // Pop two values, push quotient (second / first)
+    Pow, // Pop two values, push power (second ** first)
 
     // Comparison operations
     Equal,        // Pop two values, push equality result
@@ -30,9 +38,13 @@ pub enum Instruction {
     Not, // Pop one value, push logical NOT result
 
     // Variable operations
-    LoadVar(u32),   // Load variable v$N onto stack
-    StoreVar(u32),  // Store top of stack into variable v$N
-    LoadConst(u32), // Load constant c$N onto stack
+    LoadVar(u32),      // Load variable v$N onto stack
+    StoreVar(u32),     // Store top of stack into variable v$N
+    StoreVarKeep(u32), // Store top of stack into variable v$N, leaving it on the stack
+    IncVar(u32, i64),  // Add a constant to variable v$N in place
+    LoadConst(u32),    // Load constant c$N onto stack
+    LoadGlobal(u32),   // Load global slot N onto stack
+    StoreGlobal(u32),  // Store top of stack into global slot N
 
     // Function operations
     Call(String, u32), // Call function with N arguments
@@ -42,9 +54,16 @@ pub enum Instruction {
     // Control flow
     Jump(usize),        // Unconditional jump to instruction
     JumpIfFalse(usize), // Jump if top of stack is false/zero
+    JumpIfTrue(usize),  // Jump if top of stack is true/non-zero
+
+    // Control flow relative to the jump's own address
+    JumpRel(i32),        // Unconditional jump by N instructions
+    JumpIfFalseRel(i32), // Jump by N if top of stack is false/zero
+    JumpIfTrueRel(i32),  // Jump by N if top of stack is true/non-zero
 
     // Built-in functions
     Print,                    // Print top of stack
+    Random,                   // Pop an upper bound n, push a pseudo-random int in [0, n)
     Describe(String, String), // Describe entity with documentation
 
     // Utility
@@ -52,6 +71,71 @@ pub enum Instruction {
     Nop,  // No operation
 }
 
+impl Instruction {
+    /// Address this instruction jumps to when placed at `address`
+    ///
+    /// `None` for instructions that are not jumps, and for relative jumps
+    /// that would land before the first instruction.
+    pub fn jump_target(&self, address: usize) -> Option<usize> {
+        match self {
+            Instruction::Jump(target)
+            | Instruction::JumpIfFalse(target)
+            | Instruction::JumpIfTrue(target) => Some(*target),
+            Instruction::JumpRel(offset)
+            | Instruction::JumpIfFalseRel(offset)
+            | Instruction::JumpIfTrueRel(offset) => address.checked_add_signed(*offset as isize),
+            _ => None,
+        }
+    }
+
+    /// How many values this instruction pops and then pushes
+    ///
+    /// `CALL` is counted as consuming its arguments and pushing a result,
+    /// which holds for user functions but not for builtins without one
+    /// (e.g. `print`); `RETVAL` hands its value over to the caller's `CALL`.
+    pub fn stack_effect(&self) -> (usize, usize) {
+        match self {
+            Instruction::Push(_)
+            | Instruction::LoadVar(_)
+            | Instruction::LoadConst(_)
+            | Instruction::LoadGlobal(_) => (0, 1),
+            Instruction::Pop
+            | Instruction::StoreVar(_)
+            | Instruction::StoreGlobal(_)
+            | Instruction::ReturnValue
+            | Instruction::JumpIfFalse(_)
+            | Instruction::JumpIfTrue(_)
+            | Instruction::JumpIfFalseRel(_)
+            | Instruction::JumpIfTrueRel(_)
+            | Instruction::Print => (1, 0),
+            Instruction::Dup => (1, 2),
+            Instruction::Swap => (2, 2),
+            Instruction::Add
+            | Instruction::Sub
+            | Instruction::Mul
+            | Instruction::Div
+            | Instruction::Pow
+            | Instruction::Equal
+            | Instruction::NotEqual
+            | Instruction::Less
+            | Instruction::Greater
+            | Instruction::LessEqual
+            | Instruction::GreaterEqual
+            | Instruction::And
+            | Instruction::Or => (2, 1),
+            Instruction::Not | Instruction::Random | Instruction::StoreVarKeep(_) => (1, 1),
+            Instruction::Call(_, argc) => (*argc as usize, 1),
+            Instruction::IncVar(..)
+            | Instruction::Return
+            | Instruction::Jump(_)
+            | Instruction::JumpRel(_)
+            | Instruction::Describe(..)
+            | Instruction::Halt
+            | Instruction::Nop => (0, 0),
+        }
+    }
+}
+
 /// Runtime values that can be stored on the stack
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
@@ -147,10 +231,12 @@ impl fmt::Display for Instruction {
             Instruction::Push(value) => write!(f, "PUSH {}", value),
             Instruction::Pop => write!(f, "POP"),
             Instruction::Dup => write!(f, "DUP"),
+            Instruction::Swap => write!(f, "SWAP"),
             Instruction::Add => write!(f, "ADD"),
             Instruction::Sub => write!(f, "SUB"),
             Instruction::Mul => write!(f, "MUL"),
             Instruction::Div => write!(f, "DIV"),
+            Instruction::Pow => write!(f, "POW"),
             Instruction::Equal => write!(f, "EQUAL"),
             Instruction::NotEqual => write!(f, "NOT_EQUAL"),
             Instruction::Less => write!(f, "LESS"),
@@ -162,13 +248,22 @@ impl fmt::Display for Instruction {
             Instruction::Not => write!(f, "NOT"),
             Instruction::LoadVar(n) => write!(f, "LOADVAR v${}", n),
             Instruction::StoreVar(n) => write!(f, "STOREVAR v${}", n),
+            Instruction::StoreVarKeep(n) => write!(f, "STOREVAR_KEEP v${}", n),
+            Instruction::IncVar(n, amount) => write!(f, "INCVAR v${} {}", n, amount),
             Instruction::LoadConst(n) => write!(f, "LOADCONST c${}", n),
+            Instruction::LoadGlobal(n) => write!(f, "LOADGLOBAL {}", n),
+            Instruction::StoreGlobal(n) => write!(f, "STOREGLOBAL {}", n),
             Instruction::Call(name, argc) => write!(f, "CALL {} {}", name, argc),
             Instruction::Return => write!(f, "RETURN"),
             Instruction::ReturnValue => write!(f, "RETURN_VALUE"),
             Instruction::Jump(addr) => write!(f, "JUMP {}", addr),
             Instruction::JumpIfFalse(addr) => write!(f, "JUMP_IF_FALSE {}", addr),
+            Instruction::JumpIfTrue(addr) => write!(f, "JUMP_IF_TRUE {}", addr),
+            Instruction::JumpRel(offset) => write!(f, "JUMP_REL {:+}", offset),
+            Instruction::JumpIfFalseRel(offset) => write!(f, "JUMP_IF_FALSE_REL {:+}", offset),
+            Instruction::JumpIfTrueRel(offset) => write!(f, "JUMP_IF_TRUE_REL {:+}", offset),
             Instruction::Print => write!(f, "PRINT"),
+            Instruction::Random => write!(f, "RANDOM"),
             Instruction::Describe(entity, desc) => write!(f, "DESCRIBE {} \"{}\"", entity, desc),
             Instruction::Halt => write!(f, "HALT"),
             Instruction::Nop => write!(f, "NOP"),
@@ -182,6 +277,10 @@ pub struct Bytecode {
     pub instructions: Vec<Instruction>,
     pub constants: Vec<Value>,
     pub entry_point: usize, // Instruction index where execution starts
+    /// Maps function names to their start instruction
+    pub function_table: BTreeMap<String, usize>,
+    /// Pure functions whose results the VM caches per argument values
+    pub memoized_functions: BTreeSet<String>,
 }
 
 impl Bytecode {
@@ -190,6 +289,8 @@ impl Bytecode {
             instructions: Vec::new(),
             constants: Vec::new(),
             entry_point: 0,
+            function_table: BTreeMap::new(),
+            memoized_functions: BTreeSet::new(),
         }
     }
 
@@ -212,6 +313,299 @@ impl Bytecode {
         self.entry_point = index;
     }
 
+    /// Mark the start of a function
+    pub fn mark_function_start(&mut self, name: String, instruction_index: usize) {
+        self.function_table.insert(name, instruction_index);
+    }
+
+    /// Get function start instruction
+    pub fn get_function_start(&self, name: &str) -> Option<usize> {
+        self.function_table.get(name).copied()
+    }
+
+    /// Instructions that make up a function's body
+    ///
+    /// A function ends where the next function, main or the global prologue
+    /// starts, or at the end of the bytecode.
+    pub fn function_range(&self, name: &str) -> Option<Range<usize>> {
+        let start = self.get_function_start(name)?;
+        let end = self
+            .function_table
+            .values()
+            .copied()
+            .chain([self.entry_point])
+            .filter(|&boundary| boundary > start)
+            .min()
+            .unwrap_or(self.len());
+        Some(start..end)
+    }
+
+    /// Remove documentation embedded in the instruction stream
+    ///
+    /// `DESCRIBE` instructions are replaced with `NOP` so every jump address
+    /// stays valid.
+    pub fn strip_docs(&mut self) {
+        for instruction in &mut self.instructions {
+            if matches!(instruction, Instruction::Describe(..)) {
+                *instruction = Instruction::Nop;
+            }
+        }
+    }
+
+    /// Collect every address a jump instruction can transfer control to
+    pub fn jump_targets(&self) -> BTreeSet<usize> {
+        self.instructions
+            .iter()
+            .enumerate()
+            .filter_map(|(address, instruction)| instruction.jump_target(address))
+            .collect()
+    }
+
+    /// Rewrite every jump to use an offset from its own address
+    ///
+    /// Relative code keeps working when it is moved, so placing it after
+    /// other bytecode only needs [`Bytecode::relocate`]. The optimization
+    /// passes work on absolute jumps and convert relative code for their run.
+    pub fn to_relative_jumps(&mut self) {
+        for (address, instruction) in self.instructions.iter_mut().enumerate() {
+            let offset = |target: usize| (target as i64 - address as i64) as i32;
+            *instruction = match *instruction {
+                Instruction::Jump(target) => Instruction::JumpRel(offset(target)),
+                Instruction::JumpIfFalse(target) => Instruction::JumpIfFalseRel(offset(target)),
+                Instruction::JumpIfTrue(target) => Instruction::JumpIfTrueRel(offset(target)),
+                _ => continue,
+            };
+        }
+    }
+
+    /// Rewrite every relative jump to the absolute address it leads to
+    ///
+    /// Jumps whose offset leads before the start of the code are left as
+    /// they are, for [`Bytecode::validate`] to report.
+    pub fn to_absolute_jumps(&mut self) {
+        for (address, instruction) in self.instructions.iter_mut().enumerate() {
+            let Some(target) = instruction.jump_target(address) else {
+                continue;
+            };
+            *instruction = match instruction {
+                Instruction::JumpRel(_) => Instruction::Jump(target),
+                Instruction::JumpIfFalseRel(_) => Instruction::JumpIfFalse(target),
+                Instruction::JumpIfTrueRel(_) => Instruction::JumpIfTrue(target),
+                _ => continue,
+            };
+        }
+    }
+
+    /// Check whether any jump is relative to its own address
+    pub fn has_relative_jumps(&self) -> bool {
+        self.instructions.iter().any(|instruction| {
+            matches!(
+                instruction,
+                Instruction::JumpRel(_)
+                    | Instruction::JumpIfFalseRel(_)
+                    | Instruction::JumpIfTrueRel(_)
+            )
+        })
+    }
+
+    /// Shift every address by `offset`, for code placed after `offset` other instructions
+    ///
+    /// The entry point, function starts and absolute jump targets move;
+    /// relative jumps are left as they are.
+    pub fn relocate(&mut self, offset: usize) {
+        self.entry_point += offset;
+        for start in self.function_table.values_mut() {
+            *start += offset;
+        }
+
+        for instruction in &mut self.instructions {
+            if let Instruction::Jump(target)
+            | Instruction::JumpIfFalse(target)
+            | Instruction::JumpIfTrue(target) = instruction
+            {
+                *target += offset;
+            }
+        }
+    }
+
+    /// Check that every address and constant index refers to something that exists
+    ///
+    /// Catches malformed bytecode (hand-written, or later loaded from a file)
+    /// before it runs: a jump past the end would otherwise silently stop
+    /// execution.
+    pub fn validate(&self) -> ZvarResult<()> {
+        let invalid = |message: String| Err(ZvarError::CodegenError { message });
+
+        if self.entry_point >= self.len() && !self.is_empty() {
+            return invalid(format!(
+                "Entry point {} is outside of {} instructions",
+                self.entry_point,
+                self.len()
+            ));
+        }
+
+        for (name, &start) in &self.function_table {
+            if start >= self.len() {
+                return invalid(format!(
+                    "Function {} starts at {}, outside of {} instructions",
+                    name,
+                    start,
+                    self.len()
+                ));
+            }
+        }
+
+        for (addr, instruction) in self.instructions.iter().enumerate() {
+            match instruction {
+                Instruction::Jump(_)
+                | Instruction::JumpIfFalse(_)
+                | Instruction::JumpIfTrue(_)
+                | Instruction::JumpRel(_)
+                | Instruction::JumpIfFalseRel(_)
+                | Instruction::JumpIfTrueRel(_)
+                    if instruction
+                        .jump_target(addr)
+                        .is_none_or(|target| target >= self.len()) =>
+                {
+                    return invalid(format!(
+                        "{} at {} jumps outside of {} instructions",
+                        instruction,
+                        addr,
+                        self.len()
+                    ));
+                }
+                Instruction::LoadConst(index) if *index as usize >= self.constants.len() => {
+                    return invalid(format!(
+                        "{} at {} refers to a missing constant ({} defined)",
+                        instruction,
+                        addr,
+                        self.constants.len()
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compute the deepest the value stack gets while running this bytecode
+    ///
+    /// Follows every path from the entry point, taking the deeper side of
+    /// each branch. Functions share the caller's stack, so a call adds the
+    /// callee's own peak on top of the caller's values. A loop must leave
+    /// the stack as deep as it found it, otherwise the depth is unbounded
+    /// and an error is returned; the same goes for recursive calls.
+    pub fn max_stack_depth(&self) -> ZvarResult<usize> {
+        self.validate()?;
+        self.region_depth(self.entry_point, &Builtins::new(), &mut BTreeMap::new())
+    }
+
+    /// Peak stack depth of the code reachable from `start`, which begins on an empty stack
+    ///
+    /// `function_depths` caches the peak of every analyzed function; `None`
+    /// marks a function whose analysis is still in progress.
+    fn region_depth(
+        &self,
+        start: usize,
+        builtins: &Builtins,
+        function_depths: &mut BTreeMap<String, Option<usize>>,
+    ) -> ZvarResult<usize> {
+        let invalid = |message: String| Err(ZvarError::CodegenError { message });
+
+        let mut heights = vec![None; self.len()];
+        let mut pending: Vec<(usize, usize)> = vec![(start, 0)];
+        let mut peak = 0;
+
+        while let Some((addr, height)) = pending.pop() {
+            // Running past the last instruction ends execution
+            let Some(instruction) = self.instructions.get(addr) else {
+                continue;
+            };
+
+            match heights[addr] {
+                Some(known) if known == height => continue,
+                Some(known) => {
+                    return invalid(format!(
+                        "Stack depth at {} is {} on one path and {} on another",
+                        addr, known, height
+                    ));
+                }
+                None => heights[addr] = Some(height),
+            }
+
+            let (pops, pushes) = match instruction {
+                Instruction::Call(name, argc) => {
+                    let argc = *argc as usize;
+                    if let Some(returns_value) = builtins.returns_value(name) {
+                        (argc, returns_value as usize)
+                    } else {
+                        let callee_depth = self.function_depth(name, builtins, function_depths)?;
+                        // The callee runs on top of what the caller keeps below its arguments
+                        peak = peak.max(height.saturating_sub(argc) + callee_depth);
+                        (argc, 1)
+                    }
+                }
+                _ => instruction.stack_effect(),
+            };
+
+            if height < pops {
+                return invalid(format!(
+                    "{} at {} needs {} value(s), stack depth is {}",
+                    instruction, addr, pops, height
+                ));
+            }
+            let after = height - pops + pushes;
+            peak = peak.max(after);
+
+            match instruction {
+                Instruction::Halt | Instruction::Return | Instruction::ReturnValue => {}
+                Instruction::Jump(_) | Instruction::JumpRel(_) => {
+                    pending.extend(instruction.jump_target(addr).map(|target| (target, after)));
+                }
+                Instruction::JumpIfFalse(_)
+                | Instruction::JumpIfTrue(_)
+                | Instruction::JumpIfFalseRel(_)
+                | Instruction::JumpIfTrueRel(_) => {
+                    pending.extend(instruction.jump_target(addr).map(|target| (target, after)));
+                    pending.push((addr + 1, after));
+                }
+                _ => pending.push((addr + 1, after)),
+            }
+        }
+
+        Ok(peak)
+    }
+
+    /// Peak stack depth of a function body, analyzed once per function
+    fn function_depth(
+        &self,
+        name: &str,
+        builtins: &Builtins,
+        function_depths: &mut BTreeMap<String, Option<usize>>,
+    ) -> ZvarResult<usize> {
+        match function_depths.get(name) {
+            Some(Some(depth)) => return Ok(*depth),
+            Some(None) => {
+                return Err(ZvarError::CodegenError {
+                    message: format!("Stack depth is unbounded: {} is called recursively", name),
+                });
+            }
+            None => {}
+        }
+
+        let start = self
+            .get_function_start(name)
+            .ok_or_else(|| ZvarError::CodegenError {
+                message: format!("Unknown function: {}", name),
+            })?;
+
+        function_depths.insert(name.to_string(), None);
+        let depth = self.region_depth(start, builtins, function_depths)?;
+        function_depths.insert(name.to_string(), Some(depth));
+        Ok(depth)
+    }
+
     /// Get instruction at index
     pub fn get_instruction(&self, index: usize) -> Option<&Instruction> {
         self.instructions.get(index)
@@ -235,17 +629,136 @@ impl Bytecode {
     /// Disassemble bytecode for debugging
     pub fn disassemble(&self) -> String {
         let mut output = String::new();
-        output.push_str(&format!("=== Bytecode Disassembly ===\n"));
+        output.push_str("=== Bytecode Disassembly ===\n");
+        output.push_str(&format!("Entry point: {}\n", self.entry_point));
+        output.push_str(&format!("Constants: {:?}\n\n", self.constants));
+
+        for (i, instruction) in self.instructions.iter().enumerate() {
+            let marker = if i == self.entry_point { ">" } else { " " };
+            output.push_str(&format!("{} {:04} {}\n", marker, i, instruction));
+        }
+
+        output
+    }
+
+    /// Disassemble bytecode with a header before each function's code
+    ///
+    /// Ranges follow the function table: a function runs from its start up
+    /// to the next start. The global prologue, if any, gets its own header.
+    pub fn disassemble_functions(&self) -> String {
+        let mut starts: Vec<(usize, String)> = self
+            .function_table
+            .iter()
+            .map(|(name, &start)| (start, name.clone()))
+            .collect();
+        if !self.is_empty() && !self.function_table.values().any(|&s| s == self.entry_point) {
+            starts.push((self.entry_point, "global prologue".to_string()));
+        }
+        starts.sort();
+
+        let mut output = String::new();
+        output.push_str("=== Bytecode Disassembly (by function) ===\n");
+        output.push_str(&format!("Entry point: {}\n", self.entry_point));
+        output.push_str(&format!("Constants: {:?}\n", self.constants));
+
+        // Code before the first known start has no owner
+        let first_start = starts.first().map_or(self.len(), |(start, _)| *start);
+        if first_start > 0 {
+            starts.insert(0, (0, "<unnamed>".to_string()));
+        }
+
+        for (i, (start, name)) in starts.iter().enumerate() {
+            let end = starts.get(i + 1).map_or(self.len(), |(next, _)| *next);
+            if end <= *start {
+                continue;
+            }
+
+            output.push_str(&format!("\n-- {} [{:04}-{:04}] --\n", name, start, end - 1));
+            for index in *start..end {
+                let marker = if index == self.entry_point { ">" } else { " " };
+                output.push_str(&format!(
+                    "{} {:04} {}\n",
+                    marker, index, self.instructions[index]
+                ));
+            }
+        }
+
+        output
+    }
+
+    /// Disassemble bytecode with the source line each instruction comes from
+    ///
+    /// A line is shown once, above the first of the consecutive instructions
+    /// generated from it. Instructions without a span get no annotation.
+    pub fn disassemble_annotated(&self, debug_info: &DebugInfo, source: &str) -> String {
+        let lines: Vec<&str> = source.lines().collect();
+
+        let mut output = String::new();
+        output.push_str("=== Bytecode Disassembly (annotated) ===\n");
         output.push_str(&format!("Entry point: {}\n", self.entry_point));
         output.push_str(&format!("Constants: {:?}\n\n", self.constants));
 
+        let mut shown_line = None;
         for (i, instruction) in self.instructions.iter().enumerate() {
+            let line = debug_info
+                .get_instruction_span(i)
+                .map(|span| span.start_line)
+                .filter(|&line| line != 0);
+            if let Some(line) = line.filter(|&line| shown_line != Some(line)) {
+                let text = lines.get(line as usize - 1).map_or("", |text| text.trim());
+                output.push_str(&format!("       ; {:>3} | {}\n", line, text));
+                shown_line = Some(line);
+            }
+
             let marker = if i == self.entry_point { ">" } else { " " };
             output.push_str(&format!("{} {:04} {}\n", marker, i, instruction));
         }
 
         output
     }
+
+    /// Compare two instruction streams and report where they first diverge
+    ///
+    /// Meant for regression tests of codegen and optimization passes, where
+    /// the first differing instruction says more than two full listings.
+    pub fn first_difference(&self, other: &Bytecode) -> Option<InstructionDiff> {
+        let len = self.len().max(other.len());
+        (0..len).find_map(|index| {
+            let left = self.get_instruction(index);
+            let right = other.get_instruction(index);
+            (left != right).then(|| InstructionDiff {
+                index,
+                left: left.cloned(),
+                right: right.cloned(),
+            })
+        })
+    }
+}
+
+/// First position at which two bytecodes differ
+///
+/// A side is `None` when its instruction stream ended before `index`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstructionDiff {
+    pub index: usize,
+    pub left: Option<Instruction>,
+    pub right: Option<Instruction>,
+}
+
+impl fmt::Display for InstructionDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |instruction: &Option<Instruction>| match instruction {
+            Some(instruction) => instruction.to_string(),
+            None => "<end>".to_string(),
+        };
+        write!(
+            f,
+            "instruction {:04} differs: {} vs {}",
+            self.index,
+            show(&self.left),
+            show(&self.right)
+        )
+    }
 }
 
 impl Default for Bytecode {
@@ -280,6 +793,9 @@ mod tests {
 
         let inst = Instruction::JumpIfFalse(42);
         assert_eq!(inst.to_string(), "JUMP_IF_FALSE 42");
+
+        let inst = Instruction::JumpIfTrue(7);
+        assert_eq!(inst.to_string(), "JUMP_IF_TRUE 7");
     }
 
     #[test]
@@ -348,6 +864,41 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_validate() {
+        let mut bytecode = Bytecode::new();
+        bytecode.add_constant(Value::Int(7));
+        bytecode.emit(Instruction::LoadConst(0));
+        bytecode.emit(Instruction::JumpIfFalse(3));
+        bytecode.emit(Instruction::Nop);
+        bytecode.emit(Instruction::Halt);
+        assert!(bytecode.validate().is_ok());
+
+        let mut bad_jump = bytecode.clone();
+        bad_jump.instructions[1] = Instruction::JumpIfFalse(4);
+        let Err(ZvarError::CodegenError { message }) = bad_jump.validate() else {
+            panic!("Expected a bad jump target to be rejected");
+        };
+        assert_eq!(
+            message,
+            "JUMP_IF_FALSE 4 at 1 jumps outside of 4 instructions"
+        );
+
+        let mut bad_constant = bytecode.clone();
+        bad_constant.instructions[0] = Instruction::LoadConst(1);
+        let Err(ZvarError::CodegenError { message }) = bad_constant.validate() else {
+            panic!("Expected a bad constant index to be rejected");
+        };
+        assert_eq!(
+            message,
+            "LOADCONST c$1 at 0 refers to a missing constant (1 defined)"
+        );
+
+        let mut bad_entry = bytecode;
+        bad_entry.set_entry_point(4);
+        assert!(bad_entry.validate().is_err());
+    }
+
     #[test]
     fn test_disassembly() {
         let mut bytecode = Bytecode::new();
@@ -362,4 +913,251 @@ mod tests {
         assert!(disasm.contains("OR"));
         assert!(disasm.contains("Entry point: 0"));
     }
+
+    #[test]
+    fn test_disassemble_functions() {
+        let source = r#"
+        fn f$0(v$0 int) -> int {
+            ret v$0 + 1;
+        }
+
+        fn f$1(v$0 int) -> int {
+            ret v$0 * 2;
+        }
+
+        main {
+            print(f$1(f$0(1)));
+        }
+        "#;
+
+        let (bytecode, _) = crate::compile_source(source).unwrap();
+        let disasm = bytecode.disassemble_functions();
+
+        let f0 = disasm.find("-- f$0 [0000-").unwrap();
+        let f1 = disasm.find("-- f$1 [").unwrap();
+        let main = disasm.find("-- main [").unwrap();
+        assert!(f0 < f1 && f1 < main);
+
+        // Every instruction is listed exactly once
+        let listed = disasm
+            .lines()
+            .filter(|line| line.len() > 6 && line[2..6].chars().all(|c| c.is_ascii_digit()))
+            .count();
+        assert_eq!(listed, bytecode.len());
+    }
+
+    #[test]
+    fn test_relative_jump_conversion() {
+        let mut bytecode = Bytecode::new();
+        bytecode.emit(Instruction::Push(Value::Bool(true)));
+        bytecode.emit(Instruction::JumpIfFalse(3));
+        bytecode.emit(Instruction::Jump(0));
+        bytecode.emit(Instruction::Halt);
+
+        let mut relative = bytecode.clone();
+        relative.to_relative_jumps();
+        assert_eq!(relative.instructions[1], Instruction::JumpIfFalseRel(2));
+        assert_eq!(relative.instructions[2], Instruction::JumpRel(-2));
+        assert_eq!(relative.instructions[2].to_string(), "JUMP_REL -2");
+        assert_eq!(relative.jump_targets(), bytecode.jump_targets());
+        assert!(relative.has_relative_jumps());
+
+        let mut absolute = relative.clone();
+        absolute.to_absolute_jumps();
+        assert_eq!(absolute.instructions, bytecode.instructions);
+        assert!(!absolute.has_relative_jumps());
+
+        // Absolute targets move with the code, relative ones don't need to
+        bytecode.relocate(5);
+        assert_eq!(bytecode.instructions[1], Instruction::JumpIfFalse(8));
+        relative.relocate(5);
+        assert_eq!(relative.instructions[2], Instruction::JumpRel(-2));
+        assert_eq!(relative.entry_point, 5);
+
+        let mut before_start = Bytecode::new();
+        before_start.emit(Instruction::JumpRel(-1));
+        assert!(before_start.validate().is_err());
+    }
+
+    #[test]
+    fn test_disassemble_annotated() {
+        let source = "main {\n    int v$0 = 1 + 2;\n    print(v$0);\n}";
+
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+        let disasm = bytecode.disassemble_annotated(&debug_info, source);
+
+        let declaration = disasm.find(";   2 | int v$0 = 1 + 2;").unwrap();
+        let print = disasm.find(";   3 | print(v$0);").unwrap();
+        assert!(declaration < disasm.find("STOREVAR v$0").unwrap());
+        assert!(disasm.find("STOREVAR v$0").unwrap() < print);
+        assert!(print < disasm.find("PRINT").unwrap());
+
+        // Each line is shown once, however many instructions it produced
+        assert_eq!(disasm.matches("| int v$0").count(), 1);
+    }
+
+    #[test]
+    fn test_first_difference() {
+        let mut bytecode = Bytecode::new();
+        bytecode.emit(Instruction::LoadVar(0));
+        bytecode.emit(Instruction::Print);
+        bytecode.emit(Instruction::Halt);
+        assert_eq!(bytecode.first_difference(&bytecode.clone()), None);
+
+        let mut changed = bytecode.clone();
+        changed.instructions[1] = Instruction::StoreVar(0);
+        let diff = bytecode.first_difference(&changed).unwrap();
+        assert_eq!(diff.index, 1);
+        assert_eq!(diff.left, Some(Instruction::Print));
+        assert_eq!(diff.right, Some(Instruction::StoreVar(0)));
+        assert_eq!(
+            diff.to_string(),
+            "instruction 0001 differs: PRINT vs STOREVAR v$0"
+        );
+
+        let mut shorter = bytecode.clone();
+        shorter.instructions.pop();
+        assert_eq!(
+            bytecode.first_difference(&shorter).unwrap().to_string(),
+            "instruction 0002 differs: HALT vs <end>"
+        );
+    }
+
+    /// Bytecode made of the given instructions, starting at the first
+    fn bytecode_of(instructions: Vec<Instruction>) -> Bytecode {
+        let mut bytecode = Bytecode::new();
+        for instruction in instructions {
+            bytecode.emit(instruction);
+        }
+        bytecode
+    }
+
+    #[test]
+    fn test_max_stack_depth_straight_line() {
+        let bytecode = bytecode_of(vec![
+            Instruction::Push(Value::Int(1)),
+            Instruction::Push(Value::Int(2)),
+            Instruction::Push(Value::Int(3)),
+            Instruction::Mul,
+            Instruction::Add,
+            Instruction::Dup,
+            Instruction::Print,
+            Instruction::Print,
+            Instruction::Halt,
+        ]);
+        assert_eq!(bytecode.max_stack_depth().unwrap(), 3);
+        assert_eq!(Bytecode::new().max_stack_depth().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_max_stack_depth_takes_deeper_branch() {
+        let bytecode = bytecode_of(vec![
+            Instruction::LoadVar(0),          // 0
+            Instruction::JumpIfFalse(5),      // 1
+            Instruction::Push(Value::Int(1)), // 2
+            Instruction::Print,               // 3
+            Instruction::Jump(11),            // 4
+            Instruction::Push(Value::Int(1)), // 5
+            Instruction::Push(Value::Int(2)), // 6
+            Instruction::Push(Value::Int(3)), // 7
+            Instruction::Add,                 // 8
+            Instruction::Add,                 // 9
+            Instruction::Print,               // 10
+            Instruction::Halt,                // 11
+        ]);
+        assert_eq!(bytecode.max_stack_depth().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_max_stack_depth_loops() {
+        // while (v$0 < 10) { v$0 = v$0 + 1; }
+        let balanced = bytecode_of(vec![
+            Instruction::LoadVar(0),           // 0
+            Instruction::Push(Value::Int(10)), // 1
+            Instruction::Less,                 // 2
+            Instruction::JumpIfFalse(6),       // 3
+            Instruction::IncVar(0, 1),         // 4
+            Instruction::Jump(0),              // 5
+            Instruction::Halt,                 // 6
+        ]);
+        assert_eq!(balanced.max_stack_depth().unwrap(), 2);
+
+        // Every iteration leaves one more value behind
+        let growing = bytecode_of(vec![
+            Instruction::Push(Value::Int(1)), // 0
+            Instruction::Jump(0),             // 1
+        ]);
+        assert!(matches!(
+            growing.max_stack_depth(),
+            Err(ZvarError::CodegenError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_max_stack_depth_adds_callee_depth() {
+        let mut bytecode = bytecode_of(vec![
+            // f$0(v$0): ret v$0 * (v$0 + 1)
+            Instruction::LoadVar(0),          // 0
+            Instruction::LoadVar(0),          // 1
+            Instruction::Push(Value::Int(1)), // 2
+            Instruction::Add,                 // 3
+            Instruction::Mul,                 // 4
+            Instruction::ReturnValue,         // 5
+            // main: print(1 + f$0(2))
+            Instruction::Push(Value::Int(1)),          // 6
+            Instruction::Push(Value::Int(2)),          // 7
+            Instruction::Call("f$0".to_string(), 1),   // 8
+            Instruction::Add,                          // 9
+            Instruction::Call("print".to_string(), 1), // 10
+            Instruction::Halt,                         // 11
+        ]);
+        bytecode.mark_function_start("f$0".to_string(), 0);
+        bytecode.set_entry_point(6);
+
+        // 1 from main below the call, 3 inside f$0
+        assert_eq!(bytecode.max_stack_depth().unwrap(), 4);
+
+        let mut recursive = bytecode_of(vec![
+            Instruction::LoadVar(0),                 // 0
+            Instruction::Call("f$0".to_string(), 1), // 1
+            Instruction::ReturnValue,                // 2
+            Instruction::Push(Value::Int(1)),        // 3
+            Instruction::Call("f$0".to_string(), 1), // 4
+            Instruction::Halt,                       // 5
+        ]);
+        recursive.mark_function_start("f$0".to_string(), 0);
+        recursive.set_entry_point(3);
+        assert!(recursive.max_stack_depth().is_err());
+    }
+
+    #[test]
+    fn test_max_stack_depth_underflow() {
+        let bytecode = bytecode_of(vec![Instruction::Push(Value::Int(1)), Instruction::Add]);
+        assert!(matches!(
+            bytecode.max_stack_depth(),
+            Err(ZvarError::CodegenError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_max_stack_depth_of_generated_code() {
+        let source = r#"
+        fn f$0(v$0 int) -> int {
+            ret v$0 * 2;
+        }
+
+        main {
+            int v$0 = 0;
+            while (v$0 < 3 && v$0 != 7) {
+                match (v$0) {
+                    1 => { print(f$0(v$0)); }
+                    _ => { print(v$0 > 0 ? 1 : 0); }
+                }
+                v$0 = v$0 + 1;
+            }
+        }
+        "#;
+        let (bytecode, _) = crate::compile_source(source).unwrap();
+        assert!(bytecode.max_stack_depth().unwrap() >= 2);
+    }
 }
@@ -1,26 +1,43 @@
 //! Code generation from AST to bytecode
 
+pub mod cfg;
+pub mod const_eval;
 pub mod debug_info;
 pub mod instruction;
+pub mod optimize;
 
 use crate::{
     error::{ZvarError, ZvarResult},
     parser::ast::*,
+    prelude::*,
     symbol_table::SymbolTable,
+    vm::builtins::Builtins,
 };
 
 use debug_info::DebugInfo;
 use instruction::{Bytecode, Instruction, Value};
-use std::collections::HashMap;
 
 /// Code generator that converts AST to bytecode
 pub struct CodeGenerator {
     bytecode: Bytecode,
     debug_info: DebugInfo,
     // Maps entity names to their runtime locations
-    variable_slots: HashMap<String, u32>,
-    constant_values: HashMap<String, Value>,
+    variable_slots: BTreeMap<String, u32>,
+    // Compile-time values of the constants in the body being generated
+    constant_values: BTreeMap<String, Value>,
     next_variable_slot: u32,
+    // Top-level variables, numbered separately from the local slots above
+    global_slots: BTreeMap<String, u32>,
+    // User functions whose call leaves a return value on the stack
+    value_functions: BTreeSet<String>,
+    // Store folded constants in slots as well, for inputs compiled later
+    keep_constant_slots: bool,
+    // Addresses of the `break` jumps of every enclosing loop, innermost last
+    break_jumps: Vec<Vec<usize>>,
+    // Tells which builtin calls leave a result behind
+    builtins: Builtins,
+    // Emit jumps as offsets from their own address
+    relative_jumps: bool,
 }
 
 impl CodeGenerator {
@@ -28,12 +45,39 @@ impl CodeGenerator {
         CodeGenerator {
             bytecode: Bytecode::new(),
             debug_info: DebugInfo::new(),
-            variable_slots: HashMap::new(),
-            constant_values: HashMap::new(),
+            variable_slots: BTreeMap::new(),
+            constant_values: BTreeMap::new(),
             next_variable_slot: 0,
+            global_slots: BTreeMap::new(),
+            value_functions: BTreeSet::new(),
+            keep_constant_slots: false,
+            break_jumps: Vec::new(),
+            builtins: Builtins::new(),
+            relative_jumps: false,
         }
     }
 
+    /// Create a code generator that reuses previously assigned variable slots
+    ///
+    /// Entities already present in `variable_slots` keep their slot, and new
+    /// entities are numbered after the highest existing slot. Constants are
+    /// always stored, since a later input may load them from their slot.
+    pub fn with_variable_slots(variable_slots: BTreeMap<String, u32>) -> Self {
+        let next_variable_slot = variable_slots.values().max().map_or(0, |max| max + 1);
+
+        CodeGenerator {
+            variable_slots,
+            next_variable_slot,
+            keep_constant_slots: true,
+            ..Self::new()
+        }
+    }
+
+    /// Get the variable slot assignments made so far
+    pub fn variable_slots(&self) -> &BTreeMap<String, u32> {
+        &self.variable_slots
+    }
+
     /// Generate bytecode from a program
     pub fn generate(
         &mut self,
@@ -53,17 +97,69 @@ impl CodeGenerator {
                     // Main block is the entry point
                     let start_index = self.bytecode.len();
                     self.bytecode.set_entry_point(start_index);
-                    self.debug_info
+                    self.bytecode
                         .mark_function_start("main".to_string(), start_index);
 
+                    self.constant_values = const_eval::evaluate_constants(&main.body)?;
                     self.generate_block(&main.body)?;
 
                     // End main with halt
                     self.emit_with_span(Instruction::Halt, main.span);
                 }
+                Item::TypeAlias(_) | Item::GlobalVariable(_) => {
+                    // Type aliases produce no code; globals are initialized in the prologue
+                }
             }
         }
 
+        // Without a main block there is nothing to run, but the entry point
+        // must not fall into the first function body, and the global
+        // prologue needs a halt to jump to
+        let has_main = program
+            .items
+            .iter()
+            .any(|item| matches!(item, Item::MainBlock(_)));
+        if !has_main && (!self.bytecode.is_empty() || !self.global_slots.is_empty()) {
+            let start_index = self.bytecode.len();
+            self.bytecode.set_entry_point(start_index);
+            self.emit_with_span(Instruction::Halt, program.span);
+        }
+
+        self.generate_global_prologue(program)?;
+
+        if self.relative_jumps {
+            self.bytecode.to_relative_jumps();
+        }
+
+        Ok((self.bytecode.clone(), self.debug_info.clone()))
+    }
+
+    /// Emit position-independent jumps (`JUMP_REL` and friends)
+    ///
+    /// The generated code can then be moved with [`Bytecode::relocate`]
+    /// without rewriting any jump. Optimization passes need absolute jumps,
+    /// so leave this off when the bytecode is optimized afterwards.
+    pub fn set_relative_jumps(&mut self, relative: bool) {
+        self.relative_jumps = relative;
+    }
+
+    /// Generate code for a single expression, leaving its value on the stack
+    ///
+    /// Used by the REPL to show the value of a bare expression. Slots come
+    /// from [`CodeGenerator::with_variable_slots`] as for a full input.
+    pub fn generate_expression_only(
+        &mut self,
+        expr: &Expression,
+    ) -> ZvarResult<(Bytecode, DebugInfo)> {
+        self.collect_from_expression(expr)?;
+        self.bytecode.set_entry_point(self.bytecode.len());
+        self.generate_expression(expr)?;
+        self.emit_with_span(Instruction::Halt, expr.span());
+
+        if self.relative_jumps {
+            self.bytecode.to_relative_jumps();
+        }
+
         Ok((self.bytecode.clone(), self.debug_info.clone()))
     }
 
@@ -73,27 +169,32 @@ impl CodeGenerator {
         program: &Program,
         symbol_table: &SymbolTable,
     ) -> ZvarResult<()> {
+        // Globals get their own slots, in declaration order
+        for item in &program.items {
+            if let Item::GlobalVariable(var_decl) = item {
+                let slot = self.global_slots.len() as u32;
+                self.global_slots.insert(var_decl.name.clone(), slot);
+            }
+        }
+
         // Collect from symbol table
         for (name, symbol) in symbol_table.all_symbols() {
             match &symbol.entity_type {
                 crate::symbol_table::EntityType::Variable { .. } => {
                     // Assign a runtime slot for variables
                     if name.starts_with("v$") {
-                        let slot = self.next_variable_slot;
-                        self.variable_slots.insert(name.clone(), slot);
-                        self.next_variable_slot += 1;
+                        self.assign_slot(name);
                     }
                 }
                 crate::symbol_table::EntityType::Constant { .. } => {
-                    // Constants need slots too for now (we could optimize this later)
-                    if name.starts_with("c$") {
-                        let slot = self.next_variable_slot;
-                        self.variable_slots.insert(name.clone(), slot);
-                        self.next_variable_slot += 1;
+                    // Otherwise constants get a slot only if they are stored at runtime
+                    if name.starts_with("c$") && self.keep_constant_slots {
+                        self.assign_slot(name);
                     }
                 }
                 crate::symbol_table::EntityType::Function { .. } => {
-                    // Functions are handled separately
+                    // Every user function declares a return type, so its call pushes a value
+                    self.value_functions.insert(name.clone());
                 }
             }
 
@@ -117,21 +218,61 @@ impl CodeGenerator {
                     self.collect_from_block(&func.body)?;
                     // Also collect function parameters
                     for param in &func.params {
-                        if !self.variable_slots.contains_key(&param.name) {
-                            let slot = self.next_variable_slot;
-                            self.variable_slots.insert(param.name.clone(), slot);
-                            self.next_variable_slot += 1;
-                        }
+                        self.assign_slot(&param.name);
                     }
                 }
                 Item::MainBlock(main) => {
                     self.collect_from_block(&main.body)?;
                 }
+                Item::GlobalVariable(var_decl) => {
+                    if let Some(init) = &var_decl.initializer {
+                        self.collect_from_expression(init)?;
+                    }
+                }
+                Item::TypeAlias(_) => {}
             }
         }
         Ok(())
     }
 
+    /// Give a local entity the next free slot unless it already has one (or is global)
+    fn assign_slot(&mut self, name: &str) {
+        if !self.variable_slots.contains_key(name) && !self.global_slots.contains_key(name) {
+            self.variable_slots
+                .insert(name.to_string(), self.next_variable_slot);
+            self.next_variable_slot += 1;
+        }
+    }
+
+    /// Emit the initializers of all globals, then jump to main
+    ///
+    /// The prologue is placed after all other code and becomes the entry
+    /// point, so globals hold their values before main's first statement runs.
+    /// Programs without globals get no prologue.
+    fn generate_global_prologue(&mut self, program: &Program) -> ZvarResult<()> {
+        if self.global_slots.is_empty() {
+            return Ok(());
+        }
+
+        let main_start = self.bytecode.entry_point;
+        let prologue_start = self.bytecode.len();
+        self.constant_values.clear();
+
+        for item in &program.items {
+            if let Item::GlobalVariable(var_decl) = item {
+                if let Some(init) = &var_decl.initializer {
+                    self.generate_expression(init)?;
+                    let slot = self.global_slots[&var_decl.name];
+                    self.emit_with_span(Instruction::StoreGlobal(slot), var_decl.span);
+                }
+            }
+        }
+
+        self.emit_with_span(Instruction::Jump(main_start), program.span);
+        self.bytecode.set_entry_point(prologue_start);
+        Ok(())
+    }
+
     /// Collect variables from a block
     fn collect_from_block(&mut self, block: &Block) -> ZvarResult<()> {
         for stmt in &block.statements {
@@ -144,29 +285,19 @@ impl CodeGenerator {
     fn collect_from_statement(&mut self, stmt: &Statement) -> ZvarResult<()> {
         match stmt {
             Statement::VariableDeclaration(var_decl) => {
-                if !self.variable_slots.contains_key(&var_decl.name) {
-                    let slot = self.next_variable_slot;
-                    self.variable_slots.insert(var_decl.name.clone(), slot);
-                    self.next_variable_slot += 1;
-                }
+                self.assign_slot(&var_decl.name);
                 if let Some(init) = &var_decl.initializer {
                     self.collect_from_expression(init)?;
                 }
             }
             Statement::ConstantDeclaration(const_decl) => {
-                if !self.variable_slots.contains_key(&const_decl.name) {
-                    let slot = self.next_variable_slot;
-                    self.variable_slots.insert(const_decl.name.clone(), slot);
-                    self.next_variable_slot += 1;
+                if self.keep_constant_slots {
+                    self.assign_slot(&const_decl.name);
                 }
                 self.collect_from_expression(&const_decl.initializer)?;
             }
             Statement::Assignment(assignment) => {
-                if !self.variable_slots.contains_key(&assignment.target) {
-                    let slot = self.next_variable_slot;
-                    self.variable_slots.insert(assignment.target.clone(), slot);
-                    self.next_variable_slot += 1;
-                }
+                self.assign_slot(&assignment.target);
                 self.collect_from_expression(&assignment.value)?;
             }
             Statement::Return(ret) => {
@@ -177,8 +308,8 @@ impl CodeGenerator {
             Statement::ExpressionStatement(expr) => {
                 self.collect_from_expression(expr)?;
             }
-            Statement::Describe(_) => {
-                // Nothing to collect from describe statements
+            Statement::Describe(_) | Statement::Break(_) | Statement::Empty(_) => {
+                // Nothing to collect from describe, break or empty statements
             }
             Statement::If(if_stmt) => {
                 self.collect_from_expression(&if_stmt.condition)?;
@@ -187,6 +318,19 @@ impl CodeGenerator {
                     self.collect_from_block(else_block)?;
                 }
             }
+            Statement::While(while_stmt) => {
+                self.collect_from_expression(&while_stmt.condition)?;
+                self.collect_from_block(&while_stmt.body)?;
+                if let Some(else_block) = &while_stmt.else_block {
+                    self.collect_from_block(else_block)?;
+                }
+            }
+            Statement::Match(match_stmt) => {
+                self.collect_from_expression(&match_stmt.scrutinee)?;
+                for arm in &match_stmt.arms {
+                    self.collect_from_block(&arm.body)?;
+                }
+            }
         }
         Ok(())
     }
@@ -195,11 +339,7 @@ impl CodeGenerator {
     fn collect_from_expression(&mut self, expr: &Expression) -> ZvarResult<()> {
         match expr {
             Expression::Variable(var) => {
-                if !self.variable_slots.contains_key(&var.name) {
-                    let slot = self.next_variable_slot;
-                    self.variable_slots.insert(var.name.clone(), slot);
-                    self.next_variable_slot += 1;
-                }
+                self.assign_slot(&var.name);
             }
             Expression::Binary(binary) => {
                 self.collect_from_expression(&binary.left)?;
@@ -217,6 +357,15 @@ impl CodeGenerator {
                     self.collect_from_expression(arg)?;
                 }
             }
+            Expression::Conditional(cond) => {
+                self.collect_from_expression(&cond.condition)?;
+                self.collect_from_expression(&cond.then_expr)?;
+                self.collect_from_expression(&cond.else_expr)?;
+            }
+            Expression::Assignment(assign) => {
+                self.assign_slot(&assign.target);
+                self.collect_from_expression(&assign.value)?;
+            }
             Expression::Integer(_) => {
                 // Nothing to collect from integer literals
             }
@@ -233,10 +382,14 @@ impl CodeGenerator {
     /// Generate code for a function
     fn generate_function(&mut self, func: &Function) -> ZvarResult<()> {
         let start_index = self.bytecode.len();
-        self.debug_info
+        self.bytecode
             .mark_function_start(func.name.clone(), start_index);
+        if func.is_memoized {
+            self.bytecode.memoized_functions.insert(func.name.clone());
+        }
 
         // Generate function body
+        self.constant_values = const_eval::evaluate_constants(&func.body)?;
         self.generate_block(&func.body)?;
 
         // If no explicit return, add implicit return
@@ -250,6 +403,26 @@ impl CodeGenerator {
         Ok(())
     }
 
+    /// Match `v$N = v$N + <literal>` (or `- <literal>`), returning the amount to add
+    fn increment_amount(assignment: &Assignment) -> Option<i64> {
+        let Expression::Binary(binary) = &assignment.value else {
+            return None;
+        };
+
+        match (&*binary.left, &*binary.right) {
+            (Expression::Variable(var), Expression::Integer(int))
+                if var.name == assignment.target =>
+            {
+                match binary.operator {
+                    BinaryOperator::Add => Some(int.value),
+                    BinaryOperator::Subtract => int.value.checked_neg(),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
     /// Generate code for a block
     fn generate_block(&mut self, block: &Block) -> ZvarResult<()> {
         for statement in &block.statements {
@@ -262,12 +435,9 @@ impl CodeGenerator {
     fn generate_statement(&mut self, stmt: &Statement) -> ZvarResult<()> {
         match stmt {
             Statement::If(if_stmt) => {
-                // Generate condition
-                self.generate_expression(&if_stmt.condition)?;
-
-                // Jump to else block if condition is false
-                let else_jump = self.bytecode.len();
-                self.emit_with_span(Instruction::JumpIfFalse(0), if_stmt.span); // Placeholder address
+                // Jump to else block if condition is false, deciding logical
+                // conditions without materializing their boolean
+                let else_jumps = self.generate_condition_jumps(&if_stmt.condition, false)?;
 
                 // Generate then block
                 self.generate_block(&if_stmt.then_block)?;
@@ -277,13 +447,8 @@ impl CodeGenerator {
                     let end_jump = self.bytecode.len();
                     self.emit_with_span(Instruction::Jump(0), if_stmt.span); // Placeholder address
 
-                    // Update else jump to point here
-                    let else_target = self.bytecode.len();
-                    if let Some(Instruction::JumpIfFalse(ref mut addr)) =
-                        self.bytecode.instructions.get_mut(else_jump)
-                    {
-                        *addr = else_target;
-                    }
+                    // Update else jumps to point here
+                    self.patch_condition_jumps(&else_jumps);
 
                     // Generate else block
                     self.generate_block(else_block)?;
@@ -296,16 +461,117 @@ impl CodeGenerator {
                         *addr = end_target;
                     }
                 } else {
-                    // No else block, just update the jump to point to end
-                    let end_target = self.bytecode.len();
+                    // No else block, just update the jumps to point to end
+                    self.patch_condition_jumps(&else_jumps);
+                }
+            }
+
+            Statement::Match(match_stmt) => {
+                // The scrutinee stays on the stack while the arms are tested
+                self.generate_expression(&match_stmt.scrutinee)?;
+
+                let mut end_jumps = Vec::new();
+                let mut wildcard = None;
+                for arm in &match_stmt.arms {
+                    let MatchPattern::Integer(value) = arm.pattern else {
+                        wildcard = Some(arm);
+                        continue;
+                    };
+
+                    self.emit_with_span(Instruction::Dup, arm.span);
+                    self.emit_with_span(Instruction::Push(Value::Int(value)), arm.span);
+                    self.emit_with_span(Instruction::Equal, arm.span);
+                    let next_arm_jump = self.bytecode.len();
+                    self.emit_with_span(Instruction::JumpIfFalse(0), arm.span); // Placeholder address
+
+                    // Matched: drop the scrutinee, run the arm and skip the rest
+                    self.emit_with_span(Instruction::Pop, arm.span);
+                    self.generate_block(&arm.body)?;
+                    end_jumps.push(self.bytecode.len());
+                    self.emit_with_span(Instruction::Jump(0), arm.span); // Placeholder address
+
+                    let next_arm = self.bytecode.len();
                     if let Some(Instruction::JumpIfFalse(ref mut addr)) =
-                        self.bytecode.instructions.get_mut(else_jump)
+                        self.bytecode.instructions.get_mut(next_arm_jump)
+                    {
+                        *addr = next_arm;
+                    }
+                }
+
+                // No literal matched: drop the scrutinee and run the wildcard arm, if any
+                self.emit_with_span(Instruction::Pop, match_stmt.span);
+                if let Some(arm) = wildcard {
+                    self.generate_block(&arm.body)?;
+                }
+
+                let end_target = self.bytecode.len();
+                for end_jump in end_jumps {
+                    if let Some(Instruction::Jump(ref mut addr)) =
+                        self.bytecode.instructions.get_mut(end_jump)
                     {
                         *addr = end_target;
                     }
                 }
             }
 
+            Statement::While(while_stmt) => {
+                // The condition is tested once up front, so a loop whose body
+                // never runs can branch to the else block:
+                //
+                //     <condition>; JUMP_IF_FALSE else
+                //     body: <body>; <condition>; JUMP_IF_TRUE body; JUMP end
+                //     else: <else block>
+                //     end:
+                self.generate_expression(&while_stmt.condition)?;
+                let skip_jump = self.bytecode.len();
+                self.emit_with_span(Instruction::JumpIfFalse(0), while_stmt.span); // Placeholder address
+
+                let body_start = self.bytecode.len();
+                self.break_jumps.push(Vec::new());
+                self.generate_block(&while_stmt.body)?;
+                let break_jumps = self.break_jumps.pop().unwrap_or_default();
+
+                self.generate_expression(&while_stmt.condition)?;
+                self.emit_with_span(Instruction::JumpIfTrue(body_start), while_stmt.span);
+
+                let mut end_jumps = break_jumps;
+                if let Some(else_block) = &while_stmt.else_block {
+                    end_jumps.push(self.bytecode.len());
+                    self.emit_with_span(Instruction::Jump(0), while_stmt.span); // Placeholder address
+
+                    let else_target = self.bytecode.len();
+                    if let Some(Instruction::JumpIfFalse(ref mut addr)) =
+                        self.bytecode.instructions.get_mut(skip_jump)
+                    {
+                        *addr = else_target;
+                    }
+                    self.generate_block(else_block)?;
+                } else {
+                    end_jumps.push(skip_jump);
+                }
+
+                let end_target = self.bytecode.len();
+                for end_jump in end_jumps {
+                    if let Some(
+                        Instruction::Jump(ref mut addr) | Instruction::JumpIfFalse(ref mut addr),
+                    ) = self.bytecode.instructions.get_mut(end_jump)
+                    {
+                        *addr = end_target;
+                    }
+                }
+            }
+
+            Statement::Break(span) => {
+                let jump = self.bytecode.len();
+                self.emit_with_span(Instruction::Jump(0), *span); // Placeholder address
+                match self.break_jumps.last_mut() {
+                    Some(jumps) => jumps.push(jump),
+                    None => {
+                        return Err(ZvarError::BreakOutsideLoop { span: *span });
+                    }
+                }
+            }
+
             Statement::VariableDeclaration(var_decl) => {
                 if let Some(init) = &var_decl.initializer {
                     // Generate initializer expression
@@ -323,10 +589,17 @@ impl CodeGenerator {
             }
 
             Statement::ConstantDeclaration(const_decl) => {
+                // Folded constants are pushed inline wherever they are used
+                if self.constant_values.contains_key(&const_decl.name) && !self.keep_constant_slots
+                {
+                    return Ok(());
+                }
+
                 // Generate initializer expression
                 self.generate_expression(&const_decl.initializer)?;
 
                 // Store in variable slot (constants use same mechanism as variables)
+                self.assign_slot(&const_decl.name);
                 if let Some(&slot) = self.variable_slots.get(&const_decl.name) {
                     self.emit_with_span(Instruction::StoreVar(slot), const_decl.span);
                 } else {
@@ -337,16 +610,27 @@ impl CodeGenerator {
             }
 
             Statement::Assignment(assignment) => {
-                // Generate value expression
-                self.generate_expression(&assignment.value)?;
-
-                // Store in variable slot
-                if let Some(&slot) = self.variable_slots.get(&assignment.target) {
-                    self.emit_with_span(Instruction::StoreVar(slot), assignment.span);
+                if let Some(&slot) = self.global_slots.get(&assignment.target) {
+                    // Globals live outside the local slots, so IncVar does not apply
+                    self.generate_expression(&assignment.value)?;
+                    self.emit_with_span(Instruction::StoreGlobal(slot), assignment.span);
                 } else {
-                    return Err(ZvarError::CodegenError {
-                        message: format!("Variable {} not found in slots", assignment.target),
-                    });
+                    let Some(&slot) = self.variable_slots.get(&assignment.target) else {
+                        return Err(ZvarError::CodegenError {
+                            message: format!("Variable {} not found in slots", assignment.target),
+                        });
+                    };
+
+                    if let Some(amount) = Self::increment_amount(assignment) {
+                        // `v$N = v$N + <literal>` updates the slot in place
+                        self.emit_with_span(Instruction::IncVar(slot, amount), assignment.span);
+                    } else {
+                        // Generate value expression
+                        self.generate_expression(&assignment.value)?;
+
+                        // Store in variable slot
+                        self.emit_with_span(Instruction::StoreVar(slot), assignment.span);
+                    }
                 }
             }
 
@@ -359,6 +643,10 @@ impl CodeGenerator {
                 }
             }
 
+            Statement::Empty(_) => {
+                // Empty statements are no-ops
+            }
+
             Statement::Describe(desc) => {
                 // Generate describe instruction for runtime
                 let instruction =
@@ -368,23 +656,17 @@ impl CodeGenerator {
 
             Statement::ExpressionStatement(expr) => {
                 self.generate_expression(expr)?;
-                // Only pop the result if it's not a function call that consumes its arguments
-                // For example, print() already consumes its argument, so we don't need to pop
-                match expr {
-                    Expression::FunctionCall(call) => {
-                        // Built-in functions like print() handle their own stack management
-                        if call.name == "print" {
-                            // print() consumes its argument, no need to pop
-                        } else {
-                            // User-defined functions might leave a return value on the stack
-                            // For now, we'll pop it since expression statements don't use the result
-                            self.emit_with_span(Instruction::Pop, expr.span());
-                        }
-                    }
-                    _ => {
-                        // Other expressions leave their result on the stack, so we need to pop it
-                        self.emit_with_span(Instruction::Pop, expr.span());
-                    }
+                // Discard the result; void builtins like print() leave nothing behind
+                let leaves_value = match expr {
+                    Expression::FunctionCall(call) => match self.builtins.returns_value(&call.name)
+                    {
+                        Some(returns_value) => returns_value,
+                        None => self.value_functions.contains(&call.name),
+                    },
+                    _ => true,
+                };
+                if leaves_value {
+                    self.emit_with_span(Instruction::Pop, expr.span());
                 }
             }
         }
@@ -394,6 +676,10 @@ impl CodeGenerator {
 
     /// Generate code for an expression
     fn generate_expression(&mut self, expr: &Expression) -> ZvarResult<()> {
+        if let Some(simplified) = const_eval::simplify_boolean(expr, &self.constant_values) {
+            return self.generate_expression(&simplified);
+        }
+
         match expr {
             Expression::Integer(int_lit) => {
                 let value = Value::Int(int_lit.value);
@@ -412,7 +698,12 @@ impl CodeGenerator {
             }
 
             Expression::Variable(var) => {
-                if let Some(&slot) = self.variable_slots.get(&var.name) {
+                if let Some(value) = self.constant_values.get(&var.name) {
+                    // Constants known at compile time are pushed directly
+                    self.emit_with_span(Instruction::Push(value.clone()), var.span);
+                } else if let Some(&slot) = self.global_slots.get(&var.name) {
+                    self.emit_with_span(Instruction::LoadGlobal(slot), var.span);
+                } else if let Some(&slot) = self.variable_slots.get(&var.name) {
                     self.emit_with_span(Instruction::LoadVar(slot), var.span);
                 } else {
                     return Err(ZvarError::CodegenError {
@@ -434,6 +725,7 @@ impl CodeGenerator {
                     BinaryOperator::Subtract => Instruction::Sub,
                     BinaryOperator::Multiply => Instruction::Mul,
                     BinaryOperator::Divide => Instruction::Div,
+                    BinaryOperator::Power => Instruction::Pow,
                     BinaryOperator::Equal => Instruction::Equal, // NEW!
                     BinaryOperator::NotEqual => Instruction::NotEqual, // NEW!
                     BinaryOperator::Less => Instruction::Less,   // NEW!
@@ -446,20 +738,30 @@ impl CodeGenerator {
             }
 
             Expression::Logical(logical) => {
-                // NEW!
                 // Generate left operand
                 self.generate_expression(&logical.left)?;
 
-                // Generate right operand
-                self.generate_expression(&logical.right)?;
-
-                // Generate logical operator instruction
-                let instruction = match logical.operator {
-                    LogicalOperator::And => Instruction::And,
-                    LogicalOperator::Or => Instruction::Or,
+                // Short-circuit: keep a copy of the left value as the result
+                // when it already decides the outcome
+                self.emit_with_span(Instruction::Dup, logical.span);
+                let skip_jump = self.bytecode.len();
+                let (skip, instruction) = match logical.operator {
+                    LogicalOperator::And => (Instruction::JumpIfFalse(0), Instruction::And),
+                    LogicalOperator::Or => (Instruction::JumpIfTrue(0), Instruction::Or),
                 };
+                self.emit_with_span(skip, logical.span); // Placeholder address
 
+                // Generate right operand and combine both values
+                self.generate_expression(&logical.right)?;
                 self.emit_with_span(instruction, logical.span);
+
+                let end_target = self.bytecode.len();
+                if let Some(
+                    Instruction::JumpIfFalse(ref mut addr) | Instruction::JumpIfTrue(ref mut addr),
+                ) = self.bytecode.instructions.get_mut(skip_jump)
+                {
+                    *addr = end_target;
+                }
             }
 
             Expression::Unary(unary) => {
@@ -495,16 +797,131 @@ impl CodeGenerator {
                         });
                     }
                     self.emit_with_span(Instruction::Print, call.span);
+                } else if call.name == "random" {
+                    if argc != 1 {
+                        return Err(ZvarError::WrongArgumentCount {
+                            span: call.span,
+                            name: call.name.clone(),
+                            expected: 1,
+                            found: argc as usize,
+                        });
+                    }
+                    self.emit_with_span(Instruction::Random, call.span);
                 } else {
                     // Regular function call
                     self.emit_with_span(Instruction::Call(call.name.clone(), argc), call.span);
                 }
             }
-        }
 
+            Expression::Conditional(cond) => {
+                // Same layout as an if/else statement, but each branch leaves a value
+                self.generate_expression(&cond.condition)?;
+
+                let else_jump = self.bytecode.len();
+                self.emit_with_span(Instruction::JumpIfFalse(0), cond.span); // Placeholder address
+
+                self.generate_expression(&cond.then_expr)?;
+
+                let end_jump = self.bytecode.len();
+                self.emit_with_span(Instruction::Jump(0), cond.span); // Placeholder address
+
+                let else_target = self.bytecode.len();
+                if let Some(Instruction::JumpIfFalse(ref mut addr)) =
+                    self.bytecode.instructions.get_mut(else_jump)
+                {
+                    *addr = else_target;
+                }
+
+                self.generate_expression(&cond.else_expr)?;
+
+                let end_target = self.bytecode.len();
+                if let Some(Instruction::Jump(ref mut addr)) =
+                    self.bytecode.instructions.get_mut(end_jump)
+                {
+                    *addr = end_target;
+                }
+            }
+
+            Expression::Assignment(assign) => {
+                self.generate_expression(&assign.value)?;
+
+                // Store the value and leave it behind as the expression's result
+                if let Some(&slot) = self.global_slots.get(&assign.target) {
+                    self.emit_with_span(Instruction::Dup, assign.span);
+                    self.emit_with_span(Instruction::StoreGlobal(slot), assign.span);
+                } else if let Some(&slot) = self.variable_slots.get(&assign.target) {
+                    self.emit_with_span(Instruction::StoreVarKeep(slot), assign.span);
+                } else {
+                    return Err(ZvarError::CodegenError {
+                        message: format!("Variable {} not found in slots", assign.target),
+                    });
+                }
+            }
+        }
         Ok(())
     }
 
+    /// Generate code that jumps when a condition evaluates to `jump_when`
+    /// and falls through otherwise
+    ///
+    /// `&&`, `||` and `!` are decided by branching on their operands, which
+    /// keeps short-circuiting without pushing the intermediate booleans. The
+    /// placeholder jumps are returned for `patch_condition_jumps`.
+    fn generate_condition_jumps(
+        &mut self,
+        condition: &Expression,
+        jump_when: bool,
+    ) -> ZvarResult<Vec<usize>> {
+        if let Some(simplified) = const_eval::simplify_boolean(condition, &self.constant_values) {
+            return self.generate_condition_jumps(&simplified, jump_when);
+        }
+
+        match condition {
+            Expression::Logical(logical) => {
+                // `a && b` is false as soon as `a` is; `a || b` is true as soon as `a` is
+                let decides_early = logical.operator == LogicalOperator::Or;
+                if decides_early == jump_when {
+                    let mut jumps = self.generate_condition_jumps(&logical.left, jump_when)?;
+                    jumps.extend(self.generate_condition_jumps(&logical.right, jump_when)?);
+                    Ok(jumps)
+                } else {
+                    // The left operand alone skips the right one, falling through
+                    let skip_jumps = self.generate_condition_jumps(&logical.left, !jump_when)?;
+                    let jumps = self.generate_condition_jumps(&logical.right, jump_when)?;
+                    self.patch_condition_jumps(&skip_jumps);
+                    Ok(jumps)
+                }
+            }
+
+            Expression::Unary(unary) if unary.operator == UnaryOperator::Not => {
+                self.generate_condition_jumps(&unary.operand, !jump_when)
+            }
+
+            _ => {
+                self.generate_expression(condition)?;
+                let jump = if jump_when {
+                    Instruction::JumpIfTrue(0)
+                } else {
+                    Instruction::JumpIfFalse(0)
+                };
+                Ok(vec![self.emit_with_span(jump, condition.span())]) // Placeholder address
+            }
+        }
+    }
+
+    /// Point jumps from `generate_condition_jumps` at the next instruction
+    fn patch_condition_jumps(&mut self, jumps: &[usize]) {
+        let target = self.bytecode.len();
+        for &jump in jumps {
+            if let Some(
+                Instruction::JumpIfFalse(ref mut addr) | Instruction::JumpIfTrue(ref mut addr),
+            ) = self.bytecode.instructions.get_mut(jump)
+            {
+                *addr = target;
+            }
+        }
+    }
+
     /// Emit an instruction with debug span information
     fn emit_with_span(&mut self, instruction: Instruction, span: crate::span::Span) -> usize {
         let index = self.bytecode.emit(instruction);
@@ -522,7 +939,11 @@ impl Default for CodeGenerator {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{parser::ast::*, span::Span, symbol_table::SymbolTable};
+    use crate::{
+        span::{Span, Spanned},
+        symbol_table::SymbolTable,
+        test_util::printed,
+    };
 
     #[test]
     fn test_variable_slot_assignment() {
@@ -542,10 +963,10 @@ mod tests {
         let mut codegen = CodeGenerator::new();
 
         // Create simple integer expression: 42
-        let expr = Expression::Integer(IntegerLiteral {
-            value: 42,
-            span: Span::new(1, 1, 1, 2),
-        });
+        let expr = Expression::Integer(Spanned::new(
+            IntegerLiteral { value: 42 },
+            Span::new(1, 1, 1, 2),
+        ));
 
         codegen.generate_expression(&expr).unwrap();
 
@@ -561,14 +982,14 @@ mod tests {
         let mut codegen = CodeGenerator::new();
 
         // Create binary expression: 1 + 2
-        let left = Expression::Integer(IntegerLiteral {
-            value: 1,
-            span: Span::new(1, 1, 1, 1),
-        });
-        let right = Expression::Integer(IntegerLiteral {
-            value: 2,
-            span: Span::new(1, 5, 1, 5),
-        });
+        let left = Expression::Integer(Spanned::new(
+            IntegerLiteral { value: 1 },
+            Span::new(1, 1, 1, 1),
+        ));
+        let right = Expression::Integer(Spanned::new(
+            IntegerLiteral { value: 2 },
+            Span::new(1, 5, 1, 5),
+        ));
 
         let binary = Expression::Binary(BinaryExpression::new(
             left,
@@ -590,4 +1011,342 @@ mod tests {
         ));
         assert!(matches!(codegen.bytecode.instructions[2], Instruction::Add));
     }
+
+    #[test]
+    fn test_with_variable_slots_reuses_assignments() {
+        let mut slots = BTreeMap::new();
+        slots.insert("v$0".to_string(), 0);
+        slots.insert("v$3".to_string(), 1);
+
+        let mut codegen = CodeGenerator::with_variable_slots(slots);
+        assert_eq!(codegen.next_variable_slot, 2);
+
+        let program = Program::new(
+            vec![Item::MainBlock(MainBlock::new(
+                Block::new(
+                    vec![Statement::ExpressionStatement(Expression::Variable(
+                        Spanned::new(
+                            Variable {
+                                name: "v$3".to_string(),
+                            },
+                            Span::new(1, 1, 1, 3),
+                        ),
+                    ))],
+                    Span::new(1, 1, 1, 3),
+                ),
+                Span::new(1, 1, 1, 3),
+            ))],
+            Span::new(1, 1, 1, 3),
+        );
+
+        let (bytecode, _) = codegen.generate(&program, &SymbolTable::new()).unwrap();
+        assert_eq!(bytecode.instructions[0], Instruction::LoadVar(1));
+    }
+
+    #[test]
+    fn test_folded_constant_is_not_stored() {
+        let source = r#"
+        main {
+            int c$0 = 6 * 7;
+            int v$0 = 1;
+            int c$1 = v$0 + 1;
+            print(c$0 + c$1);
+        }
+        "#;
+        let (bytecode, _) = crate::compile_source(source).unwrap();
+
+        let stores = bytecode
+            .instructions
+            .iter()
+            .filter(|instruction| matches!(instruction, Instruction::StoreVar(_)))
+            .count();
+        // v$0 and c$1, which depends on a variable; c$0 is inlined
+        assert_eq!(stores, 2);
+        assert!(bytecode
+            .instructions
+            .contains(&Instruction::Push(Value::Int(42))));
+        assert!(!bytecode
+            .instructions
+            .contains(&Instruction::Push(Value::Int(6))));
+    }
+
+    #[test]
+    fn test_logical_short_circuit_uses_dup() {
+        let source = r#"
+        main {
+            bool v$0 = false;
+            bool v$1 = v$0 && (1 / 0 == 1);
+            bool v$2 = true || (1 / 0 == 1);
+            bool v$3 = true && v$0;
+        }
+        "#;
+
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+        let and_start = bytecode
+            .instructions
+            .iter()
+            .position(|instruction| *instruction == Instruction::Dup)
+            .unwrap();
+        assert_eq!(
+            bytecode.instructions[and_start - 1],
+            Instruction::LoadVar(0)
+        );
+        assert!(matches!(
+            bytecode.instructions[and_start + 1],
+            Instruction::JumpIfFalse(_)
+        ));
+
+        // The right-hand divisions by zero are never evaluated
+        let mut vm = crate::vm::VM::new();
+        vm.load(bytecode, Some(debug_info));
+        assert!(vm.run().is_ok());
+    }
+
+    #[test]
+    fn test_if_logical_condition_branches_directly() {
+        let conditions = [
+            "v$1 && !v$2",
+            "v$2 || v$1 && v$0 == 0",
+            "v$2 && (1 / v$0 == 1)",
+            "v$1 || (1 / v$0 == 1)",
+            "!(v$1 || v$2)",
+        ];
+        let program = |materialize: bool| {
+            let mut body = String::from("int v$0 = 0; bool v$1 = true; bool v$2 = false;");
+            for (i, condition) in conditions.iter().enumerate() {
+                let condition = if materialize {
+                    body.push_str(&format!("bool v${} = {};", i + 3, condition));
+                    format!("v${}", i + 3)
+                } else {
+                    condition.to_string()
+                };
+                body.push_str(&format!(
+                    "if ({}) {{ print({}); }} else {{ print(0); }}",
+                    condition,
+                    i + 1
+                ));
+            }
+            crate::compile_source(&format!("main {{ {} }}", body))
+                .unwrap()
+                .0
+        };
+        let logical_count = |bytecode: &Bytecode| {
+            bytecode
+                .instructions
+                .iter()
+                .filter(|i| matches!(i, Instruction::And | Instruction::Or))
+                .count()
+        };
+
+        let direct = program(false);
+        let materialized = program(true);
+        assert_eq!(logical_count(&direct), 0);
+        assert!(logical_count(&materialized) > 0);
+
+        // Same results, and the divisions by zero are still never evaluated
+        assert_eq!(printed(direct), "1\n2\n0\n4\n0\n");
+        assert_eq!(printed(materialized), "1\n2\n0\n4\n0\n");
+    }
+
+    #[test]
+    fn test_assignment_expression_keeps_value() {
+        let source = r#"
+        int v$9 = 0;
+
+        main {
+            int v$0 = 0;
+            int v$1 = 0;
+            int v$2 = 0;
+            v$1 = (v$0 = 5) + 1;
+            v$2 = v$0 = v$9 = 7;
+            print(v$0);
+            print(v$1);
+            print(v$2);
+            print(v$9);
+            print((v$1 = 2) * 10);
+            print(v$1);
+        }
+        "#;
+
+        let (bytecode, _) = crate::compile_source(source).unwrap();
+        assert!(bytecode
+            .instructions
+            .contains(&Instruction::StoreVarKeep(0)));
+        assert_eq!(printed(bytecode), "7\n6\n7\n7\n20\n2\n");
+
+        let mismatch = r#"main { str v$1 = "a"; print(v$1 = 5); }"#;
+        assert!(matches!(
+            crate::compile_source(mismatch),
+            Err(ZvarError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_len_counts_characters_not_bytes() {
+        let source = r#"
+        main {
+            str v$0 = "hi 😀";
+            print(len(v$0));
+            print(byte_len(v$0));
+            int v$1 = len("abc") + byte_len("abc");
+            print(v$1);
+        }
+        "#;
+
+        let (bytecode, _) = crate::compile_source(source).unwrap();
+        assert_eq!(printed(bytecode), "4\n7\n6\n");
+
+        assert!(matches!(
+            crate::compile_source("main { print(len(5)); }"),
+            Err(ZvarError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_increment_compiles_to_incvar() {
+        let source = r#"
+        main {
+            int v$0 = 1;
+            v$0 = v$0 + 41;
+            v$0 = v$0 - 2;
+            v$0 = v$0 * 2;
+            print(v$0);
+        }
+        "#;
+
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+        assert_eq!(
+            &bytecode.instructions[..4],
+            [
+                Instruction::Push(Value::Int(1)),
+                Instruction::StoreVar(0),
+                Instruction::IncVar(0, 41),
+                Instruction::IncVar(0, -2),
+            ]
+        );
+
+        let mut vm = crate::vm::VM::new();
+        vm.load(bytecode, Some(debug_info));
+        assert!(vm.run().is_ok());
+    }
+
+    #[test]
+    fn test_call_statement_pops_only_return_values() {
+        let source = r#"
+        fn f$0(v$0 int) -> int {
+            print(v$0);
+            ret 0;
+        }
+
+        main {
+            f$0(7);
+            print(8);
+        }
+        "#;
+
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+        let main_start = bytecode.entry_point;
+        assert_eq!(
+            bytecode.instructions[main_start..],
+            [
+                Instruction::Push(Value::Int(7)),
+                Instruction::Call("f$0".to_string(), 1),
+                Instruction::Pop,
+                Instruction::Push(Value::Int(8)),
+                Instruction::Print,
+                Instruction::Halt,
+            ]
+        );
+
+        let mut vm = crate::vm::VM::new();
+        vm.load(bytecode, Some(debug_info));
+        assert!(vm.run().is_ok());
+    }
+
+    #[test]
+    fn test_relative_jumps() {
+        let source = r#"
+        fn f$0(v$0 int) -> int {
+            ret v$0 > 2 ? v$0 : 0;
+        }
+
+        main {
+            int v$1 = 0;
+            while (v$1 < 5) {
+                if (f$0(v$1) == 0) {
+                    print(v$1);
+                }
+                v$1 = v$1 + 1;
+            }
+        }
+        "#;
+
+        let (absolute, _) = crate::compile_source(source).unwrap();
+
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = crate::parser::Parser::new(source, &mut symbol_table).unwrap();
+        let program = parser.parse_program().unwrap();
+        let mut generator = CodeGenerator::new();
+        generator.set_relative_jumps(true);
+        let (mut relative, _) = generator.generate(&program, &symbol_table).unwrap();
+
+        assert!(relative.instructions.iter().all(|instruction| !matches!(
+            instruction,
+            Instruction::Jump(_) | Instruction::JumpIfFalse(_) | Instruction::JumpIfTrue(_)
+        )));
+        assert_eq!(relative.jump_targets(), absolute.jump_targets());
+
+        // Addresses of the executed instructions, in order
+        let run = |bytecode: Bytecode| {
+            let mut vm = crate::vm::VM::new();
+            vm.load(bytecode, None);
+            let mut executed = vec![vm.snapshot().ip()];
+            while vm.step().unwrap() == crate::vm::StepOutcome::Continue {
+                executed.push(vm.snapshot().ip());
+            }
+            executed
+        };
+        let executed = run(absolute.clone());
+        assert_eq!(run(relative.clone()), executed);
+
+        // Moving the code behind three other instructions only shifts the tables
+        let instructions = relative.instructions.clone();
+        relative.relocate(3);
+        assert_eq!(relative.instructions, instructions);
+        relative.instructions.splice(
+            0..0,
+            [Instruction::Nop, Instruction::Nop, Instruction::Halt],
+        );
+        assert!(relative.validate().is_ok());
+        let shifted: Vec<usize> = executed.iter().map(|ip| ip + 3).collect();
+        assert_eq!(run(relative), shifted);
+    }
+
+    #[test]
+    fn test_void_builtin_statement_is_not_popped() {
+        let source = r#"
+        main {
+            trim(" a ");
+            print("b");
+        }
+        "#;
+
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+        assert_eq!(
+            bytecode.instructions[bytecode.entry_point..],
+            [
+                Instruction::Push(Value::from(" a ")),
+                Instruction::Call("trim".to_string(), 1),
+                Instruction::Pop,
+                Instruction::Push(Value::from("b")),
+                Instruction::Print,
+                Instruction::Halt,
+            ]
+        );
+
+        let mut vm = crate::vm::VM::new();
+        vm.load(bytecode, Some(debug_info));
+        assert!(vm.run().is_ok());
+        assert_eq!(vm.stack_top(), None);
+    }
 }
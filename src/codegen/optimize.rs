@@ -0,0 +1,772 @@
+//! Optimization passes over generated bytecode
+
+use super::{
+    debug_info::DebugInfo,
+    instruction::{Bytecode, Instruction},
+};
+use crate::prelude::*;
+
+/// Remove obviously redundant instruction pairs
+///
+/// Collapses `PUSH x; POP` and `LOADVAR n; STOREVAR n` (which stores a
+/// variable back into itself). A pair is only removed if neither instruction
+/// is a jump target, function start or the entry point. Jump addresses and
+/// the debug information are rewritten to match the shorter stream.
+pub fn peephole(bytecode: &mut Bytecode, debug_info: &mut DebugInfo) {
+    with_absolute_jumps(bytecode, |bytecode| {
+        // Removing a pair can make a new pair adjacent, so repeat until stable
+        while remove_redundant_pairs(bytecode, debug_info) {}
+    });
+}
+
+/// Run a pass on absolute jumps, converting relative code there and back
+///
+/// The passes rewrite jump addresses when they remove or insert
+/// instructions, which only works for absolute targets.
+fn with_absolute_jumps(bytecode: &mut Bytecode, pass: impl FnOnce(&mut Bytecode)) {
+    if bytecode.has_relative_jumps() {
+        bytecode.to_absolute_jumps();
+        pass(bytecode);
+        bytecode.to_relative_jumps();
+    } else {
+        pass(bytecode);
+    }
+}
+
+/// Run a single pass, returning whether anything was removed
+fn remove_redundant_pairs(bytecode: &mut Bytecode, debug_info: &mut DebugInfo) -> bool {
+    let targets = protected_addresses(bytecode);
+    let len = bytecode.instructions.len();
+
+    let mut removed = vec![false; len];
+    let mut i = 0;
+    while i + 1 < len {
+        let redundant = match (&bytecode.instructions[i], &bytecode.instructions[i + 1]) {
+            (Instruction::Push(_), Instruction::Pop) => true,
+            (Instruction::LoadVar(a), Instruction::StoreVar(b)) => a == b,
+            _ => false,
+        };
+
+        if redundant && !targets.contains(&i) && !targets.contains(&(i + 1)) {
+            removed[i] = true;
+            removed[i + 1] = true;
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+
+    if !removed.contains(&true) {
+        return false;
+    }
+
+    remove_instructions(bytecode, debug_info, &removed);
+    true
+}
+
+impl Bytecode {
+    /// Remove instructions that no execution path can reach
+    ///
+    /// Walks the control flow graph from the entry point, following jumps,
+    /// fallthrough and calls to user functions. Whatever is never visited
+    /// (functions nobody calls, code after an unconditional jump or a
+    /// return) is dropped and jump addresses are rewritten.
+    pub fn strip_unreachable(&mut self, debug_info: &mut DebugInfo) {
+        with_absolute_jumps(self, |bytecode| {
+            bytecode.strip_unreachable_absolute(debug_info)
+        });
+    }
+
+    fn strip_unreachable_absolute(&mut self, debug_info: &mut DebugInfo) {
+        let len = self.instructions.len();
+        let mut reachable = vec![false; len];
+        let mut worklist = vec![self.entry_point];
+
+        while let Some(addr) = worklist.pop() {
+            if addr >= len || reachable[addr] {
+                continue;
+            }
+            reachable[addr] = true;
+
+            match &self.instructions[addr] {
+                Instruction::Jump(target) => worklist.push(*target),
+                Instruction::JumpIfFalse(target) | Instruction::JumpIfTrue(target) => {
+                    worklist.push(*target);
+                    worklist.push(addr + 1);
+                }
+                Instruction::Return | Instruction::ReturnValue | Instruction::Halt => {}
+                Instruction::Call(name, _) => {
+                    // Builtins have no entry in the function table
+                    if let Some(start) = self.get_function_start(name) {
+                        worklist.push(start);
+                    }
+                    worklist.push(addr + 1);
+                }
+                _ => worklist.push(addr + 1),
+            }
+        }
+
+        let removed: Vec<bool> = reachable
+            .iter()
+            .map(|&is_reachable| !is_reachable)
+            .collect();
+        if removed.contains(&true) {
+            remove_instructions(self, debug_info, &removed);
+        }
+    }
+}
+
+/// Move loop-invariant computations in front of their loop
+///
+/// A loop is a backward jump to an address that is only entered by falling
+/// through from the instruction before it. Inside the loop, an expression
+/// built from literals and `LOADVAR`s of slots the loop never writes computes
+/// the same value on every iteration, so it is evaluated once before the loop
+/// into a fresh slot and the loop loads that slot instead.
+///
+/// Operations that can fail at runtime (e.g. overflowing `ADD`) are only
+/// hoisted from the straight-line start of the loop, which runs on every
+/// iteration before anything observable happens; `DIV` and `POW` never are.
+///
+/// Loops that call user functions are left alone: a call only saves its
+/// parameter slots, so a recursive call would overwrite the fresh slots.
+pub fn hoist_loop_invariants(bytecode: &mut Bytecode, debug_info: &mut DebugInfo) {
+    with_absolute_jumps(
+        bytecode,
+        |bytecode| {
+            while hoist_from_one_loop(bytecode, debug_info) {}
+        },
+    );
+}
+
+/// Hoist the invariant expressions of the first loop that has any
+fn hoist_from_one_loop(bytecode: &mut Bytecode, debug_info: &mut DebugInfo) -> bool {
+    let protected = protected_addresses(bytecode);
+    let mut next_slot = bytecode
+        .instructions
+        .iter()
+        .filter_map(|instruction| match instruction {
+            Instruction::LoadVar(slot)
+            | Instruction::StoreVar(slot)
+            | Instruction::StoreVarKeep(slot)
+            | Instruction::IncVar(slot, _) => Some(slot + 1),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0);
+
+    for (end, instruction) in bytecode.instructions.iter().enumerate() {
+        let (Instruction::Jump(start) | Instruction::JumpIfTrue(start)) = *instruction else {
+            continue;
+        };
+        if start > end
+            || !is_simple_loop(bytecode, start, end)
+            || calls_function(bytecode, start, end)
+        {
+            continue;
+        }
+
+        let hoisted = find_invariants(bytecode, start, end, &protected);
+        if hoisted.is_empty() {
+            continue;
+        }
+
+        // Pre-header: each expression followed by a store into its own slot
+        let mut preheader = Vec::new();
+        let mut replacements = Vec::new();
+        for (first, last) in hoisted {
+            for index in first..=last {
+                preheader.push((bytecode.instructions[index].clone(), index));
+            }
+            preheader.push((Instruction::StoreVar(next_slot), last));
+            replacements.push((first, last, next_slot));
+            next_slot += 1;
+        }
+
+        insert_preheader(bytecode, debug_info, start, preheader, &replacements);
+        return true;
+    }
+
+    false
+}
+
+/// Check whether a loop calls a user function
+fn calls_function(bytecode: &Bytecode, start: usize, end: usize) -> bool {
+    bytecode.instructions[start..=end]
+        .iter()
+        .any(|instruction| match instruction {
+            Instruction::Call(name, _) => bytecode.function_table.contains_key(name),
+            _ => false,
+        })
+}
+
+/// Check that a backward jump forms a loop that is only entered at its start
+fn is_simple_loop(bytecode: &Bytecode, start: usize, end: usize) -> bool {
+    // The pre-header goes right before `start`, so control must fall into it
+    let falls_through = start > 0
+        && !matches!(
+            bytecode.instructions[start - 1],
+            Instruction::Jump(_)
+                | Instruction::Return
+                | Instruction::ReturnValue
+                | Instruction::Halt
+        );
+    if !falls_through
+        || bytecode.entry_point == start
+        || bytecode.function_table.values().any(|&s| s == start)
+    {
+        return false;
+    }
+
+    // Nothing outside the loop may jump into it, not even to its start
+    let jumps_in = bytecode
+        .instructions
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| *index < start || *index > end)
+        .any(|(_, instruction)| match instruction {
+            Instruction::Jump(addr)
+            | Instruction::JumpIfFalse(addr)
+            | Instruction::JumpIfTrue(addr) => (start..=end).contains(addr),
+            _ => false,
+        });
+
+    !jumps_in
+}
+
+/// Find the invariant expressions of a loop as inclusive instruction ranges
+fn find_invariants(
+    bytecode: &Bytecode,
+    start: usize,
+    end: usize,
+    protected: &BTreeSet<usize>,
+) -> Vec<(usize, usize)> {
+    let body = &bytecode.instructions[start..=end];
+    let written: BTreeSet<u32> = body
+        .iter()
+        .filter_map(|instruction| match instruction {
+            Instruction::StoreVar(slot)
+            | Instruction::StoreVarKeep(slot)
+            | Instruction::IncVar(slot, _) => Some(*slot),
+            _ => None,
+        })
+        .collect();
+
+    // The straight-line start of the loop runs on every iteration
+    let prefix_end = (start..=end)
+        .find(|&addr| {
+            (addr > start && protected.contains(&addr))
+                || matches!(
+                    bytecode.instructions[addr],
+                    Instruction::Jump(_)
+                        | Instruction::JumpIfFalse(_)
+                        | Instruction::JumpIfTrue(_)
+                        | Instruction::Call(..)
+                        | Instruction::Return
+                        | Instruction::ReturnValue
+                        | Instruction::Print
+                        | Instruction::Random
+                        | Instruction::Describe(..)
+                        | Instruction::StoreGlobal(_)
+                        | Instruction::Halt
+                )
+        })
+        .unwrap_or(end + 1);
+
+    let mut hoisted = Vec::new();
+    let mut first = start;
+    while first <= end {
+        match invariant_expression_end(bytecode, first, end, prefix_end, &written, protected) {
+            Some(last) => {
+                hoisted.push((first, last));
+                first = last + 1;
+            }
+            None => first += 1,
+        }
+    }
+    hoisted
+}
+
+/// Find the longest invariant expression starting at `first`, if it contains an operation
+fn invariant_expression_end(
+    bytecode: &Bytecode,
+    first: usize,
+    end: usize,
+    prefix_end: usize,
+    written: &BTreeSet<u32>,
+    protected: &BTreeSet<usize>,
+) -> Option<usize> {
+    let mut depth = 0;
+    let mut last = None;
+
+    for index in first..=end {
+        // Only the first instruction may be a jump target, since it is the one kept
+        if index > first && protected.contains(&index) {
+            break;
+        }
+
+        let in_prefix = index < prefix_end;
+        match &bytecode.instructions[index] {
+            Instruction::Push(_) | Instruction::LoadConst(_) => depth += 1,
+            Instruction::LoadVar(slot) if !written.contains(slot) => depth += 1,
+            Instruction::Equal | Instruction::NotEqual if depth >= 2 => depth -= 1,
+            Instruction::Not if depth >= 1 => {}
+            Instruction::Add
+            | Instruction::Sub
+            | Instruction::Mul
+            | Instruction::Less
+            | Instruction::Greater
+            | Instruction::LessEqual
+            | Instruction::GreaterEqual
+            | Instruction::And
+            | Instruction::Or
+                if in_prefix && depth >= 2 =>
+            {
+                depth -= 1
+            }
+            _ => break,
+        }
+
+        // A lone operand is as cheap as the load that would replace it
+        if depth == 1 && index > first {
+            last = Some(index);
+        }
+    }
+
+    last
+}
+
+/// Insert a pre-header before `start` and replace hoisted ranges with slot loads
+fn insert_preheader(
+    bytecode: &mut Bytecode,
+    debug_info: &mut DebugInfo,
+    start: usize,
+    preheader: Vec<(Instruction, usize)>,
+    replacements: &[(usize, usize, u32)],
+) {
+    let len = bytecode.instructions.len();
+
+    // Each range keeps its first instruction, which becomes the load
+    let mut removed = vec![false; len];
+    let mut loads = BTreeMap::new();
+    for &(first, last, slot) in replacements {
+        loads.insert(first, slot);
+        for is_removed in &mut removed[first + 1..=last] {
+            *is_removed = true;
+        }
+    }
+
+    // Map each old address to its new one; the extra entry covers
+    // addresses one past the end. Jumps back to `start` land after the
+    // pre-header, which only runs when entering the loop.
+    let mut new_address = Vec::with_capacity(len + 1);
+    let mut next = 0;
+    for (index, &is_removed) in removed.iter().enumerate() {
+        if index == start {
+            next += preheader.len();
+        }
+        new_address.push(next);
+        if !is_removed {
+            next += 1;
+        }
+    }
+    new_address.push(next);
+
+    let old_spans = core::mem::take(&mut debug_info.instruction_spans);
+    let old_instructions = core::mem::take(&mut bytecode.instructions);
+    let mut instructions = Vec::with_capacity(next);
+    let mut push = |instruction: Instruction, origin: usize| {
+        if let Some(span) = old_spans.get(&origin) {
+            debug_info
+                .instruction_spans
+                .insert(instructions.len(), *span);
+        }
+        instructions.push(instruction);
+    };
+
+    for (index, instruction) in old_instructions.into_iter().enumerate() {
+        if index == start {
+            for (hoisted, origin) in &preheader {
+                push(hoisted.clone(), *origin);
+            }
+        }
+        if removed[index] {
+            continue;
+        }
+
+        let instruction = match (loads.get(&index), instruction) {
+            (Some(&slot), _) => Instruction::LoadVar(slot),
+            (None, Instruction::Jump(addr)) => Instruction::Jump(new_address[addr]),
+            (None, Instruction::JumpIfFalse(addr)) => Instruction::JumpIfFalse(new_address[addr]),
+            (None, Instruction::JumpIfTrue(addr)) => Instruction::JumpIfTrue(new_address[addr]),
+            (None, other) => other,
+        };
+        push(instruction, index);
+    }
+
+    bytecode.instructions = instructions;
+    bytecode.entry_point = new_address[bytecode.entry_point];
+    for function_start in bytecode.function_table.values_mut() {
+        *function_start = new_address[*function_start];
+    }
+}
+
+/// Drop the flagged instructions and rewrite every address that refers past them
+fn remove_instructions(bytecode: &mut Bytecode, debug_info: &mut DebugInfo, removed: &[bool]) {
+    let len = bytecode.instructions.len();
+
+    // Map each old address to its new one; the extra entry covers
+    // addresses one past the end
+    let mut new_address = Vec::with_capacity(len + 1);
+    let mut next = 0;
+    for &is_removed in removed {
+        new_address.push(next);
+        if !is_removed {
+            next += 1;
+        }
+    }
+    new_address.push(next);
+
+    let instructions = core::mem::take(&mut bytecode.instructions);
+    bytecode.instructions = instructions
+        .into_iter()
+        .zip(removed)
+        .filter(|(_, &is_removed)| !is_removed)
+        .map(|(instruction, _)| match instruction {
+            Instruction::Jump(addr) => Instruction::Jump(new_address[addr]),
+            Instruction::JumpIfFalse(addr) => Instruction::JumpIfFalse(new_address[addr]),
+            Instruction::JumpIfTrue(addr) => Instruction::JumpIfTrue(new_address[addr]),
+            other => other,
+        })
+        .collect();
+    bytecode.entry_point = new_address[bytecode.entry_point];
+
+    // Functions whose body was removed disappear from the table
+    bytecode
+        .function_table
+        .retain(|_, start| *start >= len || !removed[*start]);
+    for start in bytecode.function_table.values_mut() {
+        *start = new_address[*start];
+    }
+    debug_info.instruction_spans = core::mem::take(&mut debug_info.instruction_spans)
+        .into_iter()
+        .filter(|(index, _)| *index < len && !removed[*index])
+        .map(|(index, span)| (new_address[index], span))
+        .collect();
+}
+
+/// Collect every address that control flow can land on
+fn protected_addresses(bytecode: &Bytecode) -> BTreeSet<usize> {
+    let mut targets = bytecode.jump_targets();
+    targets.insert(bytecode.entry_point);
+    targets.extend(bytecode.function_table.values().copied());
+    targets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{codegen::instruction::Value, test_util::printed};
+
+    #[test]
+    fn test_removes_redundant_pairs() {
+        let mut bytecode = Bytecode::new();
+        bytecode.emit(Instruction::Push(Value::Int(1)));
+        bytecode.emit(Instruction::Push(Value::Int(2)));
+        bytecode.emit(Instruction::Pop);
+        bytecode.emit(Instruction::LoadVar(0));
+        bytecode.emit(Instruction::StoreVar(0));
+        bytecode.emit(Instruction::Print);
+        bytecode.emit(Instruction::Halt);
+
+        peephole(&mut bytecode, &mut DebugInfo::new());
+
+        assert_eq!(
+            bytecode.instructions,
+            vec![
+                Instruction::Push(Value::Int(1)),
+                Instruction::Print,
+                Instruction::Halt,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keeps_different_slots() {
+        let mut bytecode = Bytecode::new();
+        bytecode.emit(Instruction::LoadVar(0));
+        bytecode.emit(Instruction::StoreVar(1));
+        bytecode.emit(Instruction::Halt);
+
+        peephole(&mut bytecode, &mut DebugInfo::new());
+
+        assert_eq!(bytecode.len(), 3);
+    }
+
+    #[test]
+    fn test_jump_targets_are_rewritten() {
+        let mut bytecode = Bytecode::new();
+        bytecode.emit(Instruction::Push(Value::Bool(false))); // 0
+        bytecode.emit(Instruction::JumpIfFalse(5)); // 1
+        bytecode.emit(Instruction::Push(Value::Int(9))); // 2: removed
+        bytecode.emit(Instruction::Pop); // 3: removed
+        bytecode.emit(Instruction::Jump(7)); // 4
+        bytecode.emit(Instruction::Push(Value::Int(1))); // 5: jump target, kept
+        bytecode.emit(Instruction::Pop); // 6
+        bytecode.emit(Instruction::Halt); // 7
+
+        bytecode.mark_function_start("main".to_string(), 0);
+
+        peephole(&mut bytecode, &mut DebugInfo::new());
+
+        assert_eq!(
+            bytecode.instructions,
+            vec![
+                Instruction::Push(Value::Bool(false)),
+                Instruction::JumpIfFalse(3),
+                Instruction::Jump(5),
+                Instruction::Push(Value::Int(1)),
+                Instruction::Pop,
+                Instruction::Halt,
+            ]
+        );
+        assert_eq!(bytecode.get_function_start("main"), Some(0));
+    }
+
+    #[test]
+    fn test_function_starts_are_remapped() {
+        let source = r#"
+        fn f$0(v$0 int) -> int {
+            print(v$0);
+            5;
+            ret v$0 + 1;
+        }
+
+        main {
+            int v$2 = f$0(1);
+            print(v$2);
+        }
+        "#;
+
+        let (mut bytecode, mut debug_info) = crate::compile_source(source).unwrap();
+        let main_start = bytecode.get_function_start("main").unwrap();
+        peephole(&mut bytecode, &mut debug_info);
+
+        // The dead `5;` inside f$0 is gone, so main starts two instructions earlier
+        assert_eq!(bytecode.get_function_start("main"), Some(main_start - 2));
+        assert_eq!(bytecode.entry_point, main_start - 2);
+
+        let mut vm = crate::vm::VM::new();
+        vm.load(bytecode, Some(debug_info));
+        assert!(vm.run().is_ok());
+    }
+
+    #[test]
+    fn test_strip_unreachable_removes_uncalled_function() {
+        let source = r#"
+        fn f$0(v$0 int) -> int {
+            ret v$0 + 1;
+        }
+
+        fn f$1(v$0 int) -> int {
+            ret v$0 * 2;
+        }
+
+        main {
+            print(f$1(20));
+        }
+        "#;
+
+        let (mut bytecode, mut debug_info) = crate::compile_source(source).unwrap();
+        let f0_len = bytecode.get_function_start("f$1").unwrap();
+        let before = bytecode.len();
+
+        bytecode.strip_unreachable(&mut debug_info);
+
+        assert_eq!(bytecode.len(), before - f0_len);
+        assert_eq!(bytecode.get_function_start("f$0"), None);
+        assert_eq!(bytecode.get_function_start("f$1"), Some(0));
+        assert!(!bytecode
+            .instructions
+            .iter()
+            .any(|instruction| matches!(instruction, Instruction::Add)));
+
+        let mut vm = crate::vm::VM::new();
+        vm.load(bytecode, Some(debug_info));
+        assert!(vm.run().is_ok());
+    }
+
+    #[test]
+    fn test_hoists_loop_invariant_expression() {
+        let source = r#"
+        main {
+            int c$0 = 10;
+            int v$0 = 0;
+            int v$1 = 3;
+            int v$2 = 0;
+            while (v$0 < 4) {
+                v$2 = v$2 + v$1 * c$0;
+                v$0 = v$0 + 1;
+            }
+            print(v$2);
+        }
+        "#;
+
+        let (mut bytecode, mut debug_info) = crate::compile_source(source).unwrap();
+        let before = bytecode.clone();
+        hoist_loop_invariants(&mut bytecode, &mut debug_info);
+
+        // `v$1 * c$0` is computed once, in front of the loop
+        let loop_start = bytecode
+            .instructions
+            .iter()
+            .find_map(|instruction| match instruction {
+                Instruction::JumpIfTrue(start) => Some(*start),
+                _ => None,
+            })
+            .unwrap();
+        let muls: Vec<usize> = (0..bytecode.len())
+            .filter(|&i| bytecode.instructions[i] == Instruction::Mul)
+            .collect();
+        assert_eq!(muls.len(), 1);
+        assert!(muls[0] < loop_start);
+        assert_eq!(
+            bytecode.instructions[muls[0] - 1],
+            Instruction::Push(Value::Int(10))
+        );
+        assert!(matches!(
+            bytecode.instructions[muls[0] + 1],
+            Instruction::StoreVar(_)
+        ));
+
+        // Both versions print the same result
+        assert_eq!(printed(before), "120\n");
+        assert_eq!(printed(bytecode), "120\n");
+    }
+
+    #[test]
+    fn test_written_variables_are_not_hoisted() {
+        let source = r#"
+        main {
+            int v$0 = 0;
+            int v$1 = 1;
+            while (v$0 < 3) {
+                v$1 = v$1 * 2;
+                v$0 = v$0 + 1;
+            }
+            print(v$1);
+        }
+        "#;
+
+        let (mut bytecode, mut debug_info) = crate::compile_source(source).unwrap();
+        let before = bytecode.clone();
+        hoist_loop_invariants(&mut bytecode, &mut debug_info);
+
+        assert_eq!(bytecode.instructions, before.instructions);
+        assert_eq!(printed(bytecode), "8\n");
+    }
+
+    #[test]
+    fn test_loops_with_calls_are_not_hoisted() {
+        let source = r#"
+        int v$9 = 0;
+
+        fn f$0(v$0 int) -> int {
+            while (v$9 < 10 + v$0 * 5) {
+                v$9 = v$9 + v$0 * 1 + 1;
+                if (v$0 == 2) { if (v$9 == 3) { print(f$0(0)); } }
+            }
+            ret v$0;
+        }
+
+        main {
+            print(f$0(2));
+            print(v$9);
+        }
+        "#;
+
+        let (mut bytecode, mut debug_info) = crate::compile_source(source).unwrap();
+        let before = bytecode.clone();
+        hoist_loop_invariants(&mut bytecode, &mut debug_info);
+
+        assert_eq!(bytecode.instructions, before.instructions);
+        assert_eq!(printed(before), "0\n2\n22\n");
+        assert_eq!(printed(bytecode), "0\n2\n22\n");
+    }
+
+    #[test]
+    fn test_passes_convert_relative_jumps() {
+        let source = r#"
+        fn f$0() -> int {
+            ret 1;
+        }
+
+        main {
+            int v$0 = 0;
+            int v$1 = 2;
+            int v$2 = 0;
+            while (v$0 < 3) {
+                v$2 = v$2 + v$1 * 5;
+                v$0 = v$0 + 1;
+            }
+            print(v$2);
+        }
+        "#;
+        let (program, symbol_table) = crate::parse(source).unwrap();
+        let mut codegen = crate::codegen::CodeGenerator::new();
+        codegen.set_relative_jumps(true);
+        let (mut bytecode, mut debug_info) = codegen.generate(&program, &symbol_table).unwrap();
+
+        bytecode.strip_unreachable(&mut debug_info);
+        peephole(&mut bytecode, &mut debug_info);
+        hoist_loop_invariants(&mut bytecode, &mut debug_info);
+        assert!(bytecode.has_relative_jumps());
+        assert!(bytecode.validate().is_ok());
+
+        // The uncalled f$0 is gone and the product is computed before the loop
+        assert!(!bytecode.function_table.contains_key("f$0"));
+        let mut absolute = bytecode.clone();
+        absolute.to_absolute_jumps();
+        let loop_start = absolute
+            .instructions
+            .iter()
+            .find_map(|instruction| match instruction {
+                Instruction::JumpIfTrue(start) => Some(*start),
+                _ => None,
+            })
+            .unwrap();
+        let mul = absolute
+            .instructions
+            .iter()
+            .position(|instruction| *instruction == Instruction::Mul)
+            .unwrap();
+        assert!(mul < loop_start);
+
+        assert_eq!(printed(bytecode), "30\n");
+    }
+
+    #[test]
+    fn test_strip_unreachable_after_jump() {
+        let mut bytecode = Bytecode::new();
+        bytecode.emit(Instruction::Jump(3)); // 0
+        bytecode.emit(Instruction::Push(Value::Int(1))); // 1: unreachable
+        bytecode.emit(Instruction::Print); // 2: unreachable
+        bytecode.emit(Instruction::Push(Value::Bool(true))); // 3
+        bytecode.emit(Instruction::JumpIfFalse(6)); // 4
+        bytecode.emit(Instruction::Halt); // 5
+        bytecode.emit(Instruction::Halt); // 6
+
+        bytecode.strip_unreachable(&mut DebugInfo::new());
+
+        assert_eq!(
+            bytecode.instructions,
+            vec![
+                Instruction::Jump(1),
+                Instruction::Push(Value::Bool(true)),
+                Instruction::JumpIfFalse(4),
+                Instruction::Halt,
+                Instruction::Halt,
+            ]
+        );
+    }
+}
@@ -0,0 +1,474 @@
+//! API documentation export for zvar programs
+//!
+//! Documentation comes from `///` comments and `describe` statements. Every
+//! function is listed, variables (including globals) and constants only when
+//! they are documented.
+
+use crate::{parser::ast::*, prelude::*, symbol_table::SymbolTable};
+use core::fmt;
+
+/// Output format of the `docs` command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum DocsFormat {
+    /// Markdown document
+    Markdown,
+    /// Static HTML page
+    Html,
+}
+
+/// A documented entity
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocEntry {
+    pub name: String,
+    pub signature: String,
+    pub documentation: Option<String>,
+}
+
+/// How many functions carry documentation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DocsCoverage {
+    pub documented: usize,
+    pub total: usize,
+}
+
+impl DocsCoverage {
+    /// Count documented functions in a symbol table filled by the parser
+    pub fn from_symbol_table(symbol_table: &SymbolTable) -> Self {
+        let functions: Vec<_> = symbol_table
+            .all_symbols()
+            .into_iter()
+            .filter(|(_, symbol)| symbol.is_function())
+            .collect();
+
+        DocsCoverage {
+            documented: functions
+                .iter()
+                .filter(|(_, symbol)| symbol.documentation.is_some())
+                .count(),
+            total: functions.len(),
+        }
+    }
+
+    /// Documented share in percent; a program without functions is fully covered
+    pub fn percentage(&self) -> f64 {
+        if self.total == 0 {
+            100.0
+        } else {
+            self.documented as f64 * 100.0 / self.total as f64
+        }
+    }
+}
+
+impl fmt::Display for DocsCoverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} functions documented ({:.1}%)",
+            self.documented,
+            self.total,
+            self.percentage()
+        )
+    }
+}
+
+/// Collect the documented entities of a program in declaration order
+pub fn collect_entries(program: &Program) -> Vec<DocEntry> {
+    let mut entries = Vec::new();
+
+    for item in &program.items {
+        match item {
+            Item::Function(func) => {
+                let mut describes = BTreeMap::new();
+                collect_describes(&func.body, &mut describes);
+
+                let params: Vec<String> = func
+                    .params
+                    .iter()
+                    .map(|param| format!("{} {}", param.name, param.param_type))
+                    .collect();
+
+                entries.push(DocEntry {
+                    name: func.name.clone(),
+                    signature: format!(
+                        "{}{}fn {}({}) -> {}",
+                        if func.is_pure { "pure " } else { "" },
+                        if func.is_memoized { "memo " } else { "" },
+                        func.name,
+                        params.join(", "),
+                        func.return_type
+                    ),
+                    documentation: combine(
+                        func.documentation.as_deref(),
+                        describes.get(&func.name),
+                    ),
+                });
+            }
+            Item::MainBlock(main) => {
+                let mut describes = BTreeMap::new();
+                collect_describes(&main.body, &mut describes);
+                collect_declarations(&main.body, &describes, &mut entries);
+            }
+            Item::GlobalVariable(var_decl) => {
+                if let Some(doc) = &var_decl.documentation {
+                    entries.push(DocEntry {
+                        name: var_decl.name.clone(),
+                        signature: format!("{} {}", var_decl.value_type, var_decl.name),
+                        documentation: Some(doc.clone()),
+                    });
+                }
+            }
+            Item::TypeAlias(alias) => {
+                if let Some(doc) = &alias.documentation {
+                    entries.push(DocEntry {
+                        name: alias.name.clone(),
+                        signature: format!("type {} = {}", alias.name, alias.target),
+                        documentation: Some(doc.clone()),
+                    });
+                }
+            }
+        }
+    }
+
+    entries
+}
+
+/// Signature of a function with parameter descriptions inline
+///
+/// Parameters are documented with `describe` inside the function body, e.g.
+/// `f$0(v$0: int /* the first addend */, v$1: int) -> int`.
+pub fn annotated_signature(func: &Function) -> String {
+    let mut describes = BTreeMap::new();
+    collect_describes(&func.body, &mut describes);
+
+    let params: Vec<String> = func
+        .params
+        .iter()
+        .map(|param| match describes.get(&param.name) {
+            Some(texts) => format!(
+                "{}: {} /* {} */",
+                param.name,
+                param.param_type,
+                texts.join("; ")
+            ),
+            None => format!("{}: {}", param.name, param.param_type),
+        })
+        .collect();
+
+    format!(
+        "{}({}) -> {}",
+        func.name,
+        params.join(", "),
+        func.return_type
+    )
+}
+
+/// Record documented variable and constant declarations of a block
+fn collect_declarations(
+    block: &Block,
+    describes: &BTreeMap<String, Vec<String>>,
+    entries: &mut Vec<DocEntry>,
+) {
+    for stmt in &block.statements {
+        let (name, signature, documentation) = match stmt {
+            Statement::VariableDeclaration(var_decl) => (
+                &var_decl.name,
+                format!("{} {}", var_decl.value_type, var_decl.name),
+                &var_decl.documentation,
+            ),
+            Statement::ConstantDeclaration(const_decl) => (
+                &const_decl.name,
+                format!("{} {}", const_decl.value_type, const_decl.name),
+                &const_decl.documentation,
+            ),
+            Statement::If(if_stmt) => {
+                collect_declarations(&if_stmt.then_block, describes, entries);
+                if let Some(else_block) = &if_stmt.else_block {
+                    collect_declarations(else_block, describes, entries);
+                }
+                continue;
+            }
+            Statement::While(while_stmt) => {
+                collect_declarations(&while_stmt.body, describes, entries);
+                if let Some(else_block) = &while_stmt.else_block {
+                    collect_declarations(else_block, describes, entries);
+                }
+                continue;
+            }
+            Statement::Match(match_stmt) => {
+                for arm in &match_stmt.arms {
+                    collect_declarations(&arm.body, describes, entries);
+                }
+                continue;
+            }
+            _ => continue,
+        };
+
+        if let Some(documentation) = combine(documentation.as_deref(), describes.get(name)) {
+            entries.push(DocEntry {
+                name: name.clone(),
+                signature,
+                documentation: Some(documentation),
+            });
+        }
+    }
+}
+
+/// Gather `describe` texts per target, including nested blocks
+fn collect_describes(block: &Block, describes: &mut BTreeMap<String, Vec<String>>) {
+    for stmt in &block.statements {
+        match stmt {
+            Statement::Describe(describe) => describes
+                .entry(describe.target.clone())
+                .or_default()
+                .push(describe.description.clone()),
+            Statement::If(if_stmt) => {
+                collect_describes(&if_stmt.then_block, describes);
+                if let Some(else_block) = &if_stmt.else_block {
+                    collect_describes(else_block, describes);
+                }
+            }
+            Statement::While(while_stmt) => {
+                collect_describes(&while_stmt.body, describes);
+                if let Some(else_block) = &while_stmt.else_block {
+                    collect_describes(else_block, describes);
+                }
+            }
+            Statement::Match(match_stmt) => {
+                for arm in &match_stmt.arms {
+                    collect_describes(&arm.body, describes);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Join a doc comment with describe texts, one per line
+fn combine(doc_comment: Option<&str>, describes: Option<&Vec<String>>) -> Option<String> {
+    let lines: Vec<&str> = doc_comment
+        .into_iter()
+        .chain(describes.into_iter().flatten().map(String::as_str))
+        .collect();
+
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+/// Add a description to an entity's documentation, one per line
+///
+/// A description the documentation already holds is not repeated, so
+/// describing an entity twice (or in a loop) gives the same result at
+/// compile time and at runtime.
+pub fn append_description(doc: Option<&str>, description: &str) -> String {
+    match doc {
+        Some(doc) if format!("\n{}\n", doc).contains(&format!("\n{}\n", description)) => {
+            doc.to_string()
+        }
+        Some(doc) => format!("{}\n{}", doc, description),
+        None => description.to_string(),
+    }
+}
+
+/// Render the entries as a Markdown document
+///
+/// Each entity gets a second-level heading with its signature, followed by
+/// one paragraph per documentation line.
+pub fn render_markdown(title: &str, entries: &[DocEntry]) -> String {
+    let mut markdown = format!("# {}\n", title);
+
+    for entry in entries {
+        markdown.push_str(&format!("\n## `{}`\n", entry.signature));
+        if let Some(doc) = &entry.documentation {
+            for line in doc.lines() {
+                markdown.push_str(&format!("\n{}\n", line));
+            }
+        }
+    }
+
+    markdown
+}
+
+/// Render the entries as a standalone HTML page
+pub fn render_html(title: &str, entries: &[DocEntry]) -> String {
+    let mut html = String::new();
+
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n", escape_html(title)));
+    html.push_str(
+        "<style>\n\
+         body { font-family: sans-serif; max-width: 48em; margin: 2em auto; }\n\
+         .entity { border-top: 1px solid #ddd; padding: 0.5em 0; }\n\
+         code { background: #f4f4f4; padding: 0.1em 0.3em; }\n\
+         </style>\n",
+    );
+    html.push_str("</head>\n<body>\n");
+    html.push_str(&format!("<h1>{}</h1>\n", escape_html(title)));
+
+    for entry in entries {
+        html.push_str(&format!(
+            "<div class=\"entity\" id=\"{}\">\n",
+            escape_html(&entry.name)
+        ));
+        html.push_str(&format!(
+            "<h2><code>{}</code></h2>\n",
+            escape_html(&entry.signature)
+        ));
+        if let Some(doc) = &entry.documentation {
+            for line in doc.lines() {
+                html.push_str(&format!("<p>{}</p>\n", escape_html(line)));
+            }
+        }
+        html.push_str("</div>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Escape text for use inside HTML elements and attributes
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, symbol_table::SymbolTable};
+
+    fn entries(source: &str) -> Vec<DocEntry> {
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        collect_entries(&parser.parse_program().unwrap())
+    }
+
+    const SOURCE: &str = r#"
+    /// Adds two integers
+    fn f$0(v$0 int, v$1 int) -> int {
+        describe(f$0, "Result can overflow");
+        ret v$0 + v$1;
+    }
+
+    main {
+        /// The answer
+        int c$0 = 42;
+        int v$0 = 1;
+        print(f$0(c$0, v$0));
+    }
+    "#;
+
+    #[test]
+    fn test_collects_entries_in_order() {
+        let entries = entries(SOURCE);
+
+        // v$0 has no documentation, so it is left out
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].signature, "fn f$0(v$0 int, v$1 int) -> int");
+        assert_eq!(
+            entries[0].documentation.as_deref(),
+            Some("Adds two integers\nResult can overflow")
+        );
+        assert_eq!(entries[1].signature, "int c$0");
+        assert_eq!(entries[1].documentation.as_deref(), Some("The answer"));
+    }
+
+    #[test]
+    fn test_annotated_signature() {
+        let source = r#"
+        fn f$0(v$0 int, v$1 int) -> int {
+            describe(v$0, "the first addend");
+            ret v$0 + v$1;
+        }
+
+        main {
+            print(f$0(1, 2));
+        }
+        "#;
+
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        let program = parser.parse_program().unwrap();
+        let Item::Function(func) = &program.items[0] else {
+            panic!("Expected a function");
+        };
+
+        assert_eq!(
+            annotated_signature(func),
+            "f$0(v$0: int /* the first addend */, v$1: int) -> int"
+        );
+    }
+
+    #[test]
+    fn test_markdown_output() {
+        let markdown = render_markdown("prog.zvar", &entries(SOURCE));
+
+        assert!(markdown.starts_with("# prog.zvar\n"));
+        assert!(markdown.contains("## `fn f$0(v$0 int, v$1 int) -> int`\n"));
+        assert!(markdown.contains("\nAdds two integers\n"));
+        assert!(markdown.contains("\nResult can overflow\n"));
+
+        // Declaration order is kept
+        let function = markdown.find("## `fn f$0").unwrap();
+        let constant = markdown.find("## `int c$0`").unwrap();
+        assert!(function < constant);
+    }
+
+    #[test]
+    fn test_html_output() {
+        let html = render_html("prog.zvar", &entries(SOURCE));
+
+        assert!(html.contains("<title>prog.zvar</title>"));
+        assert!(html.contains("<div class=\"entity\" id=\"f$0\">"));
+        assert!(html.contains("<h2><code>fn f$0(v$0 int, v$1 int) -&gt; int</code></h2>"));
+        assert!(html.contains("<p>Adds two integers</p>"));
+        assert!(html.contains("<p>Result can overflow</p>"));
+        assert!(html.contains("<h2><code>int c$0</code></h2>"));
+        assert!(html.contains("<p>The answer</p>"));
+    }
+
+    #[test]
+    fn test_docs_coverage() {
+        let source = r#"
+        /// Documented
+        fn f$0() -> int {
+            ret 1;
+        }
+
+        fn f$1() -> int {
+            ret 2;
+        }
+
+        main {
+            print(f$0() + f$1());
+        }
+        "#;
+
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        parser.parse_program().unwrap();
+
+        let coverage = DocsCoverage::from_symbol_table(&symbol_table);
+        assert_eq!(
+            coverage,
+            DocsCoverage {
+                documented: 1,
+                total: 2
+            }
+        );
+        assert_eq!(coverage.percentage(), 50.0);
+        assert_eq!(coverage.to_string(), "1/2 functions documented (50.0%)");
+    }
+}
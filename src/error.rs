@@ -1,58 +1,74 @@
 //! Error types for the zvar language compiler
 
-use crate::span::Span;
-use thiserror::Error;
+use crate::{prelude::*, span::Span};
+use core::fmt;
 
 /// Main error type for the zvar language
-#[derive(Error, Debug)]
+#[derive(Debug)]
 pub enum ZvarError {
     // Lexer errors
-    #[error("Invalid number '{value}' at {span}")]
-    InvalidNumber { span: Span, value: String },
+    InvalidNumber {
+        span: Span,
+        value: String,
+    },
+
+    UnknownIdentifier {
+        span: Span,
+        name: String,
+    },
 
-    #[error("Unknown identifier '{name}' at {span}")]
-    UnknownIdentifier { span: Span, name: String },
+    InvalidEntityNumber {
+        span: Span,
+        entity: String,
+    },
 
-    #[error("Invalid entity number in '{entity}' at {span}")]
-    InvalidEntityNumber { span: Span, entity: String },
+    EntityNumberTooLarge {
+        span: Span,
+        entity: String,
+    },
 
-    #[error("Unexpected character '{character}' at {span}")]
-    UnexpectedCharacter { span: Span, character: char },
+    UnexpectedCharacter {
+        span: Span,
+        character: char,
+    },
 
     // Parser errors
-    #[error("Expected {expected}, found {found} at {span}")]
     UnexpectedToken {
         span: Span,
         expected: String,
         found: String,
     },
 
-    #[error("Missing semicolon at {span}")]
-    MissingSemicolon { span: Span },
+    MissingSemicolon {
+        span: Span,
+    },
 
-    #[error("Invalid assignment target at {span}")]
-    InvalidAssignmentTarget { span: Span },
+    InvalidAssignmentTarget {
+        span: Span,
+    },
 
-    #[error("Undefined entity '{name}' at {span}")]
-    UndefinedEntity { span: Span, name: String },
+    FunctionAsValue {
+        span: Span,
+        name: String,
+    },
+
+    UndefinedEntity {
+        span: Span,
+        name: String,
+    },
 
-    #[error("Entity '{name}' already defined at {span}")]
     EntityAlreadyDefined {
         span: Span,
         name: String,
         previous_span: Option<Span>,
     },
 
-    #[error("Type mismatch at {span}: expected {expected}, found {found}")]
     TypeMismatch {
         span: Span,
         expected: String,
         found: String,
     },
 
-    #[error(
-        "Wrong number of arguments for '{name}' at {span}: expected {expected}, found {found}"
-    )]
     WrongArgumentCount {
         span: Span,
         name: String,
@@ -60,34 +76,104 @@ pub enum ZvarError {
         found: usize,
     },
 
+    MissingReturn {
+        span: Span,
+        name: String,
+        return_type: String,
+    },
+
+    ImpureFunction {
+        span: Span,
+        name: String,
+        reason: String,
+    },
+
     // Codegen errors
-    #[error("Code generation failed: {message}")]
-    CodegenError { message: String },
+    CodegenError {
+        message: String,
+    },
 
     // Runtime errors
-    #[error("Runtime error: {message}")]
-    RuntimeError { message: String },
+    RuntimeError {
+        message: String,
+    },
 
-    #[error("Stack overflow")]
-    StackOverflow,
+    StackOverflow {
+        /// Depth of the value stack or call stack that hit its limit
+        depth: usize,
+        /// Functions active when the limit was hit, innermost first
+        backtrace: Vec<String>,
+    },
 
-    #[error("Stack underflow")]
     StackUnderflow,
 
-    #[error("Division by zero{}", span.map_or(String::new(), |s| format!(" at {}", s)))]
-    DivisionByZero { span: Option<Span> },
+    DivisionByZero {
+        span: Option<Span>,
+    },
 
-    #[error("Cannot assign to constant '{name}' at {span}")]
-    CannotAssignToConstant { span: Span, name: String },
+    IntegerOverflow {
+        span: Option<Span>,
+    },
+
+    CannotAssignToConstant {
+        span: Span,
+        name: String,
+    },
+
+    BreakOutsideLoop {
+        span: Span,
+    },
+
+    RecursiveConstant {
+        span: Span,
+        name: String,
+    },
 
     // IO errors
-    #[error("IO error: {message}")]
-    IoError { message: String },
+    IoError {
+        message: String,
+    },
+
+    FileError {
+        message: String,
+    },
+}
 
-    #[error("File error: {message}")]
-    FileError { message: String },
+impl fmt::Display for ZvarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZvarError::InvalidNumber { span, value } => write!(f, "Invalid number '{value}' at {span}"),
+            ZvarError::UnknownIdentifier { span, name } => write!(f, "Unknown identifier '{name}' at {span}"),
+            ZvarError::InvalidEntityNumber { span, entity } => write!(f, "Invalid entity number in '{entity}' at {span}"),
+            ZvarError::EntityNumberTooLarge { span, entity } => write!(f, "Entity number too large in '{entity}' at {span} (max {})", u32::MAX),
+            ZvarError::UnexpectedCharacter { span, character } => write!(f, "Unexpected character '{character}' at {span}"),
+            ZvarError::UnexpectedToken { span, expected, found } => write!(f, "Expected {expected}, found {found} at {span}"),
+            ZvarError::MissingSemicolon { span } => write!(f, "Missing semicolon at {span}"),
+            ZvarError::InvalidAssignmentTarget { span } => write!(f, "Invalid assignment target at {span}"),
+            ZvarError::FunctionAsValue { span, name } => write!(f, "Functions cannot be used as values; did you mean {name}(...)? at {span}"),
+            ZvarError::UndefinedEntity { span, name } => write!(f, "Undefined entity '{name}' at {span}"),
+            ZvarError::EntityAlreadyDefined { span, name, .. } => write!(f, "Entity '{name}' already defined at {span}"),
+            ZvarError::TypeMismatch { span, expected, found } => write!(f, "Type mismatch at {span}: expected {expected}, found {found}"),
+            ZvarError::WrongArgumentCount { span, name, expected, found } => write!(f, "Wrong number of arguments for '{name}' at {span}: expected {expected}, found {found}"),
+            ZvarError::MissingReturn { span, name, return_type } => write!(f, "Function '{name}' must return {return_type} but can reach its end without a return statement at {span}"),
+            ZvarError::ImpureFunction { span, name, reason } => write!(f, "Pure function '{name}' {reason} at {span}"),
+            ZvarError::CodegenError { message } => write!(f, "Code generation failed: {message}"),
+            ZvarError::RuntimeError { message } => write!(f, "Runtime error: {message}"),
+            ZvarError::StackOverflow { depth, backtrace } => write!(f, "Stack overflow at depth {depth}{}", format_backtrace(backtrace)),
+            ZvarError::StackUnderflow => f.write_str("Stack underflow"),
+            ZvarError::DivisionByZero { span } => write!(f, "Division by zero{}", span.map_or(String::new(), |s| format!(" at {}", s))),
+            ZvarError::IntegerOverflow { span } => write!(f, "Integer overflow{}", span.map_or(String::new(), |s| format!(" at {}", s))),
+            ZvarError::CannotAssignToConstant { span, name } => write!(f, "Cannot assign to constant '{name}' at {span}"),
+            ZvarError::BreakOutsideLoop { span } => write!(f, "'break' outside of a loop at {span}"),
+            ZvarError::RecursiveConstant { span, name } => write!(f, "Constant '{name}' is defined in terms of itself at {span}"),
+            ZvarError::IoError { message } => write!(f, "IO error: {message}"),
+            ZvarError::FileError { message } => write!(f, "File error: {message}"),
+        }
+    }
 }
 
+impl core::error::Error for ZvarError {}
+
 impl ZvarError {
     /// Get the span associated with this error, if any
     pub fn span(&self) -> Option<Span> {
@@ -95,30 +181,94 @@ impl ZvarError {
             ZvarError::InvalidNumber { span, .. } => Some(*span),
             ZvarError::UnknownIdentifier { span, .. } => Some(*span),
             ZvarError::InvalidEntityNumber { span, .. } => Some(*span),
+            ZvarError::EntityNumberTooLarge { span, .. } => Some(*span),
             ZvarError::UnexpectedCharacter { span, .. } => Some(*span),
             ZvarError::UnexpectedToken { span, .. } => Some(*span),
             ZvarError::MissingSemicolon { span } => Some(*span),
             ZvarError::InvalidAssignmentTarget { span } => Some(*span),
+            ZvarError::FunctionAsValue { span, .. } => Some(*span),
             ZvarError::UndefinedEntity { span, .. } => Some(*span),
             ZvarError::EntityAlreadyDefined { span, .. } => Some(*span),
             ZvarError::TypeMismatch { span, .. } => Some(*span),
             ZvarError::WrongArgumentCount { span, .. } => Some(*span),
+            ZvarError::MissingReturn { span, .. } => Some(*span),
+            ZvarError::ImpureFunction { span, .. } => Some(*span),
             ZvarError::CannotAssignToConstant { span, .. } => Some(*span),
+            ZvarError::RecursiveConstant { span, .. } => Some(*span),
+            ZvarError::BreakOutsideLoop { span } => Some(*span),
             ZvarError::DivisionByZero { span, .. } => *span,
+            ZvarError::IntegerOverflow { span } => *span,
             _ => None,
         }
     }
 
     /// Check if this is a compile-time error
     pub fn is_compile_time(&self) -> bool {
-        match self {
+        !matches!(
+            self,
             ZvarError::RuntimeError { .. }
-            | ZvarError::StackOverflow
-            | ZvarError::StackUnderflow => false,
-            _ => true,
+                | ZvarError::StackOverflow { .. }
+                | ZvarError::StackUnderflow
+                | ZvarError::IntegerOverflow { .. }
+                | ZvarError::DivisionByZero { .. }
+        )
+    }
+
+    /// Process exit code for a command that failed with this error
+    ///
+    /// `2` for errors found before running (lexing, parsing, type checking,
+    /// codegen), `3` for errors while running and `4` for file and IO errors.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ZvarError::IoError { .. } | ZvarError::FileError { .. } => 4,
+            _ if self.is_compile_time() => 2,
+            _ => 3,
         }
     }
 
+    /// Render the error with the offending source line underlined
+    ///
+    /// Errors without a span render the same as their `Display` output.
+    /// Multi-line spans underline from the start column to the end of the
+    /// first line.
+    pub fn render(&self, source: &str) -> String {
+        let message = self.to_string();
+
+        let Some(span) = self.span() else {
+            return message;
+        };
+        let Some(line) = source
+            .lines()
+            .nth(span.start_line.saturating_sub(1) as usize)
+        else {
+            return message;
+        };
+
+        let line_width = line.chars().count() as u32;
+        let start = span.start_column.max(1);
+        let end = if span.is_single_line() {
+            span.end_column.max(start)
+        } else {
+            line_width.max(start)
+        };
+
+        // Keep tabs so the caret lines up with the source text
+        let indent: String = line
+            .chars()
+            .take(start as usize - 1)
+            .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+            .collect();
+        let underline = "^".repeat((end - start + 1) as usize);
+
+        let line_number = span.start_line.to_string();
+        let gutter = " ".repeat(line_number.len());
+
+        format!(
+            "{}\n{} |\n{} | {}\n{} | {}{}",
+            message, gutter, line_number, line, gutter, indent, underline
+        )
+    }
+
     /// Create a simple runtime error
     pub fn runtime(message: impl Into<String>) -> Self {
         ZvarError::RuntimeError {
@@ -132,8 +282,25 @@ impl ZvarError {
             message: message.into(),
         }
     }
+
+    /// Create a stack overflow error without call information
+    pub fn stack_overflow(depth: usize) -> Self {
+        ZvarError::StackOverflow {
+            depth,
+            backtrace: Vec::new(),
+        }
+    }
+}
+
+/// Format call frames as indented lines, one per frame
+fn format_backtrace(backtrace: &[String]) -> String {
+    backtrace
+        .iter()
+        .map(|frame| format!("\n  in {}", frame))
+        .collect()
 }
 
+#[cfg(not(all(feature = "no_std", not(test))))]
 impl From<std::io::Error> for ZvarError {
     fn from(err: std::io::Error) -> Self {
         ZvarError::IoError {
@@ -175,6 +342,44 @@ mod tests {
         assert!(error.is_compile_time());
     }
 
+    #[test]
+    fn test_exit_codes() {
+        let span = Span::new(1, 1, 1, 1);
+        let compile_time = [
+            ZvarError::UnexpectedCharacter {
+                span,
+                character: '#',
+            },
+            ZvarError::MissingSemicolon { span },
+            ZvarError::TypeMismatch {
+                span,
+                expected: "int".to_string(),
+                found: "str".to_string(),
+            },
+        ];
+        for error in &compile_time {
+            assert_eq!(error.exit_code(), 2, "{}", error);
+        }
+
+        let runtime = [
+            ZvarError::runtime("test"),
+            ZvarError::StackUnderflow,
+            ZvarError::DivisionByZero { span: Some(span) },
+        ];
+        for error in &runtime {
+            assert_eq!(error.exit_code(), 3, "{}", error);
+        }
+
+        assert_eq!(ZvarError::file_error("missing").exit_code(), 4);
+        assert_eq!(
+            ZvarError::IoError {
+                message: "closed".to_string()
+            }
+            .exit_code(),
+            4
+        );
+    }
+
     #[test]
     fn test_error_creation_helpers() {
         let error = ZvarError::runtime("test message");
@@ -183,4 +388,42 @@ mod tests {
             _ => panic!("Wrong error type"),
         }
     }
+
+    #[test]
+    fn test_stack_overflow_display() {
+        let error = ZvarError::StackOverflow {
+            depth: 3,
+            backtrace: vec!["f$0".to_string(), "main".to_string()],
+        };
+        assert_eq!(
+            error.to_string(),
+            "Stack overflow at depth 3\n  in f$0\n  in main"
+        );
+        assert_eq!(
+            ZvarError::stack_overflow(2).to_string(),
+            "Stack overflow at depth 2"
+        );
+    }
+
+    #[test]
+    fn test_render_source_snippet() {
+        let source = "main {\n    v$7 = 1;\n}";
+        let error = ZvarError::UndefinedEntity {
+            span: Span::new(2, 5, 2, 7),
+            name: "v$7".to_string(),
+        };
+
+        let rendered = error.render(source);
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert_eq!(lines[0], error.to_string());
+        assert_eq!(lines[2], "2 |     v$7 = 1;");
+        assert_eq!(lines[3], "  |     ^^^");
+    }
+
+    #[test]
+    fn test_render_without_span() {
+        let error = ZvarError::runtime("boom");
+        assert_eq!(error.render("main {}"), error.to_string());
+    }
 }
@@ -3,15 +3,18 @@
 pub mod token;
 
 use crate::error::ZvarError;
+use crate::prelude::*;
 use crate::span::Span;
 use token::Token;
 
 pub struct Lexer<'a> {
     input: &'a str,
-    position: usize, // Current position in input
+    position: usize, // Byte offset of the current character in input
     current_char: Option<char>,
     line: u32,
     column: u32,
+    // Errors recovered from so far; `None` stops at the first error instead
+    errors: Option<Vec<ZvarError>>,
 }
 
 impl<'a> Lexer<'a> {
@@ -22,11 +25,33 @@ impl<'a> Lexer<'a> {
             current_char: None,
             line: 1,
             column: 1,
+            errors: None,
         };
         lexer.current_char = lexer.input.chars().next();
         lexer
     }
 
+    /// Keep lexing after an unknown identifier instead of failing
+    ///
+    /// The identifier becomes a `Token::Error` placeholder and its error is
+    /// recorded, so a parser can report several problems in one pass.
+    pub fn set_collect_errors(&mut self, collect: bool) {
+        self.errors = collect.then(Vec::new);
+    }
+
+    /// Errors recovered from so far; empty unless collection is enabled
+    pub fn errors(&self) -> &[ZvarError] {
+        self.errors.as_deref().unwrap_or_default()
+    }
+
+    /// Take the errors recovered from so far, leaving collection enabled
+    pub fn take_errors(&mut self) -> Vec<ZvarError> {
+        self.errors
+            .as_mut()
+            .map(core::mem::take)
+            .unwrap_or_default()
+    }
+
     /// Advance to the next character
     fn advance(&mut self) {
         if let Some(ch) = self.current_char {
@@ -36,15 +61,15 @@ impl<'a> Lexer<'a> {
             } else {
                 self.column += 1;
             }
+            self.position += ch.len_utf8();
         }
 
-        self.position += 1;
-        self.current_char = self.input.chars().nth(self.position);
+        self.current_char = self.input[self.position..].chars().next();
     }
 
     /// Peek at the next character without advancing
     fn peek(&self) -> Option<char> {
-        self.input.chars().nth(self.position + 1)
+        self.input[self.position..].chars().nth(1)
     }
 
     /// Skip whitespace (except newlines, which we track)
@@ -52,6 +77,15 @@ impl<'a> Lexer<'a> {
         while let Some(ch) = self.current_char {
             if ch.is_whitespace() && ch != '\n' {
                 self.advance();
+            } else if ch == '\\' && self.peek() == Some('\n') {
+                // Line continuation: the newline does not end the logical line
+                self.advance(); // \
+                self.advance(); // newline
+            } else if self.input[self.position..].starts_with("\\\r\n") {
+                // The same with a CRLF line ending
+                self.advance(); // \
+                self.advance(); // \r
+                self.advance(); // \n
             } else {
                 break;
             }
@@ -155,22 +189,47 @@ impl<'a> Lexer<'a> {
         // Check for keywords
         let token = match identifier {
             "fn" => Token::Fn,
+            "pure" => Token::Pure,
+            "memo" => Token::Memo,
+            "match" => Token::Match,
+            "_" => Token::Underscore,
             "main" => Token::Main,
             "ret" => Token::Ret,
             "int" => Token::Int,
             "str" => Token::Str,
             "bool" => Token::Bool,
-            "true" => Token::True,
-            "false" => Token::False,
+            "true" => Token::Boolean(true),
+            "false" => Token::Boolean(false),
             "if" => Token::If,
             "else" => Token::Else,
+            "while" => Token::While,
+            "break" => Token::Break,
             "describe" => Token::Describe,
             "print" => Token::Print,
+            "random" => Token::Random,
+            "eq_ignore_case" => Token::EqIgnoreCase,
+            "to_upper" => Token::ToUpper,
+            "to_lower" => Token::ToLower,
+            "trim" => Token::Trim,
+            "len" => Token::Len,
+            "byte_len" => Token::ByteLen,
+            "type" => Token::Type,
+            // Only type aliases may have real names, and they are capitalized
+            name if name.starts_with(|c: char| c.is_ascii_uppercase()) && !name.contains('$') => {
+                Token::TypeName(name.to_string())
+            }
             _ => {
-                return Err(ZvarError::UnknownIdentifier {
+                let error = ZvarError::UnknownIdentifier {
                     span: Span::new(self.line, start_col, self.line, self.column - 1),
                     name: identifier.to_string(),
-                });
+                };
+                match &mut self.errors {
+                    Some(errors) => {
+                        errors.push(error);
+                        Token::Error(identifier.to_string())
+                    }
+                    None => return Err(error),
+                }
             }
         };
 
@@ -179,33 +238,45 @@ impl<'a> Lexer<'a> {
 
     /// Parse entity tokens (v$N, c$N, f$N)
     fn parse_entity(&self, identifier: &str) -> Result<Option<Token>, ZvarError> {
-        if identifier.len() < 3 {
+        let mut chars = identifier.chars();
+        let prefix = chars.next();
+        if chars.next() != Some('$') || identifier.len() < 3 {
             return Ok(None);
         }
 
-        let prefix = &identifier[0..1];
-        if &identifier[1..2] != "$" {
+        if !matches!(prefix, Some('v' | 'c' | 'f')) {
             return Ok(None);
         }
 
+        let span = Span::new(
+            self.line,
+            self.column - identifier.chars().count() as u32,
+            self.line,
+            self.column - 1,
+        );
+
+        // Entity numbers must be made of digits only (rejects v$1a, c$x, ...)
         let number_str = &identifier[2..];
+        if !number_str.chars().all(|ch| ch.is_ascii_digit()) {
+            return Err(ZvarError::InvalidEntityNumber {
+                span,
+                entity: identifier.to_string(),
+            });
+        }
+
+        // All digits, so the only way parsing can fail is overflow
         let number: u32 = number_str
             .parse()
-            .map_err(|_| ZvarError::InvalidEntityNumber {
-                span: Span::new(
-                    self.line,
-                    self.column - identifier.len() as u32,
-                    self.line,
-                    self.column - 1,
-                ),
+            .map_err(|_| ZvarError::EntityNumberTooLarge {
+                span,
                 entity: identifier.to_string(),
             })?;
 
         let token = match prefix {
-            "v" => Token::Variable(number),
-            "c" => Token::Constant(number),
-            "f" => Token::Function(number),
-            _ => return Ok(None),
+            Some('v') => Token::Variable(number),
+            Some('c') => Token::Constant(number),
+            Some('f') => Token::Function(number),
+            _ => unreachable!(),
         };
 
         Ok(Some(token))
@@ -255,6 +326,8 @@ impl<'a> Lexer<'a> {
                     }
                 }
 
+                Some('\\') if self.peek() == Some('\n') => self.skip_whitespace(),
+
                 Some(ch) if ch.is_ascii_digit() => {
                     let number = self.read_number()?;
                     return Ok(Token::Integer(number));
@@ -265,6 +338,10 @@ impl<'a> Lexer<'a> {
                         self.advance(); // =
                         self.advance(); // =
                         return Ok(Token::Equal);
+                    } else if self.peek() == Some('>') {
+                        self.advance(); // =
+                        self.advance(); // >
+                        return Ok(Token::FatArrow);
                     } else {
                         self.advance();
                         return Ok(Token::Assign);
@@ -374,12 +451,14 @@ impl<'a> Lexer<'a> {
                     }
                 }
                 Some('*') => {
-                    self.advance();
-                    return Ok(Token::Multiply);
-                }
-                Some('=') => {
-                    self.advance();
-                    return Ok(Token::Assign);
+                    if self.peek() == Some('*') {
+                        self.advance(); // *
+                        self.advance(); // *
+                        return Ok(Token::Power);
+                    } else {
+                        self.advance();
+                        return Ok(Token::Multiply);
+                    }
                 }
                 Some('(') => {
                     self.advance();
@@ -405,6 +484,14 @@ impl<'a> Lexer<'a> {
                     self.advance();
                     return Ok(Token::Comma);
                 }
+                Some('?') => {
+                    self.advance();
+                    return Ok(Token::Question);
+                }
+                Some(':') => {
+                    self.advance();
+                    return Ok(Token::Colon);
+                }
 
                 Some(ch) => {
                     return Err(ZvarError::UnexpectedCharacter {
@@ -418,12 +505,29 @@ impl<'a> Lexer<'a> {
 
     /// Tokenize the entire input
     pub fn tokenize(&mut self) -> Result<Vec<Token>, ZvarError> {
+        let tokens = self.tokenize_with_spans()?;
+        Ok(tokens.into_iter().map(|(token, _)| token).collect())
+    }
+
+    /// Tokenize the entire input, keeping the source span of every token
+    pub fn tokenize_with_spans(&mut self) -> Result<Vec<(Token, Span)>, ZvarError> {
         let mut tokens = Vec::new();
 
         loop {
+            self.skip_whitespace();
+            let (start_line, start_col) = (self.line, self.column);
+
             let token = self.next_token()?;
+
+            // Newlines and end of file have no width
+            let span = if self.line == start_line && self.column > start_col {
+                Span::new(start_line, start_col, self.line, self.column - 1)
+            } else {
+                Span::single(start_line, start_col)
+            };
+
             let is_eof = matches!(token, Token::Eof);
-            tokens.push(token);
+            tokens.push((token, span));
 
             if is_eof {
                 break;
@@ -440,7 +544,7 @@ mod tests {
 
     #[test]
     fn test_basic_tokens() {
-        let mut lexer = Lexer::new("+ - * / = ( ) { } ; ,");
+        let mut lexer = Lexer::new("+ - * / = ( ) { } ; , ? :");
         let tokens = lexer.tokenize().unwrap();
 
         assert_eq!(tokens[0], Token::Plus);
@@ -448,6 +552,17 @@ mod tests {
         assert_eq!(tokens[2], Token::Multiply);
         assert_eq!(tokens[3], Token::Divide);
         assert_eq!(tokens[4], Token::Assign);
+        assert_eq!(tokens[11], Token::Question);
+        assert_eq!(tokens[12], Token::Colon);
+    }
+
+    #[test]
+    fn test_power_token() {
+        let mut lexer = Lexer::new("2 ** 3 * 4");
+        let tokens = lexer.tokenize().unwrap();
+
+        assert_eq!(tokens[1], Token::Power);
+        assert_eq!(tokens[3], Token::Multiply);
     }
 
     #[test]
@@ -462,7 +577,7 @@ mod tests {
 
     #[test]
     fn test_keywords() {
-        let mut lexer = Lexer::new("fn main ret int describe print");
+        let mut lexer = Lexer::new("fn main ret int describe print pure random");
         let tokens = lexer.tokenize().unwrap();
 
         assert_eq!(tokens[0], Token::Fn);
@@ -471,6 +586,18 @@ mod tests {
         assert_eq!(tokens[3], Token::Int);
         assert_eq!(tokens[4], Token::Describe);
         assert_eq!(tokens[5], Token::Print);
+        assert_eq!(tokens[6], Token::Pure);
+        assert_eq!(tokens[7], Token::Random);
+    }
+
+    #[test]
+    fn test_boolean_literals() {
+        let mut lexer = Lexer::new("true false bool");
+        let tokens = lexer.tokenize().unwrap();
+
+        assert_eq!(tokens[0], Token::Boolean(true));
+        assert_eq!(tokens[1], Token::Boolean(false));
+        assert_eq!(tokens[2], Token::Bool);
     }
 
     #[test]
@@ -500,4 +627,129 @@ mod tests {
 
         assert_eq!(tokens[0], Token::String("".to_string()));
     }
+
+    #[test]
+    fn test_entity_number_too_large() {
+        let mut lexer = Lexer::new("v$99999999999");
+        let result = lexer.tokenize();
+
+        match result {
+            Err(err @ ZvarError::EntityNumberTooLarge { .. }) => {
+                assert!(err.to_string().contains("max 4294967295"));
+            }
+            other => panic!("Expected EntityNumberTooLarge, got {:?}", other),
+        }
+
+        // The largest representable entity number is still accepted
+        let mut lexer = Lexer::new("c$4294967295");
+        let tokens = lexer.tokenize().unwrap();
+        assert_eq!(tokens[0], Token::Constant(u32::MAX));
+    }
+
+    #[test]
+    fn test_malformed_entity_number() {
+        let mut lexer = Lexer::new("v$1a");
+        let result = lexer.tokenize();
+
+        assert!(matches!(
+            result,
+            Err(ZvarError::InvalidEntityNumber { ref entity, .. }) if entity == "v$1a"
+        ));
+    }
+
+    #[test]
+    fn test_type_alias_tokens() {
+        let mut lexer = Lexer::new("type Meters = int;");
+        let tokens = lexer.tokenize().unwrap();
+
+        assert_eq!(tokens[0], Token::Type);
+        assert_eq!(tokens[1], Token::TypeName("Meters".to_string()));
+        assert_eq!(tokens[2], Token::Assign);
+        assert_eq!(tokens[3], Token::Int);
+
+        // Lowercase names are still rejected
+        let mut lexer = Lexer::new("meters");
+        assert!(matches!(
+            lexer.tokenize(),
+            Err(ZvarError::UnknownIdentifier { .. })
+        ));
+    }
+
+    #[test]
+    fn test_collect_unknown_identifiers() {
+        let source = "int v$0 = foo;\nbar(v$0);";
+
+        // Fail fast by default
+        assert!(matches!(
+            Lexer::new(source).tokenize(),
+            Err(ZvarError::UnknownIdentifier { ref name, .. }) if name == "foo"
+        ));
+
+        let mut lexer = Lexer::new(source);
+        lexer.set_collect_errors(true);
+        let tokens = lexer.tokenize().unwrap();
+
+        assert_eq!(tokens[3], Token::Error("foo".to_string()));
+        assert_eq!(tokens[6], Token::Error("bar".to_string()));
+        let names: Vec<String> = lexer
+            .errors()
+            .iter()
+            .map(|error| error.to_string())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "Unknown identifier 'foo' at 1:11-13",
+                "Unknown identifier 'bar' at 2:1-3"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_token_spans() {
+        let mut lexer = Lexer::new("int v$0 = 5;\n  print(v$0);");
+        let tokens = lexer.tokenize_with_spans().unwrap();
+
+        assert_eq!(tokens[0], (Token::Int, Span::new(1, 1, 1, 3)));
+        assert_eq!(tokens[1], (Token::Variable(0), Span::new(1, 5, 1, 7)));
+        assert_eq!(tokens[4], (Token::Semicolon, Span::new(1, 12, 1, 12)));
+        assert_eq!(tokens[5], (Token::Newline, Span::single(1, 13)));
+        assert_eq!(tokens[6], (Token::Print, Span::new(2, 3, 2, 7)));
+        assert_eq!(tokens[8], (Token::Variable(0), Span::new(2, 9, 2, 11)));
+    }
+
+    #[test]
+    fn test_line_continuation() {
+        let mut lexer = Lexer::new("1 +\\\n 2");
+        let tokens = lexer.tokenize_with_spans().unwrap();
+
+        assert_eq!(tokens[0].0, Token::Integer(1));
+        assert_eq!(tokens[1].0, Token::Plus);
+        assert_eq!(tokens[2], (Token::Integer(2), Span::new(2, 2, 2, 2)));
+        assert_eq!(tokens[3].0, Token::Eof);
+
+        let mut lexer = Lexer::new("1 +\\\r\n 2");
+        let tokens = lexer.tokenize_with_spans().unwrap();
+
+        assert_eq!(tokens[1].0, Token::Plus);
+        assert_eq!(tokens[2], (Token::Integer(2), Span::new(2, 2, 2, 2)));
+        assert_eq!(tokens[3].0, Token::Eof);
+    }
+
+    #[test]
+    fn test_newline_in_string_is_rejected() {
+        let mut lexer = Lexer::new("\"line one\nline two\"");
+        assert!(matches!(
+            lexer.tokenize(),
+            Err(ZvarError::UnexpectedToken { .. })
+        ));
+    }
+
+    #[test]
+    fn test_non_ascii_string_literal() {
+        let mut lexer = Lexer::new("\"hé 😀\" v$0");
+        let tokens = lexer.tokenize_with_spans().unwrap();
+        assert_eq!(tokens[0].0, Token::String("hé 😀".to_string()));
+        assert_eq!(tokens[1], (Token::Variable(0), Span::new(1, 8, 1, 10)));
+    }
 }
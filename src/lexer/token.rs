@@ -1,6 +1,7 @@
 //! Token definitions for the zvar language
 
-use std::fmt;
+use crate::prelude::*;
+use core::fmt;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
@@ -10,28 +11,41 @@ pub enum Token {
     Boolean(bool), // true, false
 
     // Identifiers with prefixes
-    Variable(u32), // v$0, v$1, etc.
-    Constant(u32), // c$0, c$1, etc.
-    Function(u32), // f$0, f$1, etc.
+    Variable(u32),    // v$0, v$1, etc.
+    Constant(u32),    // c$0, c$1, etc.
+    Function(u32),    // f$0, f$1, etc.
+    TypeName(String), // Capitalized type alias name, e.g. Meters
 
     // Keywords
-    Fn,       // fn
-    Main,     // main
-    Ret,      // ret
-    Int,      // int
-    Str,      // str
-    Bool,     // bool
-    True,     // true
-    False,    // false
-    If,       // if
-    Else,     // else
-    Describe, // describe
-    Print,    // print
+    Fn,           // fn
+    Pure,         // pure
+    Memo,         // memo
+    Match,        // match
+    Main,         // main
+    Ret,          // ret
+    Int,          // int
+    Str,          // str
+    Bool,         // bool
+    If,           // if
+    Else,         // else
+    While,        // while
+    Break,        // break
+    Describe,     // describe
+    Print,        // print
+    Random,       // random
+    EqIgnoreCase, // eq_ignore_case
+    ToUpper,      // to_upper
+    ToLower,      // to_lower
+    Trim,         // trim
+    Len,          // len
+    ByteLen,      // byte_len
+    Type,         // type
 
     // Operators
     Plus,     // +
     Minus,    // -
     Multiply, // *
+    Power,    // **
     Divide,   // /
     Assign,   // =
 
@@ -56,13 +70,18 @@ pub enum Token {
     Semicolon,  // ;
     Comma,      // ,
     Arrow,      // ->
+    FatArrow,   // =>
+    Underscore, // _
+    Question,   // ?
+    Colon,      // :
 
     // Comments and Documentation
     DocComment(String), // /// comment
 
     // Special
-    Eof,     // End of file
-    Newline, // \n (for tracking lines)
+    Eof,           // End of file
+    Newline,       // \n (for tracking lines)
+    Error(String), // Unrecognized text, when the lexer collects errors
 }
 
 impl fmt::Display for Token {
@@ -74,21 +93,34 @@ impl fmt::Display for Token {
             Token::Variable(n) => write!(f, "v${}", n),
             Token::Constant(n) => write!(f, "c${}", n),
             Token::Function(n) => write!(f, "f${}", n),
+            Token::TypeName(name) => write!(f, "{}", name),
             Token::Fn => write!(f, "fn"),
+            Token::Pure => write!(f, "pure"),
+            Token::Memo => write!(f, "memo"),
+            Token::Match => write!(f, "match"),
             Token::Main => write!(f, "main"),
             Token::Ret => write!(f, "ret"),
             Token::Int => write!(f, "int"),
             Token::Str => write!(f, "str"),
             Token::Bool => write!(f, "bool"),
-            Token::True => write!(f, "true"),
-            Token::False => write!(f, "false"),
             Token::If => write!(f, "if"),
             Token::Else => write!(f, "else"),
+            Token::While => write!(f, "while"),
+            Token::Break => write!(f, "break"),
             Token::Describe => write!(f, "describe"),
             Token::Print => write!(f, "print"),
+            Token::Random => write!(f, "random"),
+            Token::EqIgnoreCase => write!(f, "eq_ignore_case"),
+            Token::ToUpper => write!(f, "to_upper"),
+            Token::ToLower => write!(f, "to_lower"),
+            Token::Trim => write!(f, "trim"),
+            Token::Len => write!(f, "len"),
+            Token::ByteLen => write!(f, "byte_len"),
+            Token::Type => write!(f, "type"),
             Token::Plus => write!(f, "+"),
             Token::Minus => write!(f, "-"),
             Token::Multiply => write!(f, "*"),
+            Token::Power => write!(f, "**"),
             Token::Divide => write!(f, "/"),
             Token::Assign => write!(f, "="),
             Token::Equal => write!(f, "=="),
@@ -107,9 +139,14 @@ impl fmt::Display for Token {
             Token::Semicolon => write!(f, ";"),
             Token::Comma => write!(f, ","),
             Token::Arrow => write!(f, "->"),
+            Token::FatArrow => write!(f, "=>"),
+            Token::Underscore => write!(f, "_"),
+            Token::Question => write!(f, "?"),
+            Token::Colon => write!(f, ":"),
             Token::DocComment(s) => write!(f, "/// {}", s),
             Token::Eof => write!(f, "EOF"),
             Token::Newline => write!(f, "\\n"),
+            Token::Error(text) => write!(f, "{}", text),
         }
     }
 }
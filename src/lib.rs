@@ -1,9 +1,50 @@
 //! The zvar programming language compiler and runtime
 //!
 //! A bytecode programming language that uses numbered variables and eliminates naming.
+//!
+//! With the `no_std` feature the library only needs `core` and `alloc`.
+//! Printed values then go to the writer set with
+//! [`vm::VM::set_output_writer`] and are dropped without one.
+
+#![cfg_attr(all(feature = "no_std", not(test)), no_std)]
+
+#[cfg(all(feature = "no_std", feature = "cli"))]
+compile_error!("the `cli` feature needs std; build with `--no-default-features --features no_std`");
+
+extern crate alloc;
+
+/// Console output has nowhere to go without std
+#[cfg(all(feature = "no_std", not(test)))]
+macro_rules! println {
+    ($($arg:tt)*) => {{
+        let _ = format_args!($($arg)*);
+    }};
+}
 
+#[cfg(all(feature = "no_std", not(test)))]
+macro_rules! eprintln {
+    ($($arg:tt)*) => {
+        println!($($arg)*)
+    };
+}
+
+/// Names the std prelude provides, taken from `alloc` so they exist without std
+mod prelude {
+    pub use alloc::{
+        boxed::Box,
+        collections::{BTreeMap, BTreeSet},
+        format,
+        rc::Rc,
+        string::{String, ToString},
+        vec,
+        vec::Vec,
+    };
+}
+
+#[cfg(feature = "cli")]
 pub mod cli;
 pub mod codegen;
+pub mod docs;
 pub mod error;
 pub mod lexer;
 pub mod parser;
@@ -11,10 +52,14 @@ pub mod span;
 pub mod symbol_table;
 pub mod types;
 pub mod vm;
+pub mod warning;
+
+#[cfg(test)]
+mod test_util;
 
 // Re-export commonly used types
 pub use error::{ZvarError, ZvarResult};
-pub use span::Span;
+pub use span::{Span, Spanned};
 
 /// Version information
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -25,20 +70,47 @@ pub fn init() {
     // Any global initialization can go here
 }
 
-/// Convenience function to compile and run zvar source code
-pub fn run_source(source: &str) -> ZvarResult<()> {
+/// Parse source into a program and the symbol table built while parsing
+pub fn parse(source: &str) -> ZvarResult<(parser::ast::Program, symbol_table::SymbolTable)> {
     let mut symbol_table = symbol_table::SymbolTable::new();
     let mut parser = parser::Parser::new(source, &mut symbol_table)?;
     let program = parser.parse_program()?;
+    Ok((program, symbol_table))
+}
 
-    let mut codegen = codegen::CodeGenerator::new();
-    let (bytecode, debug_info) = codegen.generate(&program, &symbol_table)?;
+/// Type check a parsed program
+pub fn typecheck(
+    program: &parser::ast::Program,
+    symbol_table: &symbol_table::SymbolTable,
+) -> ZvarResult<()> {
+    types::TypeChecker::new(symbol_table).check_program(program)
+}
 
+/// Generate bytecode and debug information for a checked program
+pub fn generate(
+    program: &parser::ast::Program,
+    symbol_table: &symbol_table::SymbolTable,
+) -> ZvarResult<(
+    codegen::instruction::Bytecode,
+    codegen::debug_info::DebugInfo,
+)> {
+    codegen::CodeGenerator::new().generate(program, symbol_table)
+}
+
+/// Run bytecode on a fresh VM
+pub fn execute(
+    bytecode: codegen::instruction::Bytecode,
+    debug_info: codegen::debug_info::DebugInfo,
+) -> ZvarResult<()> {
     let mut vm = vm::VM::new();
     vm.load(bytecode, Some(debug_info));
-    vm.run()?;
+    vm.run()
+}
 
-    Ok(())
+/// Convenience function to compile and run zvar source code
+pub fn run_source(source: &str) -> ZvarResult<()> {
+    let (bytecode, debug_info) = compile_source(source)?;
+    execute(bytecode, debug_info)
 }
 
 /// Convenience function to compile zvar source to bytecode
@@ -48,12 +120,9 @@ pub fn compile_source(
     codegen::instruction::Bytecode,
     codegen::debug_info::DebugInfo,
 )> {
-    let mut symbol_table = symbol_table::SymbolTable::new();
-    let mut parser = parser::Parser::new(source, &mut symbol_table)?;
-    let program = parser.parse_program()?;
-
-    let mut codegen = codegen::CodeGenerator::new();
-    codegen.generate(&program, &symbol_table)
+    let (program, symbol_table) = parse(source)?;
+    typecheck(&program, &symbol_table)?;
+    generate(&program, &symbol_table)
 }
 
 #[cfg(test)]
@@ -80,6 +149,12 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_empty_program() {
+        assert!(run_source("").is_ok());
+        assert!(run_source("main {}").is_ok());
+    }
+
     #[test]
     fn test_arithmetic_program() {
         let source = r#"
@@ -111,4 +186,49 @@ mod tests {
         let result = run_source(source);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_phases_separately() {
+        let source = r#"
+        fn f$0(v$0 int) -> int {
+            ret v$0 * 2;
+        }
+
+        main {
+            print(f$0(21));
+        }
+        "#;
+
+        let (program, symbol_table) = parse(source).unwrap();
+        assert_eq!(program.items.len(), 2);
+        typecheck(&program, &symbol_table).unwrap();
+
+        let (bytecode, debug_info) = generate(&program, &symbol_table).unwrap();
+        assert!(bytecode.function_table.contains_key("f$0"));
+        assert_eq!(
+            bytecode.instructions,
+            compile_source(source).unwrap().0.instructions
+        );
+        assert!(execute(bytecode, debug_info).is_ok());
+    }
+
+    #[test]
+    fn test_phase_errors() {
+        assert!(matches!(
+            parse("main { int v$0 = ; }"),
+            Err(ZvarError::UnexpectedToken { .. })
+        ));
+
+        let (program, symbol_table) = parse("main { print(len(5)); }").unwrap();
+        assert!(matches!(
+            typecheck(&program, &symbol_table),
+            Err(ZvarError::TypeMismatch { .. })
+        ));
+
+        let (bytecode, debug_info) = compile_source("main { print(1 / 0); }").unwrap();
+        assert!(matches!(
+            execute(bytecode, debug_info),
+            Err(ZvarError::DivisionByZero { .. })
+        ));
+    }
 }
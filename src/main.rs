@@ -1,13 +1,29 @@
 //! Main entry point for the zvar compiler
 
-use std::{fs, process};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, process,
+    time::{Duration, Instant},
+};
 use zvar_lang::{
     cli::{Cli, Commands},
-    codegen::CodeGenerator,
+    codegen::{
+        cfg::ControlFlowGraph, debug_info::DebugInfo, instruction::Bytecode, optimize,
+        CodeGenerator,
+    },
+    docs::{self, DocsCoverage, DocsFormat},
     error::{ZvarError, ZvarResult},
-    parser::Parser,
-    symbol_table::SymbolTable,
-    vm::VM,
+    lexer::Lexer,
+    parser::{
+        ast::{self, Expression, Program},
+        Parser,
+    },
+    symbol_table::{EntityType, SymbolTable},
+    types::TypeChecker,
+    vm::{
+        value::{OverflowMode, Value},
+        VM,
+    },
 };
 
 fn main() {
@@ -20,32 +36,172 @@ fn main() {
         process::exit(1);
     }
 
-    if let Err(e) = run_command(cli) {
-        eprintln!("Error: {}", e);
-        process::exit(1);
+    if let Err(failure) = run_command(cli) {
+        eprintln!("Error: {}", failure.message);
+        process::exit(failure.exit_code);
+    }
+}
+
+/// Why a command failed: the message to report and the process exit code
+struct Failure {
+    message: String,
+    exit_code: i32,
+}
+
+impl Failure {
+    fn new(error: &ZvarError, message: String) -> Self {
+        Failure {
+            message,
+            exit_code: error.exit_code(),
+        }
     }
 }
 
-fn run_command(cli: Cli) -> ZvarResult<()> {
+/// Run a command, returning what to report on failure
+fn run_command(cli: Cli) -> Result<(), Failure> {
     match cli.command {
         Commands::Run {
             file,
             disasm,
             debug,
-        } => run_file(&file, disasm, debug || cli.verbose),
+            optimize,
+            trace,
+            seed,
+            overflow,
+            group_digits,
+        } => with_source(&file, |source| {
+            run_file(
+                &file,
+                source,
+                &RunOptions {
+                    show_disasm: disasm,
+                    debug: debug || cli.verbose,
+                    optimize,
+                    trace,
+                    seed,
+                    overflow,
+                    group_digits,
+                },
+            )
+        }),
         Commands::Compile {
             file,
             output,
             disasm,
-        } => compile_file(&file, output.as_deref(), disasm),
-        Commands::Check { file } => check_file(&file),
-        Commands::Info { file, docs_only } => show_info(&file, docs_only),
-        Commands::Repl { show_bytecode } => run_repl(show_bytecode),
+            disasm_functions,
+            disasm_source,
+            optimize,
+            strip,
+            emit_ast,
+            emit_cfg,
+        } => with_source(&file, |source| {
+            compile_file(
+                &file,
+                source,
+                &CompileOptions {
+                    output: output.as_deref(),
+                    show_disasm: disasm,
+                    disasm_functions,
+                    disasm_source,
+                    optimize,
+                    strip,
+                    emit_ast,
+                    emit_cfg: emit_cfg.as_deref(),
+                },
+            )
+        }),
+        Commands::Check {
+            file,
+            strict_semicolons,
+            disasm,
+            disasm_functions,
+            emit_ast,
+            check_docs_coverage,
+            dump_tokens,
+        } => with_source(&file, |source| {
+            if dump_tokens {
+                print!("{}", token_dump(source)?);
+            }
+            check_file(
+                &file,
+                source,
+                strict_semicolons,
+                disasm,
+                disasm_functions,
+                emit_ast,
+                check_docs_coverage,
+            )
+        }),
+        Commands::Info {
+            file,
+            docs_only,
+            dump_symbols,
+        } => with_source(&file, |source| {
+            show_info(&file, source, docs_only)?;
+            if dump_symbols {
+                print!("{}", symbol_dump(source)?);
+            }
+            Ok(())
+        }),
+        Commands::Docs {
+            file,
+            format,
+            output,
+        } => with_source(&file, |source| {
+            export_docs(&file, source, format, output.as_deref())
+        }),
+        Commands::Bench {
+            file,
+            iterations,
+            optimize,
+        } => with_source(&file, |source| {
+            bench_file(&file, source, iterations, optimize)
+        }),
+        Commands::Repl { show_bytecode } => {
+            run_repl(show_bytecode).map_err(|e| Failure::new(&e, e.to_string()))
+        }
     }
 }
 
-fn run_file(file: &std::path::Path, show_disasm: bool, debug: bool) -> ZvarResult<()> {
-    if debug {
+/// Read a source file and run a command on it, rendering errors against the source
+fn with_source(
+    file: &std::path::Path,
+    command: impl FnOnce(&str) -> ZvarResult<()>,
+) -> Result<(), Failure> {
+    let source = fs::read_to_string(file).map_err(|e| {
+        let error = ZvarError::file_error(format!("Failed to read file {}: {}", file.display(), e));
+        Failure::new(&error, error.to_string())
+    })?;
+
+    command(&source).map_err(|e| Failure::new(&e, e.render(&source)))
+}
+
+/// Flags of the `run` command
+struct RunOptions {
+    show_disasm: bool,
+    debug: bool,
+    optimize: bool,
+    trace: bool,
+    seed: Option<u64>,
+    overflow: OverflowMode,
+    group_digits: bool,
+}
+
+/// Compile source to bytecode, running every optimization pass if `optimize` is set
+fn build_bytecode(source: &str, optimize: bool) -> ZvarResult<(Bytecode, DebugInfo)> {
+    let (mut bytecode, mut debug_info) = zvar_lang::compile_source(source)?;
+
+    if optimize {
+        bytecode.strip_unreachable(&mut debug_info);
+        optimize::peephole(&mut bytecode, &mut debug_info);
+        optimize::hoist_loop_invariants(&mut bytecode, &mut debug_info);
+    }
+
+    Ok((bytecode, debug_info))
+}
+
+fn run_file(file: &std::path::Path, source: &str, options: &RunOptions) -> ZvarResult<()> {
+    if options.debug {
         println!(
             "Running file: {} (extension: {})",
             file.display(),
@@ -53,75 +209,99 @@ fn run_file(file: &std::path::Path, show_disasm: bool, debug: bool) -> ZvarResul
         );
     }
 
-    // Read source code
-    let source = fs::read_to_string(file).map_err(|e| {
-        ZvarError::file_error(format!("Failed to read file {}: {}", file.display(), e))
-    })?;
-
-    // Compile to bytecode
-    let mut symbol_table = SymbolTable::new();
-    let mut parser = Parser::new(&source, &mut symbol_table)?;
-    let program = parser.parse_program()?;
-
-    if debug {
-        println!("Parsed {} top-level items", program.items.len());
-    }
-
-    let mut codegen = CodeGenerator::new();
-    let (bytecode, debug_info) = codegen.generate(&program, &symbol_table)?;
+    let (bytecode, debug_info) = build_bytecode(source, options.optimize)?;
 
-    if show_disasm {
+    if options.show_disasm {
         println!("\n{}", bytecode.disassemble());
     }
 
-    if debug {
+    if options.debug {
         println!("Generated {} instructions", bytecode.len());
     }
 
     // Execute
     let mut vm = VM::new();
+    vm.set_trace(options.trace);
+    if let Some(seed) = options.seed {
+        vm.set_seed(seed);
+    }
+    vm.set_overflow_mode(options.overflow);
+    vm.set_group_digits(options.group_digits);
     vm.load(bytecode, Some(debug_info));
 
-    if debug {
+    if options.debug {
         println!("Starting execution...\n");
     }
 
     vm.run()?;
 
-    if debug {
+    if options.debug {
         println!("\nExecution completed successfully");
     }
 
     Ok(())
 }
 
-fn compile_file(
-    file: &std::path::Path,
-    output: Option<&std::path::Path>,
+/// Flags of the `compile` command
+struct CompileOptions<'a> {
+    output: Option<&'a std::path::Path>,
     show_disasm: bool,
-) -> ZvarResult<()> {
+    disasm_functions: bool,
+    disasm_source: bool,
+    optimize: bool,
+    strip: bool,
+    emit_ast: bool,
+    emit_cfg: Option<&'a std::path::Path>,
+}
+
+fn compile_file(file: &std::path::Path, source: &str, options: &CompileOptions) -> ZvarResult<()> {
     println!("Compiling file: {}", file.display());
 
-    // Read source code
-    let source = fs::read_to_string(file).map_err(|e| {
-        ZvarError::file_error(format!("Failed to read file {}: {}", file.display(), e))
-    })?;
+    // Shown before type checking, so the tree of a program that fails it is still printed
+    if options.emit_ast {
+        let (program, _) = zvar_lang::parse(source)?;
+        println!("\n{}", ast::pretty_print(&program));
+    }
 
-    // Compile to bytecode
-    let mut symbol_table = SymbolTable::new();
-    let mut parser = Parser::new(&source, &mut symbol_table)?;
-    let program = parser.parse_program()?;
+    let (mut bytecode, debug_info) = build_bytecode(source, options.optimize)?;
 
-    let mut codegen = CodeGenerator::new();
-    let (bytecode, _debug_info) = codegen.generate(&program, &symbol_table)?;
+    if let Some(cfg_path) = options.emit_cfg {
+        let dot = ControlFlowGraph::build(&bytecode).to_dot(&bytecode);
+        fs::write(cfg_path, dot).map_err(|e| {
+            ZvarError::file_error(format!("Failed to write {}: {}", cfg_path.display(), e))
+        })?;
+        println!("Wrote control flow graph to {}", cfg_path.display());
+    }
 
-    if show_disasm {
+    // Spans are still available here, even when they are stripped from the output
+    if options.disasm_source {
+        println!("\n{}", bytecode.disassemble_annotated(&debug_info, source));
+    }
+
+    // The function table lives in the bytecode, so calls still resolve without debug info
+    let debug_info = if options.strip {
+        bytecode.strip_docs();
+        println!("Stripped debug info (spans, docs)");
+        None
+    } else {
+        Some(debug_info)
+    };
+
+    if options.show_disasm {
         println!("\n{}", bytecode.disassemble());
     }
+    if options.disasm_functions {
+        println!("\n{}", bytecode.disassemble_functions());
+    }
 
     // In a real implementation, we'd serialize the bytecode to the output file
-    if let Some(output_path) = output {
-        println!("Would write bytecode to: {}", output_path.display());
+    if let Some(output_path) = options.output {
+        let contents = if debug_info.is_some() {
+            "bytecode and debug info"
+        } else {
+            "bytecode"
+        };
+        println!("Would write {} to: {}", contents, output_path.display());
         // TODO: Implement bytecode serialization
     } else {
         println!(
@@ -133,127 +313,419 @@ fn compile_file(
     Ok(())
 }
 
-fn check_file(file: &std::path::Path) -> ZvarResult<()> {
+fn check_file(
+    file: &std::path::Path,
+    source: &str,
+    strict_semicolons: bool,
+    show_disasm: bool,
+    disasm_functions: bool,
+    emit_ast: bool,
+    docs_coverage: bool,
+) -> ZvarResult<()> {
     println!("Checking file: {}", file.display());
 
-    // Read source code
-    let source = fs::read_to_string(file).map_err(|e| {
-        ZvarError::file_error(format!("Failed to read file {}: {}", file.display(), e))
-    })?;
+    // Report every unknown identifier at once, not only the first one
+    let mut errors = unknown_identifiers(source)?;
+    if let Some(last) = errors.pop() {
+        for error in &errors {
+            eprintln!("Error: {}", error.render(source));
+        }
+        return Err(last);
+    }
 
     // Parse only (don't generate code)
     let mut symbol_table = SymbolTable::new();
-    let mut parser = Parser::new(&source, &mut symbol_table)?;
+    let mut parser = Parser::new(source, &mut symbol_table)?;
+    parser.set_strict_semicolons(strict_semicolons);
     let program = parser.parse_program()?;
 
     println!("✓ Syntax is valid");
+
+    for warning in parser.warnings() {
+        println!("⚠ Warning: {}", warning);
+    }
+
+    if emit_ast {
+        println!("\n{}", ast::pretty_print(&program));
+    }
+
+    TypeChecker::new(&symbol_table).check_program(&program)?;
+    println!("✓ Types are valid");
     println!("✓ Found {} top-level items", program.items.len());
 
     // Show basic statistics
     let mut functions = 0;
     let mut main_blocks = 0;
+    let mut type_aliases = 0;
+    let mut globals = 0;
 
     for item in &program.items {
         match item {
             zvar_lang::parser::ast::Item::Function(_) => functions += 1,
             zvar_lang::parser::ast::Item::MainBlock(_) => main_blocks += 1,
+            zvar_lang::parser::ast::Item::TypeAlias(_) => type_aliases += 1,
+            zvar_lang::parser::ast::Item::GlobalVariable(_) => globals += 1,
         }
     }
 
-    println!("✓ {} functions, {} main blocks", functions, main_blocks);
+    println!(
+        "✓ {} functions, {} main blocks, {} type aliases, {} global variables",
+        functions, main_blocks, type_aliases, globals
+    );
+
+    if docs_coverage {
+        println!(
+            "✓ Documentation coverage: {}",
+            DocsCoverage::from_symbol_table(&symbol_table)
+        );
+    }
+
+    if show_disasm {
+        println!("\n{}", check_disassembly(&program, &symbol_table, false)?);
+    }
+    if disasm_functions {
+        println!("\n{}", check_disassembly(&program, &symbol_table, true)?);
+    }
 
     Ok(())
 }
 
-fn show_info(file: &std::path::Path, docs_only: bool) -> ZvarResult<()> {
+/// Generate bytecode for a checked program and disassemble it, without running it
+///
+/// With `by_function` every function's instructions get their own header.
+fn check_disassembly(
+    program: &Program,
+    symbol_table: &SymbolTable,
+    by_function: bool,
+) -> ZvarResult<String> {
+    let mut codegen = CodeGenerator::new();
+    let (bytecode, _debug_info) = codegen.generate(program, symbol_table)?;
+    Ok(if by_function {
+        bytecode.disassemble_functions()
+    } else {
+        bytecode.disassemble()
+    })
+}
+
+fn show_info(file: &std::path::Path, source: &str, docs_only: bool) -> ZvarResult<()> {
     println!("Analyzing file: {}", file.display());
+    print!("{}", entity_info(source, docs_only)?);
+    Ok(())
+}
 
-    // Read source code
-    let source = fs::read_to_string(file).map_err(|e| {
-        ZvarError::file_error(format!("Failed to read file {}: {}", file.display(), e))
-    })?;
+/// Describe every entity of a program, as printed by the `info` command
+fn entity_info(source: &str, docs_only: bool) -> ZvarResult<String> {
+    use std::fmt::Write;
 
-    // Parse and analyze
+    // Parse and analyze, recording every scope so entities that share a
+    // name (e.g. a parameter and a variable of main) are reported separately
     let mut symbol_table = SymbolTable::new();
-    let mut parser = Parser::new(&source, &mut symbol_table)?;
-    let _program = parser.parse_program()?;
+    symbol_table.set_record_scopes(true);
+    let mut parser = Parser::new(source, &mut symbol_table)?;
+    let program = parser.parse_program()?;
+
+    let signatures: HashMap<&str, String> = program
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            ast::Item::Function(func) => {
+                Some((func.name.as_str(), docs::annotated_signature(func)))
+            }
+            _ => None,
+        })
+        .collect();
 
-    println!("\nEntity Information:");
-    println!("{:-<50}", "");
+    let mut output = String::new();
+    writeln!(output, "\nEntity Information:").unwrap();
+    writeln!(output, "{:-<50}", "").unwrap();
 
-    for (name, symbol) in symbol_table.all_symbols() {
+    let scopes = symbol_table.scopes_snapshot();
+    for (name, symbol) in scopes.iter().flat_map(|scope| &scope.symbols) {
         if !docs_only {
-            println!(
+            writeln!(
+                output,
                 "{}: {} (defined at {})",
                 name,
-                match &symbol.entity_type {
-                    zvar_lang::symbol_table::EntityType::Variable { value_type } =>
-                        format!("{} variable", value_type),
-                    zvar_lang::symbol_table::EntityType::Constant { value_type } =>
-                        format!("{} constant", value_type),
-                    zvar_lang::symbol_table::EntityType::Function {
-                        params,
-                        return_type,
-                    } => format!("function({} params) -> {}", params.len(), return_type),
-                },
+                entity_kind(&symbol.entity_type),
                 symbol.definition_span
-            );
+            )
+            .unwrap();
+            writeln!(output, "  Used {} time(s)", symbol.usage_count).unwrap();
+        }
+
+        if let Some(signature) = signatures.get(name.as_str()) {
+            writeln!(output, "  Signature: {}", signature).unwrap();
         }
 
         if let Some(doc) = &symbol.documentation {
-            println!("  Documentation: {}", doc);
+            writeln!(output, "  Documentation: {}", doc).unwrap();
         }
 
         if !docs_only {
-            println!();
+            writeln!(output).unwrap();
+        }
+    }
+
+    Ok(output)
+}
+
+/// Short description of what kind of entity a symbol is
+fn entity_kind(entity_type: &EntityType) -> String {
+    match entity_type {
+        EntityType::Variable { value_type } => format!("{} variable", value_type),
+        EntityType::Constant { value_type } => format!("{} constant", value_type),
+        EntityType::Function {
+            params,
+            return_type,
+        } => format!("function({} params) -> {}", params.len(), return_type),
+    }
+}
+
+/// Lex a whole source, collecting every unknown identifier
+///
+/// Other lexical errors still stop at the first one.
+fn unknown_identifiers(source: &str) -> ZvarResult<Vec<ZvarError>> {
+    let mut lexer = Lexer::new(source);
+    lexer.set_collect_errors(true);
+    lexer.tokenize()?;
+    Ok(lexer.take_errors())
+}
+
+/// Render the token stream of a source, one token per line with its span
+fn token_dump(source: &str) -> ZvarResult<String> {
+    use std::fmt::Write;
+
+    let mut dump = String::new();
+    for (token, span) in Lexer::new(source).tokenize_with_spans()? {
+        writeln!(dump, "{:<12}{}", span.to_string(), token).unwrap();
+    }
+    Ok(dump)
+}
+
+/// Render every scope of a program and its entries as an indented tree
+fn symbol_dump(source: &str) -> ZvarResult<String> {
+    use std::fmt::Write;
+
+    let mut symbol_table = SymbolTable::new();
+    symbol_table.set_record_scopes(true);
+    let mut parser = Parser::new(source, &mut symbol_table)?;
+    parser.parse_program()?;
+
+    let mut output = String::new();
+    writeln!(output, "\nSymbol Table:").unwrap();
+    writeln!(output, "{:-<50}", "").unwrap();
+
+    for scope in symbol_table.scopes_snapshot() {
+        let indent = "  ".repeat(scope.depth);
+        writeln!(output, "{}scope {}", indent, scope.depth).unwrap();
+
+        for (name, symbol) in &scope.symbols {
+            write!(
+                output,
+                "{}  {}: {}",
+                indent,
+                name,
+                entity_kind(&symbol.entity_type)
+            )
+            .unwrap();
+            if symbol.is_initialized {
+                write!(output, ", initialized").unwrap();
+            }
+            if let Some(doc) = &symbol.documentation {
+                write!(output, " // {}", doc.replace('\n', " ")).unwrap();
+            }
+            writeln!(output).unwrap();
+        }
+    }
+
+    Ok(output)
+}
+
+fn export_docs(
+    file: &std::path::Path,
+    source: &str,
+    format: DocsFormat,
+    output: Option<&std::path::Path>,
+) -> ZvarResult<()> {
+    let mut symbol_table = SymbolTable::new();
+    let mut parser = Parser::new(source, &mut symbol_table)?;
+    let program = parser.parse_program()?;
+
+    let entries = docs::collect_entries(&program);
+    let title = file
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let document = match format {
+        DocsFormat::Markdown => docs::render_markdown(&title, &entries),
+        DocsFormat::Html => docs::render_html(&title, &entries),
+    };
+
+    match output {
+        Some(output_path) => {
+            fs::write(output_path, document).map_err(|e| {
+                ZvarError::file_error(format!("Failed to write {}: {}", output_path.display(), e))
+            })?;
+            println!(
+                "Documented {} entities in {}",
+                entries.len(),
+                output_path.display()
+            );
         }
+        None => print!("{}", document),
+    }
+
+    Ok(())
+}
+
+/// Compile a program once, then execute it `iterations` times and report timings
+///
+/// Printed output is discarded so it neither skews nor floods the results.
+fn bench_file(
+    file: &std::path::Path,
+    source: &str,
+    iterations: u32,
+    optimize: bool,
+) -> ZvarResult<()> {
+    let (bytecode, debug_info) = build_bytecode(source, optimize)?;
+
+    let mut vm = VM::new();
+    vm.set_output_writer(std::io::sink());
+
+    let mut durations = Vec::with_capacity(iterations as usize);
+    for _ in 0..iterations {
+        vm.reset();
+        vm.load(bytecode.clone(), Some(debug_info.clone()));
+
+        let start = Instant::now();
+        vm.run()?;
+        durations.push(start.elapsed());
+    }
+
+    println!("Benchmarked file: {}", file.display());
+    if let Some(stats) = BenchStats::from_durations(&durations) {
+        println!("{}", stats);
     }
 
     Ok(())
 }
 
+/// Timing summary of the runs of a benchmark
+#[derive(Debug, PartialEq)]
+struct BenchStats {
+    runs: usize,
+    min: Duration,
+    mean: Duration,
+    max: Duration,
+}
+
+impl BenchStats {
+    /// Summarize run durations, or `None` if there were no runs
+    fn from_durations(durations: &[Duration]) -> Option<Self> {
+        let min = *durations.iter().min()?;
+        let max = *durations.iter().max()?;
+        let total: Duration = durations.iter().sum();
+
+        Some(BenchStats {
+            runs: durations.len(),
+            min,
+            mean: total / durations.len() as u32,
+            max,
+        })
+    }
+}
+
+impl std::fmt::Display for BenchStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} runs: min {:?}, mean {:?}, max {:?}",
+            self.runs, self.min, self.mean, self.max
+        )
+    }
+}
+
 fn run_repl(show_bytecode: bool) -> ZvarResult<()> {
-    println!("zvar REPL - Interactive mode");
-    println!("Type expressions to evaluate them, or 'exit' to quit");
-    println!("{:-<50}", "");
+    let stdin = std::io::stdin();
+    repl_loop(stdin.lock(), std::io::stdout(), show_bytecode)
+}
+
+/// Read and evaluate lines until `exit`, `quit` or the end of the input
+fn repl_loop(
+    mut input: impl std::io::BufRead,
+    mut output: impl std::io::Write,
+    show_bytecode: bool,
+) -> ZvarResult<()> {
+    writeln!(output, "zvar REPL - Interactive mode")?;
+    writeln!(
+        output,
+        "Type expressions to evaluate them, or 'exit' to quit"
+    )?;
+    writeln!(output, "{:-<50}", "")?;
 
+    // State shared by every line so earlier declarations stay usable
     let mut symbol_table = SymbolTable::new();
+    symbol_table.set_persist_scopes(true);
+    let mut variable_slots = BTreeMap::new();
     let mut vm = VM::new();
 
     loop {
-        print!("> ");
-        use std::io::{self, Write};
-        io::stdout().flush().unwrap();
-
-        let mut input = String::new();
-        match io::stdin().read_line(&mut input) {
-            Ok(_) => {
-                let input = input.trim();
-
-                if input.is_empty() {
-                    continue;
-                }
-
-                if input == "exit" || input == "quit" {
-                    println!("Goodbye!");
-                    break;
-                }
-
-                // Wrap the input in a main block for parsing
-                let wrapped_input = format!("main {{ {} }}", input);
-
-                match evaluate_repl_input(&wrapped_input, &mut symbol_table, &mut vm, show_bytecode)
-                {
-                    Ok(()) => {}
-                    Err(e) => {
-                        println!("Error: {}", e);
-                    }
-                }
-            }
-            Err(e) => {
-                println!("Error reading input: {}", e);
-                break;
+        write!(output, "> ")?;
+        output.flush()?;
+
+        let mut line = String::new();
+        let read = input.read_line(&mut line).map_err(|e| ZvarError::IoError {
+            message: format!("Failed to read REPL input: {}", e),
+        })?;
+
+        // Closed input (e.g. Ctrl-D or the end of a pipe)
+        if read == 0 {
+            writeln!(output)?;
+            break;
+        }
+
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if line == "exit" || line == "quit" {
+            writeln!(output, "Goodbye!")?;
+            break;
+        }
+
+        // A bare expression shows its value; anything else runs as statements
+        let expression = Parser::new(line, &mut symbol_table)
+            .and_then(|mut parser| parser.parse_expression_only())
+            .ok();
+        if let Some(expression) = expression {
+            match evaluate_repl_expression(
+                &expression,
+                &symbol_table,
+                &mut variable_slots,
+                &mut vm,
+                show_bytecode,
+            ) {
+                Ok(Some(value)) => writeln!(output, "{}", value.display_quoted())?,
+                Ok(None) => {}
+                Err(e) => writeln!(output, "Error: {}", e.render(line))?,
             }
+            continue;
+        }
+
+        // Wrap the input in a main block for parsing
+        let wrapped_input = format!("main {{ {} }}", line);
+
+        if let Err(e) = evaluate_repl_input(
+            &wrapped_input,
+            &mut symbol_table,
+            &mut variable_slots,
+            &mut vm,
+            show_bytecode,
+        ) {
+            writeln!(output, "Error: {}", e.render(&wrapped_input))?;
         }
     }
 
@@ -263,6 +735,7 @@ fn run_repl(show_bytecode: bool) -> ZvarResult<()> {
 fn evaluate_repl_input(
     input: &str,
     symbol_table: &mut SymbolTable,
+    variable_slots: &mut BTreeMap<String, u32>,
     vm: &mut VM,
     show_bytecode: bool,
 ) -> ZvarResult<()> {
@@ -270,18 +743,302 @@ fn evaluate_repl_input(
     let mut parser = Parser::new(input, symbol_table)?;
     let program = parser.parse_program()?;
 
-    // Generate bytecode
-    let mut codegen = CodeGenerator::new();
+    TypeChecker::new(symbol_table).check_program(&program)?;
+
+    // Generate bytecode, keeping the slots assigned by previous lines
+    let mut codegen = CodeGenerator::with_variable_slots(variable_slots.clone());
     let (bytecode, debug_info) = codegen.generate(&program, symbol_table)?;
+    *variable_slots = codegen.variable_slots().clone();
 
     if show_bytecode {
         println!("{}", bytecode.disassemble());
     }
 
-    // Execute
-    vm.reset();
-    vm.load(bytecode, Some(debug_info));
-    vm.run()?;
+    // Execute on top of the variables left by previous lines
+    vm.load_and_continue(bytecode, Some(debug_info));
+    if let Err(e) = vm.run() {
+        // Leave the VM ready for the next line
+        vm.clear_transient_state();
+        return Err(e);
+    }
 
     Ok(())
 }
+
+/// Evaluate a bare REPL expression and return the value it leaves, if any
+///
+/// Calls to builtins such as `print` leave nothing behind.
+fn evaluate_repl_expression(
+    expression: &Expression,
+    symbol_table: &SymbolTable,
+    variable_slots: &mut BTreeMap<String, u32>,
+    vm: &mut VM,
+    show_bytecode: bool,
+) -> ZvarResult<Option<Value>> {
+    TypeChecker::new(symbol_table).infer_expression(expression)?;
+
+    let mut codegen = CodeGenerator::with_variable_slots(variable_slots.clone());
+    let (bytecode, debug_info) = codegen.generate_expression_only(expression)?;
+    *variable_slots = codegen.variable_slots().clone();
+
+    if show_bytecode {
+        println!("{}", bytecode.disassemble());
+    }
+
+    vm.load_and_continue(bytecode, Some(debug_info));
+    let result = vm.run().map(|()| vm.stack_top().cloned());
+
+    // The value has been taken off the stack; leave the VM ready for the next line
+    vm.clear_transient_state();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zvar_lang::span::Span;
+
+    #[test]
+    fn test_repl_variables_persist_between_inputs() {
+        let mut symbol_table = SymbolTable::new();
+        symbol_table.set_persist_scopes(true);
+        let mut variable_slots = BTreeMap::new();
+        let mut vm = VM::new();
+
+        let mut eval = |input: &str| {
+            evaluate_repl_input(
+                &format!("main {{ {} }}", input),
+                &mut symbol_table,
+                &mut variable_slots,
+                &mut vm,
+                false,
+            )
+        };
+
+        eval("int v$0 = 40;").unwrap();
+        eval("int v$1 = 2;").unwrap();
+        eval("v$0 = v$0 + v$1;").unwrap();
+
+        assert_eq!(variable_slots.len(), 2);
+        assert_ne!(variable_slots["v$0"], variable_slots["v$1"]);
+        assert!(symbol_table.lookup("v$0").is_some());
+    }
+
+    #[test]
+    fn test_repl_exits_on_closed_input() {
+        let mut output = Vec::new();
+        repl_loop(std::io::empty(), &mut output, false).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.ends_with("> \n"));
+
+        let mut output = Vec::new();
+        repl_loop(
+            "print(1);\nexit\nprint(2);\n".as_bytes(),
+            &mut output,
+            false,
+        )
+        .unwrap();
+        assert!(String::from_utf8(output).unwrap().ends_with("Goodbye!\n"));
+    }
+
+    #[test]
+    fn test_repl_prints_expression_values() {
+        let mut output = Vec::new();
+        repl_loop(
+            "1 + 2\nint v$0 = 40;\nv$0 + 2\ntrim(\" a \")\n1 / 0\n".as_bytes(),
+            &mut output,
+            false,
+        )
+        .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let results: Vec<&str> = output
+            .split("> ")
+            .skip(1)
+            .map(|result| result.trim_end())
+            .collect();
+        assert_eq!(results[0], "3");
+        assert_eq!(results[1], "");
+        assert_eq!(results[2], "42");
+        assert_eq!(results[3], "\"a\"");
+        assert!(results[4].starts_with("Error: Division by zero"));
+    }
+
+    #[test]
+    fn test_repl_line_with_several_statements() {
+        let mut symbol_table = SymbolTable::new();
+        symbol_table.set_persist_scopes(true);
+        let mut variable_slots = BTreeMap::new();
+        let mut vm = VM::new();
+
+        let mut eval = |input: &str| {
+            evaluate_repl_input(
+                &format!("main {{ {} }}", input),
+                &mut symbol_table,
+                &mut variable_slots,
+                &mut vm,
+                false,
+            )
+        };
+
+        eval("int v$0 = 1; int v$1 = v$0 + 1; print(v$1);").unwrap();
+
+        // Both declarations outlive the line; dividing by v$1 - 2 fails only if v$1 is 2
+        eval("print(v$0);").unwrap();
+        assert!(matches!(
+            eval("print(10 / (v$1 - 2));"),
+            Err(ZvarError::DivisionByZero { .. })
+        ));
+        assert_eq!(variable_slots.len(), 2);
+    }
+
+    #[test]
+    fn test_info_shows_parameter_docs() {
+        let source = r#"
+        /// Adds two integers
+        fn f$0(v$0 int, v$1 int) -> int {
+            describe(v$0, "the first addend");
+            ret v$0 + v$1;
+        }
+
+        main {
+            print(f$0(1, 2));
+        }
+        "#;
+
+        let info = entity_info(source, false).unwrap();
+        assert!(info.contains("  Signature: f$0(v$0: int /* the first addend */, v$1: int) -> int"));
+        assert!(info.contains("  Documentation: Adds two integers"));
+    }
+
+    #[test]
+    fn test_info_keeps_shadowed_entities_apart() {
+        let source = r#"
+        fn f$0(v$0 int) -> int {
+            describe(v$0, "the input");
+            ret v$0 * v$0;
+        }
+
+        main {
+            int v$0 = 2;
+            v$0 = f$0(3);
+        }
+        "#;
+
+        let info = entity_info(source, false).unwrap();
+        assert!(info.contains("v$0: int variable (defined at 2:16-22)\n  Used 2 time(s)\n  Documentation: the input\n"));
+        // The assignment to main's v$0 is not a use
+        assert!(info.contains("v$0: int variable (defined at 8:17-24)\n  Used 0 time(s)\n"));
+        assert!(
+            info.contains("f$0: function(1 params) -> int (defined at 2:9-10)\n  Used 1 time(s)\n")
+        );
+    }
+
+    #[test]
+    fn test_repeated_describe_matches_at_runtime() {
+        let source = r#"
+        main {
+            int v$0 = 1;
+            describe(v$0, "a");
+            describe(v$0, "b");
+            describe(v$0, "a");
+        }
+        "#;
+
+        let info = entity_info(source, false).unwrap();
+        assert!(info.contains("  Documentation: a\nb\n"));
+
+        let (bytecode, debug_info) = zvar_lang::compile_source(source).unwrap();
+        let mut vm = VM::new();
+        vm.load(bytecode.clone(), Some(debug_info));
+        vm.run().unwrap();
+        assert_eq!(vm.get_entity_doc("v$0").map(String::as_str), Some("a\nb"));
+
+        // Without debug info the runtime builds the same documentation
+        let mut vm = VM::new();
+        vm.load(bytecode, None);
+        vm.run().unwrap();
+        assert_eq!(vm.get_entity_doc("v$0").map(String::as_str), Some("a\nb"));
+    }
+
+    #[test]
+    fn test_symbol_dump_shows_each_scope() {
+        let source = r#"
+        fn f$0(v$0 int) -> int {
+            ret v$0;
+        }
+
+        main {
+            /// The input
+            str v$0 = "shadowing";
+            print(v$0);
+        }
+        "#;
+
+        let dump = symbol_dump(source).unwrap();
+        assert!(dump.contains("scope 0\n  f$0: function(1 params) -> int\n"));
+        assert!(dump.contains("  scope 1\n    v$0: int variable\n"));
+        assert!(dump.contains("  scope 1\n    v$0: str variable, initialized // The input\n"));
+    }
+
+    #[test]
+    fn test_check_reports_all_unknown_identifiers() {
+        let source = "main {\n    print(foo);\n    int v$0 = bar + 1;\n}\n";
+
+        let errors = unknown_identifiers(source).unwrap();
+        let spans: Vec<_> = errors.iter().map(|error| error.span()).collect();
+        assert_eq!(
+            spans,
+            vec![Some(Span::new(2, 11, 2, 13)), Some(Span::new(3, 15, 3, 17))]
+        );
+
+        assert!(unknown_identifiers("main { print(1); }")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_token_dump() {
+        assert_eq!(
+            token_dump("v$0 = 1;").unwrap(),
+            "1:1-3       v$0\n1:5         =\n1:7         1\n1:8         ;\n1:9         EOF\n"
+        );
+    }
+
+    #[test]
+    fn test_bench_stats() {
+        let durations = [
+            Duration::from_micros(30),
+            Duration::from_micros(10),
+            Duration::from_micros(20),
+            Duration::from_micros(60),
+        ];
+
+        assert_eq!(
+            BenchStats::from_durations(&durations),
+            Some(BenchStats {
+                runs: 4,
+                min: Duration::from_micros(10),
+                mean: Duration::from_micros(30),
+                max: Duration::from_micros(60),
+            })
+        );
+        assert_eq!(BenchStats::from_durations(&[]), None);
+    }
+
+    #[test]
+    fn test_check_disassembly() {
+        let source = "main { int v$0 = 2 + 3; print(v$0); }";
+
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        let program = parser.parse_program().unwrap();
+
+        let disasm = check_disassembly(&program, &symbol_table, false).unwrap();
+        assert!(disasm.contains("ADD"));
+        assert!(disasm.contains("STOREVAR v$0"));
+        assert!(disasm.contains("PRINT"));
+        assert!(disasm.contains("HALT"));
+    }
+}
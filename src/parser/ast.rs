@@ -1,6 +1,10 @@
 //! Abstract Syntax Tree definitions for the zvar language
 
-use crate::{span::Span, symbol_table::ValueType};
+use crate::{
+    prelude::*,
+    span::{Span, Spanned},
+    symbol_table::ValueType,
+};
 
 /// Top-level program structure
 #[derive(Debug, Clone)]
@@ -14,6 +18,8 @@ pub struct Program {
 pub enum Item {
     Function(Function),
     MainBlock(MainBlock),
+    TypeAlias(TypeAlias),
+    GlobalVariable(VariableDeclaration),
 }
 
 impl Item {
@@ -21,6 +27,8 @@ impl Item {
         match self {
             Item::Function(f) => f.span,
             Item::MainBlock(m) => m.span,
+            Item::TypeAlias(t) => t.span,
+            Item::GlobalVariable(g) => g.span,
         }
     }
 }
@@ -34,6 +42,10 @@ pub struct Function {
     pub body: Block,
     pub span: Span,
     pub documentation: Option<String>,
+    /// Declared with `pure`: no I/O and no writes to globals
+    pub is_pure: bool,
+    /// Declared with `pure memo`: results are cached per argument values
+    pub is_memoized: bool,
 }
 
 /// Function parameter
@@ -52,6 +64,15 @@ pub struct MainBlock {
     pub documentation: Option<String>,
 }
 
+/// Type alias: type Meters = int;
+#[derive(Debug, Clone)]
+pub struct TypeAlias {
+    pub name: String,
+    pub target: ValueType,
+    pub span: Span,
+    pub documentation: Option<String>,
+}
+
 /// Block of statements
 #[derive(Debug, Clone)]
 pub struct Block {
@@ -69,6 +90,42 @@ pub enum Statement {
     Return(Return),
     Describe(Describe),
     If(IfStatement),
+    While(WhileStatement),
+    Match(MatchStatement),
+    Break(Span),
+    Empty(Span), // A lone ';'
+}
+
+/// Match statement: match (scrutinee) { 1 => { ... } _ => { ... } }
+#[derive(Debug, Clone)]
+pub struct MatchStatement {
+    pub scrutinee: Expression,
+    pub arms: Vec<MatchArm>,
+    pub span: Span,
+}
+
+/// A single `pattern => { ... }` arm of a match statement
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: MatchPattern,
+    pub body: Block,
+    pub span: Span,
+}
+
+/// Pattern of a match arm
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatchPattern {
+    Integer(i64),
+    Wildcard, // _
+}
+
+impl core::fmt::Display for MatchPattern {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            MatchPattern::Integer(value) => write!(f, "{}", value),
+            MatchPattern::Wildcard => write!(f, "_"),
+        }
+    }
 }
 
 /// If statement: if (condition) { ... } else { ... }  -- NEW!
@@ -80,14 +137,27 @@ pub struct IfStatement {
     pub span: Span,
 }
 
+/// While loop: while (condition) { ... } else { ... }
+///
+/// The else block runs only when the body never ran, i.e. the condition was
+/// false on the first test.
+#[derive(Debug, Clone)]
+pub struct WhileStatement {
+    pub condition: Expression,
+    pub body: Block,
+    pub else_block: Option<Block>,
+    pub span: Span,
+}
+
 // Add Display implementations
-impl std::fmt::Display for BinaryOperator {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             BinaryOperator::Add => write!(f, "+"),
             BinaryOperator::Subtract => write!(f, "-"),
             BinaryOperator::Multiply => write!(f, "*"),
             BinaryOperator::Divide => write!(f, "/"),
+            BinaryOperator::Power => write!(f, "**"),
             BinaryOperator::Equal => write!(f, "=="),
             BinaryOperator::NotEqual => write!(f, "!="),
             BinaryOperator::Less => write!(f, "<"),
@@ -98,8 +168,8 @@ impl std::fmt::Display for BinaryOperator {
     }
 }
 
-impl std::fmt::Display for LogicalOperator {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for LogicalOperator {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             LogicalOperator::And => write!(f, "&&"),
             LogicalOperator::Or => write!(f, "||"),
@@ -107,8 +177,8 @@ impl std::fmt::Display for LogicalOperator {
     }
 }
 
-impl std::fmt::Display for UnaryOperator {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             UnaryOperator::Not => write!(f, "!"),
         }
@@ -117,23 +187,54 @@ impl std::fmt::Display for UnaryOperator {
 
 // Helper constructors
 impl LogicalExpression {
-    pub fn new(left: Expression, operator: LogicalOperator, right: Expression, span: Span) -> Self {
-        LogicalExpression {
+    pub fn new(
+        left: Expression,
+        operator: LogicalOperator,
+        right: Expression,
+        span: Span,
+    ) -> Spanned<Self> {
+        let node = LogicalExpression {
             left: Box::new(left),
             operator,
             right: Box::new(right),
-            span,
-        }
+        };
+        Spanned::new(node, span)
     }
 }
 
 impl UnaryExpression {
-    pub fn new(operator: UnaryOperator, operand: Expression, span: Span) -> Self {
-        UnaryExpression {
+    pub fn new(operator: UnaryOperator, operand: Expression, span: Span) -> Spanned<Self> {
+        let node = UnaryExpression {
             operator,
             operand: Box::new(operand),
-            span,
-        }
+        };
+        Spanned::new(node, span)
+    }
+}
+
+impl ConditionalExpression {
+    pub fn new(
+        condition: Expression,
+        then_expr: Expression,
+        else_expr: Expression,
+        span: Span,
+    ) -> Spanned<Self> {
+        let node = ConditionalExpression {
+            condition: Box::new(condition),
+            then_expr: Box::new(then_expr),
+            else_expr: Box::new(else_expr),
+        };
+        Spanned::new(node, span)
+    }
+}
+
+impl AssignmentExpression {
+    pub fn new(target: String, value: Expression, span: Span) -> Spanned<Self> {
+        let node = AssignmentExpression {
+            target,
+            value: Box::new(value),
+        };
+        Spanned::new(node, span)
     }
 }
 
@@ -163,6 +264,9 @@ impl Statement {
             Statement::Return(r) => r.span,
             Statement::Describe(d) => d.span,
             Statement::If(i) => i.span,
+            Statement::While(w) => w.span,
+            Statement::Match(m) => m.span,
+            Statement::Break(span) | Statement::Empty(span) => *span,
         }
     }
 }
@@ -210,30 +314,34 @@ pub struct Describe {
     pub span: Span,
 }
 
-/// Expressions
+/// Expressions, each carrying its span through `Spanned`
 #[derive(Debug, Clone)]
 pub enum Expression {
-    Integer(IntegerLiteral),
-    String(StringLiteral),
-    Boolean(BooleanLiteral),
-    Variable(Variable),
-    Binary(BinaryExpression),
-    Logical(LogicalExpression),
-    Unary(UnaryExpression),
-    FunctionCall(FunctionCall),
+    Integer(Spanned<IntegerLiteral>),
+    String(Spanned<StringLiteral>),
+    Boolean(Spanned<BooleanLiteral>),
+    Variable(Spanned<Variable>),
+    Binary(Spanned<BinaryExpression>),
+    Logical(Spanned<LogicalExpression>),
+    Unary(Spanned<UnaryExpression>),
+    FunctionCall(Spanned<FunctionCall>),
+    Conditional(Spanned<ConditionalExpression>),
+    Assignment(Spanned<AssignmentExpression>),
 }
 
 impl Expression {
     pub fn span(&self) -> Span {
         match self {
-            Expression::Integer(i) => i.span,
-            Expression::String(s) => s.span,
-            Expression::Boolean(b) => b.span,
-            Expression::Variable(v) => v.span,
-            Expression::Binary(b) => b.span,
-            Expression::Logical(l) => l.span,
-            Expression::Unary(u) => u.span,
-            Expression::FunctionCall(f) => f.span,
+            Expression::Integer(Spanned { span, .. })
+            | Expression::String(Spanned { span, .. })
+            | Expression::Boolean(Spanned { span, .. })
+            | Expression::Variable(Spanned { span, .. })
+            | Expression::Binary(Spanned { span, .. })
+            | Expression::Logical(Spanned { span, .. })
+            | Expression::Unary(Spanned { span, .. })
+            | Expression::FunctionCall(Spanned { span, .. })
+            | Expression::Conditional(Spanned { span, .. })
+            | Expression::Assignment(Spanned { span, .. }) => *span,
         }
     }
 }
@@ -242,28 +350,24 @@ impl Expression {
 #[derive(Debug, Clone)]
 pub struct IntegerLiteral {
     pub value: i64,
-    pub span: Span,
 }
 
 /// String literal: "hello world"  -- NEW!
 #[derive(Debug, Clone)]
 pub struct StringLiteral {
     pub value: String,
-    pub span: Span,
 }
 
 /// Boolean literal: true, false
 #[derive(Debug, Clone)]
 pub struct BooleanLiteral {
     pub value: bool,
-    pub span: Span,
 }
 
 /// Variable reference: v$0
 #[derive(Debug, Clone)]
 pub struct Variable {
     pub name: String,
-    pub span: Span,
 }
 
 /// Binary expression: v$0 + v$1
@@ -272,7 +376,6 @@ pub struct BinaryExpression {
     pub left: Box<Expression>,
     pub operator: BinaryOperator,
     pub right: Box<Expression>,
-    pub span: Span,
 }
 
 /// Logical expression: v$0 && v$1
@@ -281,7 +384,6 @@ pub struct LogicalExpression {
     pub left: Box<Expression>,
     pub operator: LogicalOperator,
     pub right: Box<Expression>,
-    pub span: Span,
 }
 
 /// Unary expression: !v$0
@@ -289,7 +391,21 @@ pub struct LogicalExpression {
 pub struct UnaryExpression {
     pub operator: UnaryOperator,
     pub operand: Box<Expression>,
-    pub span: Span,
+}
+
+/// Conditional expression: v$0 > 0 ? 1 : -1
+#[derive(Debug, Clone)]
+pub struct ConditionalExpression {
+    pub condition: Box<Expression>,
+    pub then_expr: Box<Expression>,
+    pub else_expr: Box<Expression>,
+}
+
+/// Assignment used as a value: (v$0 = 5) + 1
+#[derive(Debug, Clone)]
+pub struct AssignmentExpression {
+    pub target: String,
+    pub value: Box<Expression>,
 }
 
 /// Binary operators
@@ -299,6 +415,7 @@ pub enum BinaryOperator {
     Subtract, // -
     Multiply, // *
     Divide,   // /
+    Power,    // **
 
     Equal,        // ==
     NotEqual,     // !=
@@ -326,7 +443,6 @@ pub enum UnaryOperator {
 pub struct FunctionCall {
     pub name: String,
     pub arguments: Vec<Expression>,
-    pub span: Span,
 }
 
 // Helper constructors for easier AST building
@@ -351,9 +467,21 @@ impl Function {
             body,
             span,
             documentation: None,
+            is_pure: false,
+            is_memoized: false,
         }
     }
 
+    pub fn mark_pure(mut self) -> Self {
+        self.is_pure = true;
+        self
+    }
+
+    pub fn mark_memoized(mut self) -> Self {
+        self.is_memoized = true;
+        self
+    }
+
     pub fn with_documentation(mut self, doc: String) -> Self {
         self.documentation = Some(doc);
         self
@@ -382,12 +510,238 @@ impl Block {
 }
 
 impl BinaryExpression {
-    pub fn new(left: Expression, operator: BinaryOperator, right: Expression, span: Span) -> Self {
-        BinaryExpression {
+    pub fn new(
+        left: Expression,
+        operator: BinaryOperator,
+        right: Expression,
+        span: Span,
+    ) -> Spanned<Self> {
+        let node = BinaryExpression {
             left: Box::new(left),
             operator,
             right: Box::new(right),
-            span,
+        };
+        Spanned::new(node, span)
+    }
+}
+
+/// Render a program as an indented tree, one node per line
+///
+/// Every line carries the node label and its span, children are indented by
+/// two spaces. Used by `--emit-ast` and stable enough for snapshot tests.
+pub fn pretty_print(program: &Program) -> String {
+    let mut printer = PrettyPrinter::default();
+    printer.line(&format!("Program @ {}", program.span));
+
+    printer.indent += 1;
+    for item in &program.items {
+        printer.item(item);
+    }
+
+    printer.output
+}
+
+#[derive(Default)]
+struct PrettyPrinter {
+    output: String,
+    indent: usize,
+}
+
+impl PrettyPrinter {
+    fn line(&mut self, text: &str) {
+        self.output.push_str(&"  ".repeat(self.indent));
+        self.output.push_str(text);
+        self.output.push('\n');
+    }
+
+    /// Print a node line followed by its children one level deeper
+    fn node(&mut self, text: &str, children: impl FnOnce(&mut Self)) {
+        self.line(text);
+        self.indent += 1;
+        children(self);
+        self.indent -= 1;
+    }
+
+    fn item(&mut self, item: &Item) {
+        match item {
+            Item::Function(func) => {
+                let params: Vec<String> = func
+                    .params
+                    .iter()
+                    .map(|param| format!("{} {}", param.name, param.param_type))
+                    .collect();
+                let label = format!(
+                    "{}Function {}({}) -> {}{} @ {}",
+                    if func.is_pure { "Pure" } else { "" },
+                    func.name,
+                    params.join(", "),
+                    func.return_type,
+                    if func.is_memoized { " memo" } else { "" },
+                    func.span
+                );
+                self.node(&label, |p| p.block(&func.body));
+            }
+            Item::MainBlock(main) => {
+                self.node(&format!("MainBlock @ {}", main.span), |p| {
+                    p.block(&main.body)
+                });
+            }
+            Item::TypeAlias(alias) => {
+                self.line(&format!(
+                    "TypeAlias {} = {} @ {}",
+                    alias.name, alias.target, alias.span
+                ));
+            }
+            Item::GlobalVariable(var_decl) => {
+                let label = format!(
+                    "GlobalVariable {} {} @ {}",
+                    var_decl.value_type, var_decl.name, var_decl.span
+                );
+                self.node(&label, |p| {
+                    if let Some(init) = &var_decl.initializer {
+                        p.expression(init);
+                    }
+                });
+            }
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        for stmt in &block.statements {
+            self.statement(stmt);
+        }
+    }
+
+    fn statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::VariableDeclaration(var_decl) => {
+                let label = format!(
+                    "VariableDeclaration {} {} @ {}",
+                    var_decl.value_type, var_decl.name, var_decl.span
+                );
+                self.node(&label, |p| {
+                    if let Some(init) = &var_decl.initializer {
+                        p.expression(init);
+                    }
+                });
+            }
+            Statement::ConstantDeclaration(const_decl) => {
+                let label = format!(
+                    "ConstantDeclaration {} {} @ {}",
+                    const_decl.value_type, const_decl.name, const_decl.span
+                );
+                self.node(&label, |p| p.expression(&const_decl.initializer));
+            }
+            Statement::Assignment(assignment) => {
+                let label = format!("Assignment {} @ {}", assignment.target, assignment.span);
+                self.node(&label, |p| p.expression(&assignment.value));
+            }
+            Statement::ExpressionStatement(expr) => {
+                self.node(&format!("ExpressionStatement @ {}", expr.span()), |p| {
+                    p.expression(expr)
+                });
+            }
+            Statement::Return(ret) => {
+                self.node(&format!("Return @ {}", ret.span), |p| {
+                    if let Some(value) = &ret.value {
+                        p.expression(value);
+                    }
+                });
+            }
+            Statement::Describe(describe) => {
+                self.line(&format!(
+                    "Describe {} {:?} @ {}",
+                    describe.target, describe.description, describe.span
+                ));
+            }
+            Statement::If(if_stmt) => {
+                self.node(&format!("If @ {}", if_stmt.span), |p| {
+                    p.expression(&if_stmt.condition);
+                    p.node("Then", |p| p.block(&if_stmt.then_block));
+                    if let Some(else_block) = &if_stmt.else_block {
+                        p.node("Else", |p| p.block(else_block));
+                    }
+                });
+            }
+            Statement::While(while_stmt) => {
+                self.node(&format!("While @ {}", while_stmt.span), |p| {
+                    p.expression(&while_stmt.condition);
+                    p.node("Body", |p| p.block(&while_stmt.body));
+                    if let Some(else_block) = &while_stmt.else_block {
+                        p.node("Else", |p| p.block(else_block));
+                    }
+                });
+            }
+            Statement::Break(span) => self.line(&format!("Break @ {}", span)),
+            Statement::Match(match_stmt) => {
+                self.node(&format!("Match @ {}", match_stmt.span), |p| {
+                    p.expression(&match_stmt.scrutinee);
+                    for arm in &match_stmt.arms {
+                        p.node(&format!("Arm {} @ {}", arm.pattern, arm.span), |p| {
+                            p.block(&arm.body)
+                        });
+                    }
+                });
+            }
+            Statement::Empty(span) => self.line(&format!("Empty @ {}", span)),
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Integer(int) => self.line(&format!("Integer {} @ {}", int.value, int.span)),
+            Expression::String(string) => {
+                self.line(&format!("String {:?} @ {}", string.value, string.span))
+            }
+            Expression::Boolean(boolean) => {
+                self.line(&format!("Boolean {} @ {}", boolean.value, boolean.span))
+            }
+            Expression::Variable(var) => {
+                self.line(&format!("Variable {} @ {}", var.name, var.span))
+            }
+            Expression::Binary(binary) => {
+                self.node(
+                    &format!("Binary {} @ {}", binary.operator, binary.span),
+                    |p| {
+                        p.expression(&binary.left);
+                        p.expression(&binary.right);
+                    },
+                );
+            }
+            Expression::Logical(logical) => {
+                self.node(
+                    &format!("Logical {} @ {}", logical.operator, logical.span),
+                    |p| {
+                        p.expression(&logical.left);
+                        p.expression(&logical.right);
+                    },
+                );
+            }
+            Expression::Unary(unary) => {
+                self.node(&format!("Unary {} @ {}", unary.operator, unary.span), |p| {
+                    p.expression(&unary.operand)
+                });
+            }
+            Expression::FunctionCall(call) => {
+                self.node(&format!("Call {} @ {}", call.name, call.span), |p| {
+                    for arg in &call.arguments {
+                        p.expression(arg);
+                    }
+                });
+            }
+            Expression::Conditional(cond) => {
+                self.node(&format!("Conditional @ {}", cond.span), |p| {
+                    p.expression(&cond.condition);
+                    p.expression(&cond.then_expr);
+                    p.expression(&cond.else_expr);
+                });
+            }
+            Expression::Assignment(assign) => {
+                self.node(
+                    &format!("Assign {} @ {}", assign.target, assign.span),
+                    |p| p.expression(&assign.value),
+                );
+            }
         }
     }
 }
@@ -404,7 +758,10 @@ mod tests {
         let var_decl = VariableDeclaration {
             name: "v$0".to_string(),
             value_type: ValueType::Int,
-            initializer: Some(Expression::Integer(IntegerLiteral { value: 42, span })),
+            initializer: Some(Expression::Integer(Spanned::new(
+                IntegerLiteral { value: 42 },
+                span,
+            ))),
             span,
             documentation: None,
         };
@@ -417,10 +774,12 @@ mod tests {
     fn test_string_literal() {
         let span = Span::new(1, 1, 1, 10);
 
-        let string_lit = StringLiteral {
-            value: "hello world".to_string(),
+        let string_lit = Spanned::new(
+            StringLiteral {
+                value: "hello world".to_string(),
+            },
             span,
-        };
+        );
 
         assert_eq!(string_lit.value, "hello world");
         assert_eq!(string_lit.span, span);
@@ -430,12 +789,14 @@ mod tests {
     fn test_binary_expression() {
         let span = Span::new(1, 1, 1, 10);
 
-        let left = Expression::Variable(Variable {
-            name: "v$0".to_string(),
+        let left = Expression::Variable(Spanned::new(
+            Variable {
+                name: "v$0".to_string(),
+            },
             span,
-        });
+        ));
 
-        let right = Expression::Integer(IntegerLiteral { value: 5, span });
+        let right = Expression::Integer(Spanned::new(IntegerLiteral { value: 5 }, span));
 
         let binary = BinaryExpression::new(left, BinaryOperator::Add, right, span);
 
@@ -462,4 +823,39 @@ mod tests {
         assert_eq!(function.params.len(), 1);
         assert_eq!(function.documentation, Some("Test function".to_string()));
     }
+
+    #[test]
+    fn test_pretty_print() {
+        let source =
+            "fn f$0(v$0 int) -> int {\n    ret v$0 * 2;\n}\nmain {\n    print(f$0(1 + 2));\n}";
+
+        let mut symbol_table = crate::symbol_table::SymbolTable::new();
+        let mut parser = crate::parser::Parser::new(source, &mut symbol_table).unwrap();
+        let program = parser.parse_program().unwrap();
+
+        let labels: Vec<String> = pretty_print(&program)
+            .lines()
+            .map(|line| line.split(" @ ").next().unwrap().to_string())
+            .collect();
+
+        assert_eq!(
+            labels,
+            [
+                "Program",
+                "  Function f$0(v$0 int) -> int",
+                "    Return",
+                "      Binary *",
+                "        Variable v$0",
+                "        Integer 2",
+                "  MainBlock",
+                "    ExpressionStatement",
+                "      Call print",
+                "        Call f$0",
+                "          Binary +",
+                "            Integer 1",
+                "            Integer 2",
+            ]
+        );
+        assert!(pretty_print(&program).contains("    Return @ 2:5-16\n"));
+    }
 }
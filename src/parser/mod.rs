@@ -5,10 +5,13 @@
 pub mod ast;
 
 use crate::{
+    codegen::{const_eval, instruction::Value},
     error::{ZvarError, ZvarResult},
     lexer::{token::Token, Lexer},
-    span::Span,
+    prelude::*,
+    span::{Span, Spanned},
     symbol_table::{EntityType, Symbol, SymbolTable, ValueType},
+    warning::Warning,
 };
 
 use ast::*;
@@ -16,23 +19,46 @@ use ast::*;
 /// Recursive descent parser for zvar
 pub struct Parser<'a> {
     tokens: Vec<Token>,
+    spans: Vec<Span>,
     current: usize,
     symbol_table: &'a mut SymbolTable,
+    // Warn about redundant empty statements
+    strict_semicolons: bool,
+    warnings: Vec<Warning>,
+    // Number of loops around the statement being parsed, for `break`
+    loop_depth: usize,
+    // Compile-time values of the constants declared so far, for `describe`
+    constant_values: BTreeMap<String, Value>,
 }
 
 impl<'a> Parser<'a> {
     /// Create a new parser from source code
     pub fn new(source: &str, symbol_table: &'a mut SymbolTable) -> ZvarResult<Self> {
         let mut lexer = Lexer::new(source);
-        let tokens = lexer.tokenize()?;
+        let (tokens, spans) = lexer.tokenize_with_spans()?.into_iter().unzip();
 
         Ok(Parser {
             tokens,
+            spans,
             current: 0,
             symbol_table,
+            strict_semicolons: false,
+            warnings: Vec::new(),
+            loop_depth: 0,
+            constant_values: BTreeMap::new(),
         })
     }
 
+    /// Enable or disable warnings for redundant empty statements
+    pub fn set_strict_semicolons(&mut self, strict: bool) {
+        self.strict_semicolons = strict;
+    }
+
+    /// Warnings collected while parsing
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
     /// Get the current token without advancing
     fn current_token(&self) -> &Token {
         self.tokens.get(self.current).unwrap_or(&Token::Eof)
@@ -65,7 +91,7 @@ impl<'a> Parser<'a> {
         if self.is_at_end() {
             return false;
         }
-        std::mem::discriminant(self.current_token()) == std::mem::discriminant(token_type)
+        core::mem::discriminant(self.current_token()) == core::mem::discriminant(token_type)
     }
 
     /// Consume a token if it matches, otherwise return error
@@ -81,11 +107,51 @@ impl<'a> Parser<'a> {
         }
     }
 
-    /// Get a span for the current token
+    /// Get the span of the current token
     fn current_span(&self) -> Span {
-        // For now, we'll use a dummy span. In a real implementation,
-        // we'd need to track spans through the lexer
-        Span::new(1, 1, 1, 1)
+        self.spans
+            .get(self.current)
+            .or(self.spans.last())
+            .copied()
+            .unwrap_or(Span::single(1, 1))
+    }
+
+    /// Get the span of the most recently consumed token
+    fn previous_span(&self) -> Span {
+        if self.current > 0 {
+            self.spans[self.current - 1]
+        } else {
+            self.current_span()
+        }
+    }
+
+    /// Span from `start` to the end of the last consumed token
+    ///
+    /// Expression spans are built from tokens rather than from the operand
+    /// nodes, so parentheses around an operand belong to the span too.
+    fn span_from(&self, start: Span) -> Span {
+        Span::from_to(start, self.previous_span())
+    }
+
+    /// Append string literals that directly follow a consumed one
+    ///
+    /// `"a" "b"` is read as `"ab"`. The literals may be on separate lines,
+    /// which is how long strings are split.
+    fn concat_adjacent_strings(&mut self, mut value: String) -> String {
+        loop {
+            let mut next = self.current;
+            while matches!(self.tokens.get(next), Some(Token::Newline)) {
+                next += 1;
+            }
+
+            match self.tokens.get(next) {
+                Some(Token::String(part)) => {
+                    value.push_str(part);
+                    self.current = next + 1;
+                }
+                _ => return value,
+            }
+        }
     }
 
     /// Skip newlines and comments
@@ -95,6 +161,13 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Documentation currently attached to a symbol
+    fn documentation_of(&self, name: &str) -> Option<String> {
+        self.symbol_table
+            .lookup(name)
+            .and_then(|symbol| symbol.documentation.clone())
+    }
+
     /// Collect documentation comments
     fn collect_documentation(&mut self) -> Option<String> {
         let mut docs = Vec::new();
@@ -131,16 +204,36 @@ impl<'a> Parser<'a> {
             self.skip_newlines();
         }
 
-        let end_span = self.current_span();
+        let end_span = self.previous_span();
         let span = Span::from_to(start_span, end_span);
 
         Ok(Program::new(items, span))
     }
 
-    /// Parse a top-level item (function or main block)
+    /// Parse input that consists of a single expression and nothing else
+    ///
+    /// The REPL tries this first, so a bare `1 + 2` shows its value instead
+    /// of running as an expression statement.
+    pub fn parse_expression_only(&mut self) -> ZvarResult<Expression> {
+        self.skip_newlines();
+        let expr = self.parse_expression()?;
+        self.skip_newlines();
+
+        if !self.is_at_end() {
+            return Err(ZvarError::UnexpectedToken {
+                span: self.current_span(),
+                expected: "end of expression".to_string(),
+                found: self.current_token().to_string(),
+            });
+        }
+
+        Ok(expr)
+    }
+
+    /// Parse a top-level item (function, main block, type alias or global variable)
     fn parse_item(&mut self) -> ZvarResult<Item> {
         match self.current_token() {
-            Token::Fn => {
+            Token::Fn | Token::Pure | Token::Memo => {
                 let function = self.parse_function()?;
                 Ok(Item::Function(function))
             }
@@ -148,19 +241,85 @@ impl<'a> Parser<'a> {
                 let main_block = self.parse_main_block()?;
                 Ok(Item::MainBlock(main_block))
             }
+            Token::Type => {
+                let type_alias = self.parse_type_alias()?;
+                Ok(Item::TypeAlias(type_alias))
+            }
+            Token::Int | Token::Str | Token::Bool | Token::TypeName(_) => {
+                // Global variable, shared by main and all functions
+                let value_type = self.parse_type()?;
+                let var_decl = self.parse_variable_declaration_after_type(value_type)?;
+                self.symbol_table.mark_global(&var_decl.name);
+                Ok(Item::GlobalVariable(var_decl))
+            }
             _ => Err(ZvarError::UnexpectedToken {
                 span: self.current_span(),
-                expected: "fn or main".to_string(),
+                expected: "fn, main, type or global variable".to_string(),
                 found: self.current_token().to_string(),
             }),
         }
     }
 
+    /// Parse a type alias: type Name = <type>;
+    fn parse_type_alias(&mut self) -> ZvarResult<TypeAlias> {
+        let start_span = self.current_span();
+
+        self.consume(Token::Type, "Expected 'type'")?;
+
+        let name = match self.current_token() {
+            Token::TypeName(name) => {
+                let name = name.clone();
+                self.advance();
+                name
+            }
+            _ => {
+                return Err(ZvarError::UnexpectedToken {
+                    span: self.current_span(),
+                    expected: "type alias name".to_string(),
+                    found: self.current_token().to_string(),
+                });
+            }
+        };
+
+        self.consume(Token::Assign, "Expected '='")?;
+        let target = self.parse_type()?;
+        self.consume(Token::Semicolon, "Expected ';'")?;
+
+        let end_span = self.previous_span();
+        let span = Span::from_to(start_span, end_span);
+
+        self.symbol_table
+            .define_type_alias(name.clone(), target.clone(), span)?;
+
+        Ok(TypeAlias {
+            name,
+            target,
+            span,
+            documentation: self.symbol_table.take_pending_docs(),
+        })
+    }
+
     /// Parse a function definition
     fn parse_function(&mut self) -> ZvarResult<Function> {
         let start_span = self.current_span();
 
-        // fn
+        // [pure [memo]] fn
+        let is_pure = self.check(&Token::Pure);
+        if is_pure {
+            self.advance();
+        }
+        let is_memoized = self.check(&Token::Memo);
+        if is_memoized {
+            // Caching results is only sound when they depend on the arguments alone
+            if !is_pure {
+                return Err(ZvarError::UnexpectedToken {
+                    span: self.current_span(),
+                    expected: "'pure' before 'memo'".to_string(),
+                    found: self.current_token().to_string(),
+                });
+            }
+            self.advance();
+        }
         self.consume(Token::Fn, "Expected 'fn'")?;
 
         // Function name (f$N)
@@ -212,6 +371,9 @@ impl<'a> Parser<'a> {
         );
         self.symbol_table.define(name.clone(), func_symbol)?;
 
+        // `define` attached any pending documentation to the symbol
+        let documentation = self.documentation_of(&name);
+
         // Enter function scope
         self.symbol_table.enter_scope();
 
@@ -232,13 +394,19 @@ impl<'a> Parser<'a> {
         // Exit function scope
         self.symbol_table.exit_scope();
 
-        let end_span = self.current_span();
+        let end_span = self.previous_span();
         let span = Span::from_to(start_span, end_span);
 
         let mut function = Function::new(name, params, return_type, body, span);
+        if is_pure {
+            function = function.mark_pure();
+        }
+        if is_memoized {
+            function = function.mark_memoized();
+        }
 
         // Attach documentation if any
-        if let Some(docs) = self.symbol_table.take_pending_docs() {
+        if let Some(docs) = documentation {
             function = function.with_documentation(docs);
         }
 
@@ -268,7 +436,7 @@ impl<'a> Parser<'a> {
         // Parameter type
         let param_type = self.parse_type()?;
 
-        let end_span = self.current_span();
+        let end_span = self.previous_span();
         let span = Span::from_to(start_span, end_span);
 
         Ok(Parameter {
@@ -294,7 +462,7 @@ impl<'a> Parser<'a> {
         // Exit main scope
         self.symbol_table.exit_scope();
 
-        let end_span = self.current_span();
+        let end_span = self.previous_span();
         let span = Span::from_to(start_span, end_span);
 
         let mut main_block = MainBlock::new(body, span);
@@ -330,7 +498,7 @@ impl<'a> Parser<'a> {
 
         self.consume(Token::RightBrace, "Expected '}'")?;
 
-        let end_span = self.current_span();
+        let end_span = self.previous_span();
         let span = Span::from_to(start_span, end_span);
 
         Ok(Block::new(statements, span))
@@ -339,23 +507,9 @@ impl<'a> Parser<'a> {
     /// Parse a statement
     fn parse_statement(&mut self) -> ZvarResult<Statement> {
         match self.current_token() {
-            Token::Int | Token::Str | Token::Bool => {
+            Token::Int | Token::Str | Token::Bool | Token::TypeName(_) => {
                 // Could be variable or constant declaration
-                let value_type = match self.current_token() {
-                    Token::Int => {
-                        self.advance();
-                        ValueType::Int
-                    }
-                    Token::Str => {
-                        self.advance();
-                        ValueType::Str
-                    }
-                    Token::Bool => {
-                        self.advance();
-                        ValueType::Bool
-                    }
-                    _ => unreachable!(),
-                };
+                let value_type = self.parse_type()?;
 
                 match self.current_token() {
                     Token::Variable(_) => {
@@ -390,6 +544,35 @@ impl<'a> Parser<'a> {
                 let if_stmt = self.parse_if_statement()?;
                 Ok(Statement::If(if_stmt))
             }
+            Token::While => {
+                let while_stmt = self.parse_while_statement()?;
+                Ok(Statement::While(while_stmt))
+            }
+            Token::Break => {
+                let span = self.current_span();
+                if self.loop_depth == 0 {
+                    return Err(ZvarError::BreakOutsideLoop { span });
+                }
+                self.advance();
+                self.consume(Token::Semicolon, "Expected ';' after 'break'")?;
+                Ok(Statement::Break(span))
+            }
+            Token::Match => {
+                let match_stmt = self.parse_match_statement()?;
+                Ok(Statement::Match(match_stmt))
+            }
+            Token::Semicolon => {
+                // A lone ';' is an empty statement
+                let span = self.current_span();
+                self.advance();
+
+                if self.strict_semicolons {
+                    self.warnings
+                        .push(Warning::new(span, "redundant empty statement ';'"));
+                }
+
+                Ok(Statement::Empty(span))
+            }
             _ => {
                 // Expression statement
                 let expr = self.parse_expression()?;
@@ -419,12 +602,121 @@ impl<'a> Parser<'a> {
             None
         };
 
-        let end_span = self.current_span();
+        let end_span = self.previous_span();
         let span = Span::from_to(start_span, end_span);
 
         Ok(IfStatement::new(condition, then_block, else_block, span))
     }
 
+    /// Parse while loop: while (expr) { ... } with an optional else block
+    fn parse_while_statement(&mut self) -> ZvarResult<WhileStatement> {
+        let start_span = self.current_span();
+
+        self.consume(Token::While, "Expected 'while'")?;
+        self.consume(Token::LeftParen, "Expected '('")?;
+        let condition = self.parse_expression()?;
+        self.consume(Token::RightParen, "Expected ')'")?;
+
+        self.loop_depth += 1;
+        let body = self.parse_block();
+        self.loop_depth -= 1;
+        let body = body?;
+
+        // The else block is outside the loop, so it cannot break
+        let else_block = if self.check(&Token::Else) {
+            self.advance(); // consume 'else'
+            Some(self.parse_block()?)
+        } else {
+            None
+        };
+
+        let span = self.span_from(start_span);
+        Ok(WhileStatement {
+            condition,
+            body,
+            else_block,
+            span,
+        })
+    }
+
+    /// Parse match statement: match (expr) { <int> => { ... } ... _ => { ... } }
+    fn parse_match_statement(&mut self) -> ZvarResult<MatchStatement> {
+        let start_span = self.current_span();
+
+        self.consume(Token::Match, "Expected 'match'")?;
+        self.consume(Token::LeftParen, "Expected '('")?;
+        let scrutinee = self.parse_expression()?;
+        self.consume(Token::RightParen, "Expected ')'")?;
+
+        self.consume(Token::LeftBrace, "Expected '{'")?;
+        self.skip_newlines();
+
+        let mut arms: Vec<MatchArm> = Vec::new();
+        while !self.check(&Token::RightBrace) && !self.is_at_end() {
+            // Arms after the wildcard could never run
+            if arms
+                .last()
+                .is_some_and(|arm| arm.pattern == MatchPattern::Wildcard)
+            {
+                return Err(ZvarError::UnexpectedToken {
+                    span: self.current_span(),
+                    expected: "'}' (the wildcard arm must be last)".to_string(),
+                    found: self.current_token().to_string(),
+                });
+            }
+
+            arms.push(self.parse_match_arm()?);
+            self.skip_newlines();
+        }
+
+        self.consume(Token::RightBrace, "Expected '}'")?;
+
+        let end_span = self.previous_span();
+        let span = Span::from_to(start_span, end_span);
+
+        Ok(MatchStatement {
+            scrutinee,
+            arms,
+            span,
+        })
+    }
+
+    /// Parse a match arm: an integer literal or `_`, then `=>` and a block
+    fn parse_match_arm(&mut self) -> ZvarResult<MatchArm> {
+        let start_span = self.current_span();
+
+        let pattern = match self.current_token() {
+            Token::Integer(value) => {
+                let value = *value;
+                self.advance();
+                MatchPattern::Integer(value)
+            }
+            Token::Underscore => {
+                self.advance();
+                MatchPattern::Wildcard
+            }
+            _ => {
+                return Err(ZvarError::UnexpectedToken {
+                    span: self.current_span(),
+                    expected: "integer pattern or '_'".to_string(),
+                    found: self.current_token().to_string(),
+                });
+            }
+        };
+
+        self.consume(Token::FatArrow, "Expected '=>'")?;
+        let body = self.parse_block()?;
+
+        let end_span = self.previous_span();
+        let span = Span::from_to(start_span, end_span);
+
+        Ok(MatchArm {
+            pattern,
+            body,
+            span,
+        })
+    }
+
     /// Parse variable declaration after type has been consumed
     fn parse_variable_declaration_after_type(
         &mut self,
@@ -458,7 +750,7 @@ impl<'a> Parser<'a> {
 
         self.consume(Token::Semicolon, "Expected ';'")?;
 
-        let end_span = self.current_span();
+        let end_span = self.previous_span();
         let span = Span::from_to(start_span, end_span);
 
         // Add to symbol table
@@ -475,19 +767,17 @@ impl<'a> Parser<'a> {
 
         self.symbol_table.define(name.clone(), symbol)?;
 
-        let mut var_decl = VariableDeclaration {
+        // `define` attached any pending documentation to the symbol
+        let documentation = self.documentation_of(&name);
+
+        let var_decl = VariableDeclaration {
             name,
             value_type,
             initializer,
             span,
-            documentation: None,
+            documentation,
         };
 
-        // Attach documentation if any
-        if let Some(docs) = self.symbol_table.take_pending_docs() {
-            var_decl.documentation = Some(docs);
-        }
-
         Ok(var_decl)
     }
 
@@ -523,7 +813,7 @@ impl<'a> Parser<'a> {
 
         self.consume(Token::Semicolon, "Expected ';'")?;
 
-        let end_span = self.current_span();
+        let end_span = self.previous_span();
         let span = Span::from_to(start_span, end_span);
 
         // Add to symbol table
@@ -537,27 +827,47 @@ impl<'a> Parser<'a> {
 
         self.symbol_table.define(name.clone(), symbol)?;
 
-        let mut const_decl = ConstantDeclaration {
+        // `define` attached any pending documentation to the symbol
+        let documentation = self.documentation_of(&name);
+
+        // A constant that shadows another must not keep the other's value
+        match const_eval::evaluate_with(&initializer, &self.constant_values) {
+            Some(value) => self.constant_values.insert(name.clone(), value),
+            None => self.constant_values.remove(&name),
+        };
+
+        let const_decl = ConstantDeclaration {
             name,
             value_type,
             initializer,
             span,
-            documentation: None,
+            documentation,
         };
 
-        // Attach documentation if any
-        if let Some(docs) = self.symbol_table.take_pending_docs() {
-            const_decl.documentation = Some(docs);
-        }
-
         Ok(const_decl)
     }
 
     /// Parse assignment statement
     fn parse_assignment(&mut self) -> ZvarResult<Assignment> {
         let start_span = self.current_span();
+        let target = self.parse_assignment_target()?;
 
-        // Target variable
+        self.consume(Token::Assign, "Expected '='")?;
+        let value = self.parse_expression()?;
+        self.consume(Token::Semicolon, "Expected ';'")?;
+
+        let end_span = self.previous_span();
+        let span = Span::from_to(start_span, end_span);
+
+        Ok(Assignment {
+            target,
+            value,
+            span,
+        })
+    }
+
+    /// Parse the variable on the left of '=', which must be declared and not a constant
+    fn parse_assignment_target(&mut self) -> ZvarResult<String> {
         let target = match self.current_token() {
             Token::Variable(n) => {
                 let name = format!("v${}", n);
@@ -588,18 +898,7 @@ impl<'a> Parser<'a> {
             });
         }
 
-        self.consume(Token::Assign, "Expected '='")?;
-        let value = self.parse_expression()?;
-        self.consume(Token::Semicolon, "Expected ';'")?;
-
-        let end_span = self.current_span();
-        let span = Span::from_to(start_span, end_span);
-
-        Ok(Assignment {
-            target,
-            value,
-            span,
-        })
+        Ok(target)
     }
 
     /// Parse return statement
@@ -616,7 +915,7 @@ impl<'a> Parser<'a> {
 
         self.consume(Token::Semicolon, "Expected ';'")?;
 
-        let end_span = self.current_span();
+        let end_span = self.previous_span();
         let span = Span::from_to(start_span, end_span);
 
         Ok(Return { value, span })
@@ -657,26 +956,17 @@ impl<'a> Parser<'a> {
 
         self.consume(Token::Comma, "Expected ','")?;
 
-        // Description string
-        let description = match self.current_token() {
-            Token::String(s) => {
-                let desc = s.clone();
-                self.advance();
-                desc
-            }
-            _ => {
-                return Err(ZvarError::UnexpectedToken {
-                    span: self.current_span(),
-                    expected: "string literal".to_string(),
-                    found: self.current_token().to_string(),
-                });
-            }
-        };
+        // Description: strings and constants joined with '+'
+        let mut description = self.parse_description_part()?;
+        while self.check(&Token::Plus) {
+            self.advance();
+            description.push_str(&self.parse_description_part()?);
+        }
 
         self.consume(Token::RightParen, "Expected ')'")?;
         self.consume(Token::Semicolon, "Expected ';'")?;
 
-        let end_span = self.current_span();
+        let end_span = self.previous_span();
         let span = Span::from_to(start_span, end_span);
 
         // Try to add documentation, but don't fail if entity doesn't exist yet
@@ -691,6 +981,49 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Parse a string literal or a constant of a `describe` description
+    ///
+    /// Constants are replaced by their compile-time value, so they must be
+    /// declared before the `describe` and initialized from literals or
+    /// other such constants.
+    fn parse_description_part(&mut self) -> ZvarResult<String> {
+        let span = self.current_span();
+        match self.current_token() {
+            Token::String(s) => {
+                let part = s.clone();
+                self.advance();
+                Ok(self.concat_adjacent_strings(part))
+            }
+            Token::Constant(n) => {
+                let name = format!("c${}", n);
+                self.advance();
+
+                if !self
+                    .symbol_table
+                    .lookup(&name)
+                    .is_some_and(|symbol| symbol.is_constant())
+                {
+                    return Err(ZvarError::UndefinedEntity { span, name });
+                }
+                self.symbol_table.record_usage(&name);
+
+                match self.constant_values.get(&name) {
+                    Some(value) => Ok(value.to_string()),
+                    None => Err(ZvarError::UnexpectedToken {
+                        span,
+                        expected: "constant with a compile-time value".to_string(),
+                        found: name,
+                    }),
+                }
+            }
+            _ => Err(ZvarError::UnexpectedToken {
+                span,
+                expected: "string literal or constant (c$N)".to_string(),
+                found: self.current_token().to_string(),
+            }),
+        }
+    }
+
     /// Parse a type
     fn parse_type(&mut self) -> ZvarResult<ValueType> {
         match self.current_token() {
@@ -706,6 +1039,18 @@ impl<'a> Parser<'a> {
                 self.advance();
                 Ok(ValueType::Bool)
             }
+            Token::TypeName(name) => {
+                let value_type = self
+                    .symbol_table
+                    .resolve_type_alias(name)
+                    .cloned()
+                    .ok_or_else(|| ZvarError::UndefinedEntity {
+                        span: self.current_span(),
+                        name: name.clone(),
+                    })?;
+                self.advance();
+                Ok(value_type)
+            }
             _ => Err(ZvarError::UnexpectedToken {
                 span: self.current_span(),
                 expected: "type".to_string(),
@@ -716,18 +1061,54 @@ impl<'a> Parser<'a> {
 
     /// Parse an expression (updated with precedence for logical operators)
     fn parse_expression(&mut self) -> ZvarResult<Expression> {
-        self.parse_logical_or()
+        // `v$N = value` is an expression producing the stored value, right-associative
+        if matches!(self.current_token(), Token::Variable(_))
+            && matches!(self.tokens.get(self.current + 1), Some(Token::Assign))
+        {
+            let start_span = self.current_span();
+            let target = self.parse_assignment_target()?;
+            self.advance(); // consume '='
+            let value = self.parse_expression()?;
+            let span = self.span_from(start_span);
+
+            return Ok(Expression::Assignment(AssignmentExpression::new(
+                target, value, span,
+            )));
+        }
+
+        self.parse_conditional()
+    }
+
+    /// Parse conditional expressions (cond ? a : b), right-associative
+    fn parse_conditional(&mut self) -> ZvarResult<Expression> {
+        let start_span = self.current_span();
+        let condition = self.parse_logical_or()?;
+
+        if !matches!(self.current_token(), Token::Question) {
+            return Ok(condition);
+        }
+
+        self.advance(); // consume '?'
+        let then_expr = self.parse_expression()?;
+        self.consume(Token::Colon, "Expected ':'")?;
+        let else_expr = self.parse_conditional()?;
+        let span = self.span_from(start_span);
+
+        Ok(Expression::Conditional(ConditionalExpression::new(
+            condition, then_expr, else_expr, span,
+        )))
     }
 
     /// Parse logical OR expressions
     fn parse_logical_or(&mut self) -> ZvarResult<Expression> {
+        let start_span = self.current_span();
         let mut expr = self.parse_logical_and()?;
 
         while matches!(self.current_token(), Token::Or) {
             let operator = LogicalOperator::Or;
             self.advance();
             let right = self.parse_logical_and()?;
-            let span = Span::from_to(expr.span(), right.span());
+            let span = self.span_from(start_span);
 
             expr = Expression::Logical(LogicalExpression::new(expr, operator, right, span));
         }
@@ -737,13 +1118,14 @@ impl<'a> Parser<'a> {
 
     /// Parse logical AND expressions
     fn parse_logical_and(&mut self) -> ZvarResult<Expression> {
+        let start_span = self.current_span();
         let mut expr = self.parse_equality()?;
 
         while matches!(self.current_token(), Token::And) {
             let operator = LogicalOperator::And;
             self.advance();
             let right = self.parse_equality()?;
-            let span = Span::from_to(expr.span(), right.span());
+            let span = self.span_from(start_span);
 
             expr = Expression::Logical(LogicalExpression::new(expr, operator, right, span));
         }
@@ -753,6 +1135,7 @@ impl<'a> Parser<'a> {
 
     /// Parse equality expressions
     fn parse_equality(&mut self) -> ZvarResult<Expression> {
+        let start_span = self.current_span();
         let mut expr = self.parse_comparison()?;
 
         while matches!(self.current_token(), Token::Equal | Token::NotEqual) {
@@ -764,7 +1147,7 @@ impl<'a> Parser<'a> {
 
             self.advance();
             let right = self.parse_comparison()?;
-            let span = Span::from_to(expr.span(), right.span());
+            let span = self.span_from(start_span);
 
             expr = Expression::Binary(BinaryExpression::new(expr, operator, right, span));
         }
@@ -774,6 +1157,7 @@ impl<'a> Parser<'a> {
 
     /// Parse comparison expressions
     fn parse_comparison(&mut self) -> ZvarResult<Expression> {
+        let start_span = self.current_span();
         let mut expr = self.parse_additive()?;
 
         while matches!(
@@ -790,7 +1174,7 @@ impl<'a> Parser<'a> {
 
             self.advance();
             let right = self.parse_additive()?;
-            let span = Span::from_to(expr.span(), right.span());
+            let span = self.span_from(start_span);
 
             expr = Expression::Binary(BinaryExpression::new(expr, operator, right, span));
         }
@@ -800,6 +1184,7 @@ impl<'a> Parser<'a> {
 
     /// Parse additive expressions (+ and -)
     fn parse_additive(&mut self) -> ZvarResult<Expression> {
+        let start_span = self.current_span();
         let mut expr = self.parse_multiplicative()?;
 
         while matches!(self.current_token(), Token::Plus | Token::Minus) {
@@ -811,7 +1196,7 @@ impl<'a> Parser<'a> {
 
             self.advance();
             let right = self.parse_multiplicative()?;
-            let span = Span::from_to(expr.span(), right.span());
+            let span = self.span_from(start_span);
 
             expr = Expression::Binary(BinaryExpression::new(expr, operator, right, span));
         }
@@ -821,7 +1206,8 @@ impl<'a> Parser<'a> {
 
     /// Parse multiplicative expressions (* and /)
     fn parse_multiplicative(&mut self) -> ZvarResult<Expression> {
-        let mut expr = self.parse_unary()?;
+        let start_span = self.current_span();
+        let mut expr = self.parse_power()?;
 
         while matches!(self.current_token(), Token::Multiply | Token::Divide) {
             let operator = match self.current_token() {
@@ -831,8 +1217,8 @@ impl<'a> Parser<'a> {
             };
 
             self.advance();
-            let right = self.parse_unary()?;
-            let span = Span::from_to(expr.span(), right.span());
+            let right = self.parse_power()?;
+            let span = self.span_from(start_span);
 
             expr = Expression::Binary(BinaryExpression::new(expr, operator, right, span));
         }
@@ -840,6 +1226,27 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 
+    /// Parse exponentiation (**), which is right-associative
+    fn parse_power(&mut self) -> ZvarResult<Expression> {
+        let start_span = self.current_span();
+        let base = self.parse_unary()?;
+
+        if !self.check(&Token::Power) {
+            return Ok(base);
+        }
+
+        self.advance();
+        let exponent = self.parse_power()?;
+        let span = self.span_from(start_span);
+
+        Ok(Expression::Binary(BinaryExpression::new(
+            base,
+            BinaryOperator::Power,
+            exponent,
+            span,
+        )))
+    }
+
     /// Parse unary expressions
     fn parse_unary(&mut self) -> ZvarResult<Expression> {
         match self.current_token() {
@@ -848,7 +1255,7 @@ impl<'a> Parser<'a> {
                 let start_span = self.current_span();
                 self.advance();
                 let operand = self.parse_unary()?;
-                let span = Span::from_to(start_span, operand.span());
+                let span = self.span_from(start_span);
                 Ok(Expression::Unary(UnaryExpression::new(
                     operator, operand, span,
                 )))
@@ -865,20 +1272,28 @@ impl<'a> Parser<'a> {
             Token::Integer(value) => {
                 let value = *value;
                 self.advance();
-                Ok(Expression::Integer(IntegerLiteral { value, span }))
+                Ok(Expression::Integer(Spanned::new(
+                    IntegerLiteral { value },
+                    span,
+                )))
             }
             Token::String(value) => {
                 let value = value.clone();
                 self.advance();
-                Ok(Expression::String(StringLiteral { value, span }))
-            }
-            Token::True => {
-                self.advance();
-                Ok(Expression::Boolean(BooleanLiteral { value: true, span }))
+                let value = self.concat_adjacent_strings(value);
+                let span = Span::from_to(span, self.previous_span());
+                Ok(Expression::String(Spanned::new(
+                    StringLiteral { value },
+                    span,
+                )))
             }
-            Token::False => {
+            Token::Boolean(value) => {
+                let value = *value;
                 self.advance();
-                Ok(Expression::Boolean(BooleanLiteral { value: false, span }))
+                Ok(Expression::Boolean(Spanned::new(
+                    BooleanLiteral { value },
+                    span,
+                )))
             }
             Token::Variable(n) => {
                 let name = format!("v${}", n);
@@ -893,19 +1308,25 @@ impl<'a> Parser<'a> {
                     });
                 }
 
-                Ok(Expression::Variable(Variable { name, span }))
+                self.symbol_table.record_usage(&name);
+                Ok(Expression::Variable(Spanned::new(Variable { name }, span)))
             }
             Token::Constant(n) => {
                 let name = format!("c${}", n);
                 self.advance();
-                Ok(Expression::Variable(Variable { name, span }))
+                self.symbol_table.record_usage(&name);
+                Ok(Expression::Variable(Spanned::new(Variable { name }, span)))
             }
             Token::Function(n) => {
                 let name = format!("f${}", n);
                 self.advance();
+                self.symbol_table.record_usage(&name);
 
                 // Must be a function call
-                self.consume(Token::LeftParen, "Expected '(' after function name")?;
+                if !self.check(&Token::LeftParen) {
+                    return Err(ZvarError::FunctionAsValue { span, name });
+                }
+                self.advance();
 
                 let mut arguments = Vec::new();
                 if !self.check(&Token::RightParen) {
@@ -923,20 +1344,27 @@ impl<'a> Parser<'a> {
 
                 self.consume(Token::RightParen, "Expected ')'")?;
 
-                let end_span = self.current_span();
-                let call_span = Span::from_to(span, end_span);
+                let mut spans = vec![span, self.previous_span()];
+                spans.extend(arguments.iter().map(Expression::span));
+                let call_span = Span::merge(&spans);
 
-                Ok(Expression::FunctionCall(FunctionCall {
-                    name,
-                    arguments,
-                    span: call_span,
-                }))
+                Ok(Expression::FunctionCall(Spanned::new(
+                    FunctionCall { name, arguments },
+                    call_span,
+                )))
             }
-            Token::Print => {
-                let name = "print".to_string();
+            Token::Print
+            | Token::Random
+            | Token::EqIgnoreCase
+            | Token::ToUpper
+            | Token::ToLower
+            | Token::Trim
+            | Token::Len
+            | Token::ByteLen => {
+                let name = self.current_token().to_string();
                 self.advance();
 
-                self.consume(Token::LeftParen, "Expected '(' after 'print'")?;
+                self.consume(Token::LeftParen, &format!("Expected '(' after '{}'", name))?;
 
                 let mut arguments = Vec::new();
                 if !self.check(&Token::RightParen) {
@@ -953,14 +1381,14 @@ impl<'a> Parser<'a> {
                 }
 
                 self.consume(Token::RightParen, "Expected ')'")?;
-                let end_span = self.current_span();
-                let call_span = Span::from_to(span, end_span);
+                let mut spans = vec![span, self.previous_span()];
+                spans.extend(arguments.iter().map(Expression::span));
+                let call_span = Span::merge(&spans);
 
-                Ok(Expression::FunctionCall(FunctionCall {
-                    name,
-                    arguments,
-                    span: call_span,
-                }))
+                Ok(Expression::FunctionCall(Spanned::new(
+                    FunctionCall { name, arguments },
+                    call_span,
+                )))
             }
             Token::LeftParen => {
                 self.advance(); // consume '('
@@ -1097,6 +1525,131 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_while_statement() {
+        let source = "main { int v$0 = 1; while (v$0 > 0) { break; } else { v$0 = 2; } }";
+
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        let program = parser.parse_program().unwrap();
+
+        let Item::MainBlock(main) = &program.items[0] else {
+            panic!("Expected main block");
+        };
+        let Statement::While(while_stmt) = &main.body.statements[1] else {
+            panic!("Expected while statement");
+        };
+        assert!(matches!(while_stmt.body.statements[0], Statement::Break(_)));
+        assert!(while_stmt.else_block.is_some());
+
+        // The else block is not part of the loop
+        let source = "main { int v$0 = 1; while (v$0 > 0) { v$0 = 0; } else { break; } }";
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        assert!(matches!(
+            parser.parse_program(),
+            Err(ZvarError::BreakOutsideLoop { .. })
+        ));
+    }
+
+    #[test]
+    fn test_describe_embeds_constant_values() {
+        let source = r#"
+        main {
+            int c$0 = 10;
+            int c$1 = c$0 * 2;
+            str c$2 = "items";
+            int v$0 = 0;
+            describe(v$0, "Holds up to " + c$1 + " " + c$2);
+        }
+        "#;
+        let mut symbol_table = SymbolTable::new();
+        symbol_table.set_persist_scopes(true);
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        let program = parser.parse_program().unwrap();
+
+        let Item::MainBlock(main) = &program.items[0] else {
+            panic!("Expected main block");
+        };
+        let Statement::Describe(describe) = &main.body.statements[4] else {
+            panic!("Expected describe statement");
+        };
+        assert_eq!(describe.description, "Holds up to 20 items");
+        assert_eq!(
+            symbol_table.lookup("v$0").unwrap().documentation.as_deref(),
+            Some("Holds up to 20 items")
+        );
+    }
+
+    #[test]
+    fn test_describe_rejects_variables_and_later_constants() {
+        let parse = |body: &str| {
+            let source = format!("main {{ int v$0 = 1; {} }}", body);
+            let mut symbol_table = SymbolTable::new();
+            let mut parser = Parser::new(&source, &mut symbol_table)?;
+            parser.parse_program()
+        };
+
+        assert!(matches!(
+            parse(r#"describe(v$0, "is " + v$0);"#),
+            Err(ZvarError::UnexpectedToken { .. })
+        ));
+        assert!(matches!(
+            parse(r#"describe(v$0, "is " + c$0); int c$0 = 1;"#),
+            Err(ZvarError::UndefinedEntity { .. })
+        ));
+        // Known only at runtime
+        assert!(matches!(
+            parse(r#"int c$0 = v$0 + 1; describe(v$0, "is " + c$0);"#),
+            Err(ZvarError::UnexpectedToken { .. })
+        ));
+    }
+
+    #[test]
+    fn test_function_used_as_value() {
+        let source = "fn f$0() -> int { ret 1; }\nmain { int v$0 = f$0; }";
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        let error = parser.parse_program().unwrap_err();
+
+        assert!(matches!(error, ZvarError::FunctionAsValue { ref name, .. } if name == "f$0"));
+        assert_eq!(
+            error.to_string(),
+            "Functions cannot be used as values; did you mean f$0(...)? at 2:18-20"
+        );
+    }
+
+    #[test]
+    fn test_redeclaring_a_parameter_is_an_error() {
+        // Parameters, locals and nested blocks share one scope, so shadowing
+        // is rejected outright instead of producing a warning
+        let source = r#"
+        fn f$0(v$0 int) -> int {
+            int v$0 = 2;
+            ret v$0;
+        }
+        "#;
+
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        assert!(matches!(
+            parser.parse_program(),
+            Err(ZvarError::EntityAlreadyDefined { name, previous_span: Some(_), .. }) if name == "v$0"
+        ));
+
+        let source = r#"
+        fn f$0(v$0 int) -> int {
+            int v$1 = 2;
+            ret v$0 + v$1;
+        }
+        "#;
+
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        assert!(parser.parse_program().is_ok());
+        assert!(parser.warnings().is_empty());
+    }
+
     #[test]
     fn test_parse_comparison_operators() {
         let source = r#"
@@ -1166,4 +1719,354 @@ mod tests {
         // Should parse without errors with correct precedence
         assert_eq!(program.items.len(), 1);
     }
+
+    #[test]
+    fn test_parse_conditional_expression() {
+        let source = r#"
+        main {
+            int v$1 = 5;
+            int v$0 = (v$1 > 0) ? 1 : v$1 < 0 ? 2 : 3;
+        }
+        "#;
+
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        let program = parser.parse_program().unwrap();
+
+        let Item::MainBlock(main) = &program.items[0] else {
+            panic!("Expected main block");
+        };
+        let Statement::VariableDeclaration(decl) = &main.body.statements[1] else {
+            panic!("Expected variable declaration");
+        };
+
+        // Right-associative: the else branch holds the nested conditional
+        match &decl.initializer {
+            Some(Expression::Conditional(cond)) => {
+                assert!(matches!(*cond.then_expr, Expression::Integer(_)));
+                assert!(matches!(*cond.else_expr, Expression::Conditional(_)));
+            }
+            other => panic!("Expected conditional expression, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_usage_counts() {
+        let source = r#"
+        fn f$0(v$0 int) -> int {
+            ret v$0;
+        }
+
+        main {
+            int v$0 = 1;
+            int v$1 = v$0 + v$0;
+            v$1 = f$0(v$0);
+        }
+        "#;
+
+        let mut symbol_table = SymbolTable::new();
+        symbol_table.set_record_scopes(true);
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        parser.parse_program().unwrap();
+
+        // Reads are counted per scope; assigning v$1 is not a use
+        let count = |scope: usize, name: &str| {
+            symbol_table.scopes_snapshot()[scope]
+                .symbols
+                .iter()
+                .find(|(symbol_name, _)| symbol_name == name)
+                .map(|(_, symbol)| symbol.usage_count)
+        };
+        assert_eq!(count(0, "f$0"), Some(1));
+        assert_eq!(count(1, "v$0"), Some(1));
+        assert_eq!(count(2, "v$0"), Some(3));
+        assert_eq!(count(2, "v$1"), Some(0));
+    }
+
+    fn parse_warnings(source: &str) -> Vec<Warning> {
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        parser.set_strict_semicolons(true);
+        parser.parse_program().unwrap();
+        parser.warnings().to_vec()
+    }
+
+    #[test]
+    fn test_empty_statement_warnings() {
+        let doubled = r#"
+        main {
+            int v$0 = 1;;
+            print(v$0);
+        }
+        "#;
+        assert_eq!(parse_warnings(doubled).len(), 1);
+
+        let single = r#"
+        main {
+            int v$0 = 1;
+            print(v$0);
+        }
+        "#;
+        assert!(parse_warnings(single).is_empty());
+    }
+
+    #[test]
+    fn test_empty_statement_is_allowed_without_lint() {
+        let source = r#"
+        main {
+            ;
+            print(1);;
+        }
+        "#;
+
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        let program = parser.parse_program().unwrap();
+
+        assert!(parser.warnings().is_empty());
+        if let Item::MainBlock(main) = &program.items[0] {
+            assert_eq!(main.body.statements.len(), 3);
+            assert!(matches!(main.body.statements[0], Statement::Empty(_)));
+        } else {
+            panic!("Expected main block");
+        }
+    }
+
+    #[test]
+    fn test_parse_type_alias() {
+        let source = r#"
+        /// Distance in meters
+        type Meters = int;
+
+        fn f$0(v$0 Meters) -> Meters {
+            ret v$0 * 2;
+        }
+
+        main {
+            Meters v$1 = f$0(21);
+        }
+        "#;
+
+        let mut symbol_table = SymbolTable::new();
+        symbol_table.set_persist_scopes(true);
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        let program = parser.parse_program().unwrap();
+
+        let Item::TypeAlias(alias) = &program.items[0] else {
+            panic!("Expected type alias");
+        };
+        assert_eq!(alias.name, "Meters");
+        assert_eq!(alias.target, ValueType::Int);
+        assert_eq!(alias.documentation.as_deref(), Some("Distance in meters"));
+
+        assert_eq!(
+            symbol_table.lookup("v$1").unwrap().get_type(),
+            Some(&ValueType::Int)
+        );
+    }
+
+    #[test]
+    fn test_undefined_type_alias() {
+        let source = r#"
+        main {
+            Meters v$0 = 1;
+        }
+        "#;
+
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+
+        assert!(matches!(
+            parser.parse_program(),
+            Err(ZvarError::UndefinedEntity { .. })
+        ));
+    }
+
+    #[test]
+    fn test_adjacent_string_concatenation() {
+        let source = "main {\n    str v$0 = \"Hello, \"\n        \"world\" \"!\";\n}";
+
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        let program = parser.parse_program().unwrap();
+
+        let Item::MainBlock(main) = &program.items[0] else {
+            panic!("Expected main block");
+        };
+        let Statement::VariableDeclaration(var_decl) = &main.body.statements[0] else {
+            panic!("Expected variable declaration");
+        };
+        let Some(Expression::String(string)) = &var_decl.initializer else {
+            panic!("Expected string literal");
+        };
+
+        assert_eq!(string.value, "Hello, world!");
+        assert_eq!(string.span, Span::new(2, 15, 3, 19));
+    }
+
+    #[test]
+    fn test_parse_expression_only() {
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new("1 + 2 * 3", &mut symbol_table).unwrap();
+        assert!(matches!(
+            parser.parse_expression_only(),
+            Ok(Expression::Binary(binary)) if binary.operator == BinaryOperator::Add
+        ));
+
+        // Statements are not expressions
+        for source in ["print(1);", "int v$0 = 1;", "1 + 2 }"] {
+            let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+            assert!(parser.parse_expression_only().is_err(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_parse_boolean_literal() {
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new("main { bool v$0 = false; }", &mut symbol_table).unwrap();
+        let program = parser.parse_program().unwrap();
+
+        let Item::MainBlock(main) = &program.items[0] else {
+            panic!("Expected main block");
+        };
+        let Statement::VariableDeclaration(var_decl) = &main.body.statements[0] else {
+            panic!("Expected variable declaration");
+        };
+        assert!(matches!(
+            &var_decl.initializer,
+            Some(Expression::Boolean(literal)) if !literal.value
+        ));
+    }
+
+    #[test]
+    fn test_binary_expression_spans() {
+        let source = "main {\n    int v$1 = 1;\n    int v$0 = v$1 + 20 * (3 - v$1);\n}";
+
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        let program = parser.parse_program().unwrap();
+
+        let Item::MainBlock(main) = &program.items[0] else {
+            panic!("Expected main block");
+        };
+        let Statement::VariableDeclaration(var_decl) = &main.body.statements[1] else {
+            panic!("Expected variable declaration");
+        };
+        let Some(Expression::Binary(sum)) = &var_decl.initializer else {
+            panic!("Expected binary expression");
+        };
+
+        // From the left operand's first column to the right operand's last,
+        // closing parenthesis included
+        assert_eq!(sum.span, Span::new(3, 15, 3, 34));
+        assert_eq!(sum.left.span(), Span::new(3, 15, 3, 17));
+
+        let Expression::Binary(product) = &*sum.right else {
+            panic!("Expected binary expression");
+        };
+        assert_eq!(product.span, Span::new(3, 21, 3, 34));
+        assert_eq!(product.right.span(), Span::new(3, 27, 3, 33));
+    }
+
+    #[test]
+    fn test_parse_global_variable() {
+        let source = r#"
+        /// Shared counter
+        int v$0 = 1;
+
+        main {
+            v$0 = v$0 + 1;
+        }
+        "#;
+
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        let program = parser.parse_program().unwrap();
+
+        let Item::GlobalVariable(global) = &program.items[0] else {
+            panic!("Expected global variable");
+        };
+        assert_eq!(global.name, "v$0");
+        assert_eq!(global.documentation.as_deref(), Some("Shared counter"));
+        assert!(symbol_table.is_global_variable("v$0"));
+    }
+
+    #[test]
+    fn test_global_variable_cannot_be_shadowed() {
+        let source = r#"
+        int v$0 = 1;
+
+        fn f$0(v$0 int) -> int {
+            ret v$0;
+        }
+        "#;
+
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        let result = parser.parse_program();
+
+        assert!(matches!(
+            result,
+            Err(ZvarError::EntityAlreadyDefined { ref name, .. }) if name == "v$0"
+        ));
+    }
+
+    #[test]
+    fn test_parse_match_statement() {
+        let source = r#"
+        main {
+            int v$0 = 2;
+            match (v$0) {
+                1 => { print(1); }
+                _ => { print(0); }
+            }
+        }
+        "#;
+
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        let program = parser.parse_program().unwrap();
+
+        let Item::MainBlock(main) = &program.items[0] else {
+            panic!("Expected main block");
+        };
+        let Statement::Match(match_stmt) = &main.body.statements[1] else {
+            panic!("Expected match statement");
+        };
+        let patterns: Vec<_> = match_stmt.arms.iter().map(|arm| &arm.pattern).collect();
+        assert_eq!(
+            patterns,
+            [&MatchPattern::Integer(1), &MatchPattern::Wildcard]
+        );
+    }
+
+    #[test]
+    fn test_match_wildcard_must_be_last() {
+        let source = r#"
+        main {
+            match (1) {
+                _ => { print(0); }
+                1 => { print(1); }
+            }
+        }
+        "#;
+
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        let Err(ZvarError::UnexpectedToken { span, .. }) = parser.parse_program() else {
+            panic!("Expected an error for an arm after the wildcard");
+        };
+        assert_eq!(span.start_line, 5);
+    }
+
+    #[test]
+    fn test_memo_requires_pure() {
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new("memo fn f$0() -> int { ret 1; }", &mut symbol_table).unwrap();
+
+        assert!(matches!(
+            parser.parse_program(),
+            Err(ZvarError::UnexpectedToken { ref expected, .. }) if expected == "'pure' before 'memo'"
+        ));
+    }
 }
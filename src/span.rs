@@ -1,8 +1,15 @@
 //! Source code span tracking for error reporting and debugging
 
-use std::fmt;
+use core::{
+    fmt,
+    ops::{Deref, DerefMut},
+};
 
 /// Represents a span of source code with line and column information
+///
+/// Lines and columns are 1-based, matching how editors and compilers
+/// usually report positions to users. Use [`Span::to_zero_based`] when
+/// handing positions to tools that expect 0-based coordinates (e.g. LSP).
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Span {
     pub start_line: u32,
@@ -37,6 +44,35 @@ impl Span {
         )
     }
 
+    /// Create the smallest span covering all given spans
+    ///
+    /// Spans on line 0 are placeholders and are ignored; if nothing is left
+    /// the result is the placeholder `0:0`.
+    pub fn merge(spans: &[Span]) -> Self {
+        let mut real = spans.iter().filter(|span| span.start_line != 0);
+        let Some(first) = real.next() else {
+            return Span::new(0, 0, 0, 0);
+        };
+
+        real.fold(*first, |merged, span| {
+            let (start_line, start_column) =
+                (merged.start_line, merged.start_column).min((span.start_line, span.start_column));
+            let (end_line, end_column) =
+                (merged.end_line, merged.end_column).max((span.end_line, span.end_column));
+            Span::new(start_line, start_column, end_line, end_column)
+        })
+    }
+
+    /// Convert this 1-based span into 0-based line/column coordinates
+    pub fn to_zero_based(&self) -> Span {
+        Span::new(
+            self.start_line.saturating_sub(1),
+            self.start_column.saturating_sub(1),
+            self.end_line.saturating_sub(1),
+            self.end_column.saturating_sub(1),
+        )
+    }
+
     /// Check if this span is on a single line
     pub fn is_single_line(&self) -> bool {
         self.start_line == self.end_line
@@ -67,6 +103,12 @@ impl Span {
 
         true
     }
+
+    /// Check if another span lies completely inside this one
+    pub fn contains_span(&self, other: &Span) -> bool {
+        self.contains(other.start_line, other.start_column)
+            && self.contains(other.end_line, other.end_column)
+    }
 }
 
 impl fmt::Display for Span {
@@ -91,6 +133,36 @@ impl fmt::Display for Span {
     }
 }
 
+/// A syntax node together with the source range it was parsed from
+///
+/// Derefs to the node, so its fields are reachable directly while `span`
+/// stays available on every wrapped node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(node: T, span: Span) -> Self {
+        Spanned { node, span }
+    }
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.node
+    }
+}
+
+impl<T> DerefMut for Spanned<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.node
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -130,6 +202,41 @@ mod tests {
         assert!(!span.contains(3, 10));
     }
 
+    #[test]
+    fn test_span_merge() {
+        let merged = Span::merge(&[
+            Span::new(3, 8, 3, 12),
+            Span::new(2, 10, 2, 14),
+            Span::new(0, 0, 0, 0),
+            Span::new(4, 1, 4, 3),
+        ]);
+        assert_eq!(merged, Span::new(2, 10, 4, 3));
+
+        assert_eq!(Span::merge(&[]), Span::new(0, 0, 0, 0));
+    }
+
+    #[test]
+    fn test_span_contains_span() {
+        let outer = Span::new(2, 5, 4, 10);
+        assert!(outer.contains_span(&Span::new(2, 5, 4, 10)));
+        assert!(outer.contains_span(&Span::new(3, 1, 3, 80)));
+        assert!(!outer.contains_span(&Span::new(2, 4, 3, 1)));
+        assert!(!outer.contains_span(&Span::new(3, 1, 5, 1)));
+    }
+
+    #[test]
+    fn test_spanned_derefs_to_node() {
+        let span = Span::new(2, 5, 2, 9);
+        let mut spanned = Spanned::new(String::from("v$0"), span);
+
+        assert_eq!(spanned.len(), 3);
+        assert_eq!(*spanned, "v$0");
+        assert_eq!(spanned.span, span);
+
+        spanned.push('1');
+        assert_eq!(spanned.node, "v$01");
+    }
+
     #[test]
     fn test_span_display() {
         let single = Span::new(5, 10, 5, 15);
@@ -141,4 +248,17 @@ mod tests {
         let multi = Span::new(2, 5, 4, 10);
         assert_eq!(multi.to_string(), "2:5-4:10");
     }
+
+    #[test]
+    fn test_zero_based_conversion() {
+        let span = Span::new(1, 1, 3, 7);
+        let zero_based = span.to_zero_based();
+        assert_eq!(zero_based, Span::new(0, 0, 2, 6));
+
+        // Display always reports the 1-based positions
+        assert_eq!(span.to_string(), "1:1-3:7");
+
+        // Dummy spans never underflow
+        assert_eq!(Span::new(0, 0, 0, 0).to_zero_based(), Span::new(0, 0, 0, 0));
+    }
 }
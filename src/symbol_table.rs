@@ -1,7 +1,6 @@
 //! Symbol table for tracking entities and their metadata
 
-use crate::{error::ZvarError, span::Span};
-use std::collections::HashMap;
+use crate::{error::ZvarError, prelude::*, span::Span};
 
 /// Type of entity in the symbol table
 #[derive(Debug, Clone, PartialEq)]
@@ -26,8 +25,25 @@ pub enum ValueType {
     Bool,
 }
 
-impl std::fmt::Display for ValueType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl ValueType {
+    /// Check if arithmetic (`-`, `*`, `/`, `**`) applies to this type
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, ValueType::Int)
+    }
+
+    /// Check if two values of this type can be ordered with `<`, `>`, `<=` and `>=`
+    pub fn is_comparable(&self) -> bool {
+        matches!(self, ValueType::Int | ValueType::Str | ValueType::Bool)
+    }
+
+    /// Check if a value of this type is a single value rather than a collection
+    pub fn is_scalar(&self) -> bool {
+        matches!(self, ValueType::Int | ValueType::Str | ValueType::Bool)
+    }
+}
+
+impl core::fmt::Display for ValueType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             ValueType::Int => write!(f, "int"),
             ValueType::Str => write!(f, "str"),
@@ -43,6 +59,7 @@ pub struct Symbol {
     pub definition_span: Span,
     pub documentation: Option<String>,
     pub is_initialized: bool,
+    pub usage_count: usize,
 }
 
 impl Symbol {
@@ -52,6 +69,7 @@ impl Symbol {
             definition_span,
             documentation: None,
             is_initialized: false,
+            usage_count: 0,
         }
     }
 
@@ -86,32 +104,117 @@ impl Symbol {
     }
 }
 
+/// Contents of one scope, as recorded for `info --dump-symbols`
+#[derive(Debug, Clone)]
+pub struct ScopeSnapshot {
+    /// Nesting level, 0 is the global scope
+    pub depth: usize,
+    /// Entries sorted by name
+    pub symbols: Vec<(String, Symbol)>,
+}
+
 /// Symbol table with scope management
 #[derive(Debug)]
 pub struct SymbolTable {
-    // Stack of scopes, each scope is a HashMap of entity names to symbols
-    scopes: Vec<HashMap<String, Symbol>>,
+    // Stack of scopes, each scope is a BTreeMap of entity names to symbols
+    scopes: Vec<BTreeMap<String, Symbol>>,
     // Global documentation comments waiting to be attached
     pending_docs: Vec<String>,
+    // Merge exited scopes into their parent instead of discarding them
+    persist_scopes: bool,
+    // References to entities not defined yet (e.g. calls to later functions)
+    unresolved_usages: BTreeMap<String, usize>,
+    // Type alias names mapped to the type they stand for
+    type_aliases: BTreeMap<String, (ValueType, Span)>,
+    // Variables declared at the top level, outside of main and functions
+    global_variables: BTreeSet<String>,
+    // Copies of every scope entered so far, in the order they were entered
+    recorded_scopes: Option<Vec<ScopeSnapshot>>,
+    // Indices into `recorded_scopes` of the scopes still on the stack
+    open_recorded_scopes: Vec<usize>,
 }
 
 impl SymbolTable {
     pub fn new() -> Self {
         SymbolTable {
-            scopes: vec![HashMap::new()], // Start with global scope
+            scopes: vec![BTreeMap::new()], // Start with global scope
             pending_docs: Vec::new(),
+            persist_scopes: false,
+            unresolved_usages: BTreeMap::new(),
+            type_aliases: BTreeMap::new(),
+            global_variables: BTreeSet::new(),
+            recorded_scopes: None,
+            open_recorded_scopes: Vec::new(),
+        }
+    }
+
+    /// Keep a copy of every scope when it is exited
+    ///
+    /// Scopes are popped once parsing leaves them, so this has to be turned
+    /// on before parsing for `scopes_snapshot` to see more than the global
+    /// scope.
+    pub fn set_record_scopes(&mut self, record: bool) {
+        self.recorded_scopes = record.then(Vec::new);
+        self.open_recorded_scopes.clear();
+    }
+
+    /// All scopes with their entries: the global scope first, then every
+    /// recorded scope in the order it was entered
+    pub fn scopes_snapshot(&self) -> Vec<ScopeSnapshot> {
+        let mut snapshots = vec![snapshot_of(0, &self.scopes[0])];
+
+        if let Some(recorded) = &self.recorded_scopes {
+            snapshots.extend(recorded.iter().cloned());
+
+            // Scopes that are still open have not been copied yet
+            for &index in &self.open_recorded_scopes {
+                let depth = recorded[index].depth;
+                snapshots[index + 1] = snapshot_of(depth, &self.scopes[depth]);
+            }
         }
+
+        snapshots
+    }
+
+    /// Keep the symbols of exited scopes visible in the enclosing scope
+    ///
+    /// Used by the REPL so entities declared on one line can be referenced
+    /// on the next, even though every line is parsed as its own main block.
+    pub fn set_persist_scopes(&mut self, persist: bool) {
+        self.persist_scopes = persist;
     }
 
     /// Enter a new scope
     pub fn enter_scope(&mut self) {
-        self.scopes.push(HashMap::new());
+        self.scopes.push(BTreeMap::new());
+
+        if let Some(recorded) = &mut self.recorded_scopes {
+            self.open_recorded_scopes.push(recorded.len());
+            recorded.push(ScopeSnapshot {
+                depth: self.scopes.len() - 1,
+                symbols: Vec::new(),
+            });
+        }
     }
 
     /// Exit the current scope
     pub fn exit_scope(&mut self) {
         if self.scopes.len() > 1 {
-            self.scopes.pop();
+            let scope = self.scopes.pop();
+
+            if let (Some(recorded), Some(index), Some(scope)) = (
+                &mut self.recorded_scopes,
+                self.open_recorded_scopes.pop(),
+                &scope,
+            ) {
+                recorded[index] = snapshot_of(self.scopes.len(), scope);
+            }
+
+            if self.persist_scopes {
+                if let (Some(scope), Some(parent)) = (scope, self.scopes.last_mut()) {
+                    parent.extend(scope);
+                }
+            }
         }
     }
 
@@ -144,11 +247,29 @@ impl SymbolTable {
             }
         }
 
+        // Globals are visible everywhere, so they cannot be shadowed
+        if self.scopes.len() > 1 && self.global_variables.contains(&name) {
+            return Err(ZvarError::EntityAlreadyDefined {
+                span: symbol.definition_span,
+                previous_span: self.scopes[0]
+                    .get(&name)
+                    .map(|global| global.definition_span),
+                name,
+            });
+        }
+
         // Attach any pending documentation
         if let Some(docs) = self.take_pending_docs() {
             symbol.documentation = Some(docs);
         }
 
+        // Functions can be called before they are defined
+        if symbol.is_function() {
+            if let Some(count) = self.unresolved_usages.remove(&name) {
+                symbol.usage_count += count;
+            }
+        }
+
         // Add to current scope
         if let Some(current_scope) = self.scopes.last_mut() {
             current_scope.insert(name, symbol);
@@ -157,6 +278,16 @@ impl SymbolTable {
         Ok(())
     }
 
+    /// Mark a variable defined in the global scope as a global variable
+    pub fn mark_global(&mut self, name: &str) {
+        self.global_variables.insert(name.to_string());
+    }
+
+    /// Check if a name refers to a variable declared at the top level
+    pub fn is_global_variable(&self, name: &str) -> bool {
+        self.global_variables.contains(name)
+    }
+
     /// Look up a symbol in all scopes (starting from innermost)
     pub fn lookup(&self, name: &str) -> Option<&Symbol> {
         for scope in self.scopes.iter().rev() {
@@ -177,14 +308,48 @@ impl SymbolTable {
         None
     }
 
+    /// Define a type alias
+    pub fn define_type_alias(
+        &mut self,
+        name: String,
+        value_type: ValueType,
+        span: Span,
+    ) -> Result<(), ZvarError> {
+        if let Some((_, previous_span)) = self.type_aliases.get(&name) {
+            return Err(ZvarError::EntityAlreadyDefined {
+                span,
+                name,
+                previous_span: Some(*previous_span),
+            });
+        }
+
+        self.type_aliases.insert(name, (value_type, span));
+        Ok(())
+    }
+
+    /// Resolve a type alias to the type it stands for
+    pub fn resolve_type_alias(&self, name: &str) -> Option<&ValueType> {
+        self.type_aliases
+            .get(name)
+            .map(|(value_type, _)| value_type)
+    }
+
+    /// Record a reference to an entity
+    pub fn record_usage(&mut self, name: &str) {
+        if let Some(symbol) = self.lookup_mut(name) {
+            symbol.usage_count += 1;
+        } else {
+            *self.unresolved_usages.entry(name.to_string()).or_insert(0) += 1;
+        }
+    }
+
     /// Add documentation to an existing symbol
     pub fn add_documentation(&mut self, name: &str, doc: String) -> Result<(), ZvarError> {
         if let Some(symbol) = self.lookup_mut(name) {
-            if let Some(existing_doc) = &symbol.documentation {
-                symbol.documentation = Some(format!("{}\n{}", existing_doc, doc));
-            } else {
-                symbol.documentation = Some(doc);
-            }
+            symbol.documentation = Some(crate::docs::append_description(
+                symbol.documentation.as_deref(),
+                &doc,
+            ));
             Ok(())
         } else {
             Err(ZvarError::UndefinedEntity {
@@ -220,6 +385,17 @@ impl SymbolTable {
     }
 }
 
+/// Copy a scope with its entries sorted by name
+fn snapshot_of(depth: usize, scope: &BTreeMap<String, Symbol>) -> ScopeSnapshot {
+    let mut symbols: Vec<(String, Symbol)> = scope
+        .iter()
+        .map(|(name, symbol)| (name.clone(), symbol.clone()))
+        .collect();
+    symbols.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    ScopeSnapshot { depth, symbols }
+}
+
 impl Default for SymbolTable {
     fn default() -> Self {
         Self::new()
@@ -230,6 +406,18 @@ impl Default for SymbolTable {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_value_type_categories() {
+        assert!(ValueType::Int.is_numeric());
+        assert!(!ValueType::Str.is_numeric());
+        assert!(!ValueType::Bool.is_numeric());
+
+        for value_type in [ValueType::Int, ValueType::Str, ValueType::Bool] {
+            assert!(value_type.is_comparable(), "{}", value_type);
+            assert!(value_type.is_scalar(), "{}", value_type);
+        }
+    }
+
     #[test]
     fn test_symbol_table_basic_operations() {
         let mut table = SymbolTable::new();
@@ -341,4 +529,84 @@ mod tests {
             Err(ZvarError::EntityAlreadyDefined { .. })
         ));
     }
+
+    #[test]
+    fn test_persist_scopes() {
+        let mut table = SymbolTable::new();
+        table.set_persist_scopes(true);
+        let span = Span::new(1, 1, 1, 5);
+
+        table.enter_scope();
+        let symbol = Symbol::new(
+            EntityType::Variable {
+                value_type: ValueType::Int,
+            },
+            span,
+        );
+        table.define("v$0".to_string(), symbol).unwrap();
+        table.exit_scope();
+
+        // Still visible after the scope was exited
+        assert!(table.lookup("v$0").unwrap().is_variable());
+    }
+
+    #[test]
+    fn test_scopes_snapshot() {
+        let mut table = SymbolTable::new();
+        table.set_record_scopes(true);
+        let span = Span::new(1, 1, 1, 5);
+        let variable = || {
+            Symbol::new(
+                EntityType::Variable {
+                    value_type: ValueType::Int,
+                },
+                span,
+            )
+        };
+
+        table.enter_scope();
+        table.define("v$0".to_string(), variable()).unwrap();
+        table.enter_scope();
+        table.define("v$0".to_string(), variable()).unwrap();
+        table.define("v$1".to_string(), variable()).unwrap();
+        table.exit_scope();
+        table.exit_scope();
+
+        let snapshots = table.scopes_snapshot();
+        assert_eq!(snapshots.len(), 3);
+        assert!(snapshots[0].symbols.is_empty());
+
+        // The shadowed v$0 appears once per level
+        assert_eq!(snapshots[1].depth, 1);
+        assert_eq!(snapshots[1].symbols.len(), 1);
+        assert_eq!(snapshots[1].symbols[0].0, "v$0");
+        assert_eq!(snapshots[2].depth, 2);
+        let names: Vec<&str> = snapshots[2]
+            .symbols
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, vec!["v$0", "v$1"]);
+    }
+
+    #[test]
+    fn test_usage_counting() {
+        let mut table = SymbolTable::new();
+        let span = Span::new(1, 1, 1, 5);
+
+        // A function used before its definition
+        table.record_usage("f$0");
+
+        let function = Symbol::new(
+            EntityType::Function {
+                params: vec![],
+                return_type: ValueType::Int,
+            },
+            span,
+        );
+        table.define("f$0".to_string(), function).unwrap();
+        table.record_usage("f$0");
+
+        assert_eq!(table.lookup("f$0").unwrap().usage_count, 2);
+    }
 }
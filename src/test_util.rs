@@ -0,0 +1,34 @@
+//! Helpers shared by the unit tests of several modules
+
+use crate::{codegen::instruction::Bytecode, vm::VM};
+use std::{cell::RefCell, io, rc::Rc};
+
+/// Writer whose output stays readable after the VM took ownership
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl io::Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedBuffer {
+    pub fn contents(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+/// Run bytecode and collect what it prints
+pub fn printed(bytecode: Bytecode) -> String {
+    let buffer = SharedBuffer::default();
+    let mut vm = VM::new();
+    vm.set_output_writer(buffer.clone());
+    vm.load(bytecode, None);
+    vm.run().unwrap();
+    buffer.contents()
+}
@@ -0,0 +1,729 @@
+//! Static type checking for the zvar language
+//!
+//! Runs between parsing and code generation. Inference is deliberately
+//! lenient: an expression whose type cannot be determined is treated as
+//! unknown and never produces an error on its own.
+
+use crate::{
+    error::{ZvarError, ZvarResult},
+    parser::ast::*,
+    prelude::*,
+    span::Span,
+    symbol_table::{EntityType, SymbolTable, ValueType},
+    types::operators::binary_result_type,
+    vm::builtins::Builtins,
+};
+
+/// Type checker that validates a parsed program
+pub struct TypeChecker<'a> {
+    symbol_table: &'a SymbolTable,
+    // Built-in functions, which are callable without a definition
+    builtins: Builtins,
+    // Stack of scopes mapping entity names to their declared types
+    scopes: Vec<BTreeMap<String, ValueType>>,
+    // Declared return type of the function being checked (`None` in main)
+    return_type: Option<ValueType>,
+}
+
+impl<'a> TypeChecker<'a> {
+    pub fn new(symbol_table: &'a SymbolTable) -> Self {
+        TypeChecker {
+            symbol_table,
+            builtins: Builtins::new(),
+            scopes: vec![BTreeMap::new()],
+            return_type: None,
+        }
+    }
+
+    /// Check every item of a program
+    pub fn check_program(&mut self, program: &Program) -> ZvarResult<()> {
+        for item in &program.items {
+            match item {
+                Item::Function(func) => self.check_function(func)?,
+                Item::MainBlock(main) => {
+                    self.scopes.push(BTreeMap::new());
+                    self.check_block(&main.body)?;
+                    self.scopes.pop();
+                }
+                Item::TypeAlias(_) => {
+                    // Aliases are resolved to their target type while parsing
+                }
+                Item::GlobalVariable(var_decl) => self.check_variable_declaration(var_decl)?,
+            }
+        }
+
+        super::purity::check_purity(program, self.symbol_table)
+    }
+
+    /// Check a function body with its parameters in scope
+    fn check_function(&mut self, func: &Function) -> ZvarResult<()> {
+        self.scopes.push(BTreeMap::new());
+        for param in &func.params {
+            self.declare(&param.name, param.param_type.clone());
+        }
+
+        self.return_type = Some(func.return_type.clone());
+        let result = self.check_block(&func.body);
+        self.return_type = None;
+
+        self.scopes.pop();
+        result?;
+
+        // Falling off the end returns no value, so callers would find an empty stack
+        if !always_returns(&func.body) {
+            return Err(ZvarError::MissingReturn {
+                span: func.span,
+                name: func.name.clone(),
+                return_type: func.return_type.to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Check a block of statements (blocks share the enclosing scope, as in the parser)
+    fn check_block(&mut self, block: &Block) -> ZvarResult<()> {
+        for stmt in &block.statements {
+            self.check_statement(stmt)?;
+        }
+        Ok(())
+    }
+
+    /// Check a single statement
+    fn check_statement(&mut self, stmt: &Statement) -> ZvarResult<()> {
+        match stmt {
+            Statement::VariableDeclaration(var_decl) => {
+                self.check_variable_declaration(var_decl)?
+            }
+            Statement::ConstantDeclaration(const_decl) => {
+                let found = self.infer_expression(&const_decl.initializer)?;
+                expect_type(&const_decl.value_type, found, const_decl.span)?;
+                self.declare(&const_decl.name, const_decl.value_type.clone());
+            }
+            Statement::Assignment(assignment) => {
+                let found = self.infer_expression(&assignment.value)?;
+
+                // A variable keeps the type it was declared with
+                if let Some(expected) = self.lookup(&assignment.target) {
+                    expect_type(&expected, found, assignment.span)?;
+                }
+            }
+            Statement::ExpressionStatement(expr) => {
+                self.infer_expression(expr)?;
+            }
+            Statement::Return(ret) => {
+                let found = match &ret.value {
+                    Some(value) => self.infer_expression(value)?,
+                    None => None,
+                };
+
+                // There is no `void`: every function declares a value type, so
+                // a bare `ret;` is only allowed in main
+                if let Some(expected) = &self.return_type {
+                    let found = match (&ret.value, found) {
+                        (None, _) => Some("no value".to_string()),
+                        (Some(_), Some(found)) if &found != expected => Some(found.to_string()),
+                        _ => None,
+                    };
+
+                    if let Some(found) = found {
+                        return Err(ZvarError::TypeMismatch {
+                            span: ret.span,
+                            expected: expected.to_string(),
+                            found,
+                        });
+                    }
+                }
+            }
+            Statement::Describe(_) | Statement::Break(_) | Statement::Empty(_) => {
+                // Nothing to check in describe, break or empty statements
+            }
+            Statement::If(if_stmt) => {
+                self.infer_expression(&if_stmt.condition)?;
+                self.check_block(&if_stmt.then_block)?;
+                if let Some(else_block) = &if_stmt.else_block {
+                    self.check_block(else_block)?;
+                }
+            }
+            Statement::While(while_stmt) => {
+                self.infer_expression(&while_stmt.condition)?;
+                self.check_block(&while_stmt.body)?;
+                if let Some(else_block) = &while_stmt.else_block {
+                    self.check_block(else_block)?;
+                }
+            }
+            Statement::Match(match_stmt) => {
+                // Patterns are integer literals, so only an int can match them
+                if let Some(found) = self.infer_expression(&match_stmt.scrutinee)? {
+                    if found != ValueType::Int {
+                        return Err(ZvarError::TypeMismatch {
+                            span: match_stmt.scrutinee.span(),
+                            expected: ValueType::Int.to_string(),
+                            found: found.to_string(),
+                        });
+                    }
+                }
+                for arm in &match_stmt.arms {
+                    self.check_block(&arm.body)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check a variable initializer and bring the variable into scope
+    fn check_variable_declaration(&mut self, var_decl: &VariableDeclaration) -> ZvarResult<()> {
+        if let Some(init) = &var_decl.initializer {
+            let found = self.infer_expression(init)?;
+            expect_type(&var_decl.value_type, found, var_decl.span)?;
+        }
+        self.declare(&var_decl.name, var_decl.value_type.clone());
+        Ok(())
+    }
+
+    /// Infer the type of an expression, checking its subexpressions
+    ///
+    /// Returns `None` when the type cannot be determined statically.
+    pub fn infer_expression(&self, expr: &Expression) -> ZvarResult<Option<ValueType>> {
+        let value_type = match expr {
+            Expression::Integer(_) => Some(ValueType::Int),
+            Expression::String(_) => Some(ValueType::Str),
+            Expression::Boolean(_) => Some(ValueType::Bool),
+            Expression::Variable(var) => self.lookup(&var.name),
+            Expression::Binary(binary) => {
+                let left = self.infer_expression(&binary.left)?;
+                let right = self.infer_expression(&binary.right)?;
+
+                match (left, right) {
+                    // The operator does not apply to the operand types
+                    (Some(left), Some(right)) => Some(
+                        binary_result_type(&binary.operator, left.clone(), right.clone())
+                            .ok_or_else(|| ZvarError::TypeMismatch {
+                                span: binary.right.span(),
+                                expected: if left == right {
+                                    format!("operands supported by {}", binary.operator)
+                                } else {
+                                    left.to_string()
+                                },
+                                found: right.to_string(),
+                            })?,
+                    ),
+                    // Comparisons produce a boolean whatever their operands
+                    _ => match binary.operator {
+                        BinaryOperator::Add
+                        | BinaryOperator::Subtract
+                        | BinaryOperator::Multiply
+                        | BinaryOperator::Divide
+                        | BinaryOperator::Power => None,
+                        _ => Some(ValueType::Bool),
+                    },
+                }
+            }
+            Expression::Logical(logical) => {
+                self.expect_bool(&logical.left)?;
+                self.expect_bool(&logical.right)?;
+                Some(ValueType::Bool)
+            }
+            Expression::Unary(unary) => {
+                self.expect_bool(&unary.operand)?;
+                match unary.operator {
+                    UnaryOperator::Not => Some(ValueType::Bool),
+                }
+            }
+            Expression::FunctionCall(call) => {
+                let mut arg_types = Vec::with_capacity(call.arguments.len());
+                for arg in &call.arguments {
+                    arg_types.push(self.infer_expression(arg)?);
+                }
+
+                if let Some(params) = self.builtins.params(&call.name) {
+                    if arg_types.len() != params.len() {
+                        return Err(ZvarError::WrongArgumentCount {
+                            span: call.span,
+                            name: call.name.clone(),
+                            expected: params.len(),
+                            found: arg_types.len(),
+                        });
+                    }
+
+                    // Parameters without a type, like print's, accept anything
+                    for ((expected, found), arg) in
+                        params.iter().zip(arg_types).zip(&call.arguments)
+                    {
+                        if let Some(expected) = expected {
+                            expect_type(expected, found, arg.span())?;
+                        }
+                    }
+                    self.builtins.result(&call.name).flatten()
+                } else {
+                    // Every function is defined once parsing is done, so an
+                    // unknown name can be reported before anything runs
+                    let symbol = self
+                        .symbol_table
+                        .lookup(&call.name)
+                        .filter(|symbol| symbol.is_function())
+                        .ok_or_else(|| ZvarError::UndefinedEntity {
+                            span: call.span,
+                            name: call.name.clone(),
+                        })?;
+
+                    if let EntityType::Function { params, .. } = &symbol.entity_type {
+                        if params.len() != call.arguments.len() {
+                            return Err(ZvarError::WrongArgumentCount {
+                                span: call.span,
+                                name: call.name.clone(),
+                                expected: params.len(),
+                                found: call.arguments.len(),
+                            });
+                        }
+
+                        let params_and_args = params.iter().zip(&arg_types).zip(&call.arguments);
+                        for (position, ((expected, found), arg)) in params_and_args.enumerate() {
+                            if let Some(found) = found {
+                                if found != expected {
+                                    return Err(ZvarError::TypeMismatch {
+                                        span: arg.span(),
+                                        expected: format!(
+                                            "{} (parameter {} of {})",
+                                            expected,
+                                            position + 1,
+                                            call.name
+                                        ),
+                                        found: found.to_string(),
+                                    });
+                                }
+                            }
+                        }
+                    }
+
+                    symbol.get_type().cloned()
+                }
+            }
+            Expression::Assignment(assign) => {
+                let found = self.infer_expression(&assign.value)?;
+
+                // Same rule as an assignment statement; the result is the stored value
+                let expected = self.lookup(&assign.target);
+                if let (Some(expected), Some(found)) = (&expected, &found) {
+                    if expected != found {
+                        return Err(ZvarError::TypeMismatch {
+                            span: assign.span,
+                            expected: expected.to_string(),
+                            found: found.to_string(),
+                        });
+                    }
+                }
+
+                expected.or(found)
+            }
+            Expression::Conditional(cond) => {
+                self.infer_expression(&cond.condition)?;
+                let then_type = self.infer_expression(&cond.then_expr)?;
+                let else_type = self.infer_expression(&cond.else_expr)?;
+
+                // Both branches must produce the same type
+                if let (Some(expected), Some(found)) = (&then_type, &else_type) {
+                    if expected != found {
+                        return Err(ZvarError::TypeMismatch {
+                            span: cond.else_expr.span(),
+                            expected: expected.to_string(),
+                            found: found.to_string(),
+                        });
+                    }
+                }
+
+                then_type.or(else_type)
+            }
+        };
+
+        Ok(value_type)
+    }
+
+    /// Check an operand of `&&`, `||` or `!`, which must be a boolean if its type is known
+    fn expect_bool(&self, expr: &Expression) -> ZvarResult<()> {
+        match self.infer_expression(expr)? {
+            Some(found) if found != ValueType::Bool => Err(ZvarError::TypeMismatch {
+                span: expr.span(),
+                expected: ValueType::Bool.to_string(),
+                found: found.to_string(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Record the declared type of an entity in the current scope
+    fn declare(&mut self, name: &str, value_type: ValueType) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), value_type);
+        }
+    }
+
+    /// Look up the declared type of an entity, innermost scope first
+    fn lookup(&self, name: &str) -> Option<ValueType> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(value_type) = scope.get(name) {
+                return Some(value_type.clone());
+            }
+        }
+
+        // Fall back to entities that outlive their scope (e.g. REPL globals)
+        self.symbol_table
+            .lookup(name)
+            .filter(|symbol| !symbol.is_function())
+            .and_then(|symbol| symbol.get_type().cloned())
+    }
+}
+
+/// Check that a value of type `found`, if known, can be stored as `expected`
+fn expect_type(expected: &ValueType, found: Option<ValueType>, span: Span) -> ZvarResult<()> {
+    match found {
+        Some(found) if found != *expected => Err(ZvarError::TypeMismatch {
+            span,
+            expected: expected.to_string(),
+            found: found.to_string(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Check whether every path through a block ends in a return statement
+///
+/// A `while` never counts, since its condition may be false from the start
+/// and the checker does not evaluate it.
+fn always_returns(block: &Block) -> bool {
+    block.statements.iter().any(|stmt| match stmt {
+        Statement::Return(_) => true,
+        Statement::If(if_stmt) => {
+            always_returns(&if_stmt.then_block)
+                && if_stmt.else_block.as_ref().is_some_and(always_returns)
+        }
+        Statement::Match(match_stmt) => {
+            match_stmt
+                .arms
+                .iter()
+                .any(|arm| arm.pattern == MatchPattern::Wildcard)
+                && match_stmt.arms.iter().all(|arm| always_returns(&arm.body))
+        }
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn check(source: &str) -> ZvarResult<()> {
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new(source, &mut symbol_table)?;
+        let program = parser.parse_program()?;
+
+        TypeChecker::new(&symbol_table).check_program(&program)
+    }
+
+    #[test]
+    fn test_conditional_branches_share_type() {
+        let source = r#"
+        main {
+            int v$1 = 3;
+            int v$0 = (v$1 > 0) ? 1 : v$1;
+        }
+        "#;
+
+        assert!(check(source).is_ok());
+    }
+
+    #[test]
+    fn test_conditional_branch_type_mismatch() {
+        let source = r#"
+        main {
+            int v$0 = true ? 1 : "one";
+        }
+        "#;
+
+        match check(source) {
+            Err(ZvarError::TypeMismatch {
+                expected, found, ..
+            }) => {
+                assert_eq!(expected, "int");
+                assert_eq!(found, "str");
+            }
+            other => panic!("Expected type mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_undefined_function_call() {
+        let source = r#"
+        fn f$0() -> int {
+            ret 1;
+        }
+
+        main {
+            print(f$0());
+            print(f$3());
+        }
+        "#;
+
+        match check(source) {
+            Err(ZvarError::UndefinedEntity { name, span }) => {
+                assert_eq!(name, "f$3");
+                assert_eq!(span.start_line, 8);
+            }
+            other => panic!("Expected undefined entity, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_wrong_argument_count() {
+        // f$0 is declared after main, so the check must run once parsing is done
+        let program = |call: &str| {
+            format!(
+                "main {{ print({}); }}\nfn f$0(v$0 int, v$1 int) -> int {{ ret v$0 + v$1; }}",
+                call
+            )
+        };
+
+        assert!(check(&program("f$0(1, 2)")).is_ok());
+
+        for (call, found) in [("f$0(1)", 1), ("f$0(1, 2, 3)", 3)] {
+            match check(&program(call)) {
+                Err(ZvarError::WrongArgumentCount {
+                    name,
+                    expected,
+                    found: actual,
+                    ..
+                }) => {
+                    assert_eq!(name, "f$0");
+                    assert_eq!(expected, 2);
+                    assert_eq!(actual, found);
+                }
+                other => panic!("Expected wrong argument count, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_argument_type_mismatch() {
+        let program = |call: &str| {
+            format!(
+                "fn f$0(v$0 int, v$1 str) -> int {{ ret v$0; }}\nmain {{ print({}); }}",
+                call
+            )
+        };
+
+        assert!(check(&program(r#"f$0(1, "one")"#)).is_ok());
+
+        match check(&program(r#"f$0("one", "two")"#)) {
+            Err(ZvarError::TypeMismatch {
+                expected, found, ..
+            }) => {
+                assert_eq!(expected, "int (parameter 1 of f$0)");
+                assert_eq!(found, "str");
+            }
+            other => panic!("Expected type mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_return_type_matches_declaration() {
+        let source = r#"
+        fn f$0(v$0 int) -> int {
+            ret v$0 + 1;
+        }
+
+        fn f$1(v$0 int) -> bool {
+            ret v$0 > 0;
+        }
+
+        main {
+            print(f$0(1));
+        }
+        "#;
+
+        assert!(check(source).is_ok());
+    }
+
+    #[test]
+    fn test_return_type_mismatch() {
+        let source = r#"
+        fn f$0() -> int {
+            ret "hello";
+        }
+
+        main {
+            print(f$0());
+        }
+        "#;
+
+        match check(source) {
+            Err(ZvarError::TypeMismatch {
+                expected, found, ..
+            }) => {
+                assert_eq!(expected, "int");
+                assert_eq!(found, "str");
+            }
+            other => panic!("Expected type mismatch, got {:?}", other),
+        }
+
+        let bare = "fn f$0() -> int { ret; }\nmain { print(f$0()); }";
+        assert!(matches!(
+            check(bare),
+            Err(ZvarError::TypeMismatch { found, .. }) if found == "no value"
+        ));
+    }
+
+    #[test]
+    fn test_assignment_keeps_declared_type() {
+        let source = r#"
+        main {
+            int v$0 = 1;
+            v$0 = "hello";
+        }
+        "#;
+
+        match check(source) {
+            Err(ZvarError::TypeMismatch {
+                span,
+                expected,
+                found,
+            }) => {
+                assert_eq!(expected, "int");
+                assert_eq!(found, "str");
+                assert_eq!(span.start_line, 4);
+            }
+            other => panic!("Expected type mismatch, got {:?}", other),
+        }
+
+        assert!(check("main { int v$0 = 1; v$0 = v$0 + 2; }").is_ok());
+
+        // Initializers must match the declared type as well
+        for source in [
+            r#"main { int v$0 = "x"; }"#,
+            r#"main { str c$0 = 1; }"#,
+            r#"bool v$9 = 1; main { }"#,
+        ] {
+            assert!(
+                matches!(check(source), Err(ZvarError::TypeMismatch { .. })),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_string_builtin_arguments() {
+        assert!(check(r#"main { bool v$0 = eq_ignore_case("Hello", "hello"); }"#).is_ok());
+        assert!(matches!(
+            check(r#"main { print(eq_ignore_case("Hello")); }"#),
+            Err(ZvarError::WrongArgumentCount { expected: 2, .. })
+        ));
+        assert!(matches!(
+            check(r#"main { print(eq_ignore_case("1", 1)); }"#),
+            Err(ZvarError::TypeMismatch { found, .. }) if found == "int"
+        ));
+        assert!(check(r#"main { str v$0 = to_upper("a"); }"#).is_ok());
+        assert!(matches!(
+            check("main { print(to_lower(1)); }"),
+            Err(ZvarError::TypeMismatch { found, .. }) if found == "int"
+        ));
+    }
+
+    #[test]
+    fn test_ordering_needs_matching_types() {
+        assert!(check(r#"main { print("a" < "b"); print(1 >= 2); }"#).is_ok());
+        assert!(matches!(
+            check(r#"main { print(1 < "2"); }"#),
+            Err(ZvarError::TypeMismatch { expected, found, .. }) if expected == "int" && found == "str"
+        ));
+
+        // Equality is defined between any two values
+        assert!(check(r#"main { print(1 == "1"); }"#).is_ok());
+    }
+
+    #[test]
+    fn test_logical_operands_must_be_bool() {
+        assert!(check("main { bool v$0 = true; print(!v$0 && (1 < 2) || false); }").is_ok());
+
+        for source in [
+            "main { print(0 && 1); }",
+            "main { bool v$0 = true || 5; }",
+            r#"main { if ("x" || false) { print(1); } }"#,
+            r#"main { str v$0 = "a"; print(!v$0); }"#,
+        ] {
+            assert!(
+                matches!(
+                    check(source),
+                    Err(ZvarError::TypeMismatch { ref expected, .. }) if expected == "bool"
+                ),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_arithmetic_needs_matching_types() {
+        assert!(check(r#"main { print(1 + 2 * 3); print("a" + "b"); }"#).is_ok());
+
+        for source in [
+            r#"main { int v$0 = 1 * "a"; }"#,
+            r#"main { print((1 + 2) * "a"); }"#,
+            r#"main { print("a" + 1); }"#,
+        ] {
+            assert!(
+                matches!(check(source), Err(ZvarError::TypeMismatch { .. })),
+                "{}",
+                source
+            );
+        }
+
+        // Both operands have the right type, but `-` does not apply to strings
+        assert!(matches!(
+            check(r#"main { print("a" - "b"); }"#),
+            Err(ZvarError::TypeMismatch { expected, .. }) if expected == "operands supported by -"
+        ));
+    }
+
+    #[test]
+    fn test_missing_return() {
+        let source = "fn f$0() -> int { }\nmain { print(f$0()); }";
+
+        let err = check(source).unwrap_err();
+        assert!(matches!(
+            &err,
+            ZvarError::MissingReturn { name, return_type, .. }
+                if name == "f$0" && return_type == "int"
+        ));
+        assert!(err
+            .to_string()
+            .starts_with("Function 'f$0' must return int but can reach its end"));
+    }
+
+    #[test]
+    fn test_conditional_return_is_not_enough() {
+        let missing = [
+            "fn f$0(v$0 int) -> int { while (v$0 > 0) { ret 1; } }",
+            "fn f$0(v$0 int) -> int { if (v$0 > 0) { ret 1; } }",
+            "fn f$0(v$0 int) -> int { if (v$0 > 0) { ret 1; } else { print(v$0); } }",
+            "fn f$0(v$0 int) -> int { match (v$0) { 1 => { ret 1; } 2 => { ret 2; } } }",
+            "fn f$0(v$0 int) -> int { match (v$0) { 1 => { ret 1; } _ => { } } }",
+        ];
+        for source in missing {
+            assert!(
+                matches!(check(source), Err(ZvarError::MissingReturn { .. })),
+                "{}",
+                source
+            );
+        }
+
+        let complete = [
+            "fn f$0(v$0 int) -> int { while (v$0 > 0) { ret 1; } ret 0; }",
+            "fn f$0(v$0 int) -> int { if (v$0 > 0) { ret 1; } else { ret 0; } }",
+            "fn f$0(v$0 int) -> int { match (v$0) { 1 => { ret 1; } _ => { ret 0; } } }",
+        ];
+        for source in complete {
+            assert!(check(source).is_ok(), "{}", source);
+        }
+    }
+}
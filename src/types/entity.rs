@@ -1,7 +1,7 @@
 //! Entity type definitions for the zvar language
 
-use crate::span::Span;
-use std::fmt;
+use crate::{prelude::*, span::Span};
+use core::fmt;
 
 /// Entity types in the zvar language
 #[derive(Debug, Clone, PartialEq)]
@@ -1,5 +1,10 @@
 //! Type system for the zvar language
 
+pub mod checker;
 pub mod entity;
+pub mod operators;
+pub mod purity;
 
+pub use checker::TypeChecker;
 pub use entity::{EntityKind, EntityRef};
+pub use operators::binary_result_type;
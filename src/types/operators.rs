@@ -0,0 +1,115 @@
+//! Result types of operators
+//!
+//! The single source of truth for which operand types an operator accepts
+//! and what it produces, shared by the type checker and code generation.
+
+use crate::{parser::ast::BinaryOperator, symbol_table::ValueType};
+
+/// Type produced by a binary operator, or `None` if it does not apply to
+/// the operand types
+///
+/// `+` adds integers and joins strings, the other arithmetic operators take
+/// integers, ordering needs two operands of the same comparable type and
+/// `==`/`!=` compare values of any types.
+pub fn binary_result_type(
+    op: &BinaryOperator,
+    left: ValueType,
+    right: ValueType,
+) -> Option<ValueType> {
+    match op {
+        BinaryOperator::Add => Some(left)
+            .filter(|left| *left == right && (left.is_numeric() || *left == ValueType::Str)),
+        BinaryOperator::Subtract
+        | BinaryOperator::Multiply
+        | BinaryOperator::Divide
+        | BinaryOperator::Power => Some(left).filter(|left| *left == right && left.is_numeric()),
+        BinaryOperator::Less
+        | BinaryOperator::Greater
+        | BinaryOperator::LessEqual
+        | BinaryOperator::GreaterEqual => {
+            (left == right && left.is_comparable()).then_some(ValueType::Bool)
+        }
+        BinaryOperator::Equal | BinaryOperator::NotEqual => Some(ValueType::Bool),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TYPES: [ValueType; 3] = [ValueType::Int, ValueType::Str, ValueType::Bool];
+
+    #[test]
+    fn test_arithmetic() {
+        use BinaryOperator::*;
+
+        for op in [Add, Subtract, Multiply, Divide, Power] {
+            assert_eq!(
+                binary_result_type(&op, ValueType::Int, ValueType::Int),
+                Some(ValueType::Int)
+            );
+            assert_eq!(
+                binary_result_type(&op, ValueType::Bool, ValueType::Bool),
+                None
+            );
+        }
+
+        assert_eq!(
+            binary_result_type(&Add, ValueType::Str, ValueType::Str),
+            Some(ValueType::Str)
+        );
+        for op in [Subtract, Multiply, Divide, Power] {
+            assert_eq!(
+                binary_result_type(&op, ValueType::Str, ValueType::Str),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn test_comparison() {
+        use BinaryOperator::*;
+
+        for op in [Less, Greater, LessEqual, GreaterEqual, Equal, NotEqual] {
+            for value_type in TYPES {
+                assert_eq!(
+                    binary_result_type(&op, value_type.clone(), value_type),
+                    Some(ValueType::Bool)
+                );
+            }
+        }
+
+        // Equality also compares values of different types
+        for op in [Equal, NotEqual] {
+            assert_eq!(
+                binary_result_type(&op, ValueType::Int, ValueType::Str),
+                Some(ValueType::Bool)
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_combinations() {
+        use BinaryOperator::*;
+
+        for op in [
+            Add,
+            Subtract,
+            Multiply,
+            Divide,
+            Power,
+            Less,
+            Greater,
+            LessEqual,
+            GreaterEqual,
+        ] {
+            for left in TYPES {
+                for right in TYPES {
+                    if left != right {
+                        assert_eq!(binary_result_type(&op, left.clone(), right), None);
+                    }
+                }
+            }
+        }
+    }
+}
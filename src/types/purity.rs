@@ -0,0 +1,259 @@
+//! Verification of functions declared `pure`
+//!
+//! A pure function may not print, read or assign globals or call functions
+//! that are not pure themselves, so its result depends only on its arguments.
+//! That makes pure functions safe candidates for compile-time evaluation
+//! and memoization.
+
+use crate::{
+    error::{ZvarError, ZvarResult},
+    parser::ast::*,
+    prelude::*,
+    span::Span,
+    symbol_table::SymbolTable,
+};
+
+/// Check the body of every `pure` function in a program
+pub fn check_purity(program: &Program, symbol_table: &SymbolTable) -> ZvarResult<()> {
+    let pure_functions: BTreeSet<&str> = program
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Function(func) if func.is_pure => Some(func.name.as_str()),
+            _ => None,
+        })
+        // Builtins without side effects
+        .chain([
+            "eq_ignore_case",
+            "to_upper",
+            "to_lower",
+            "trim",
+            "len",
+            "byte_len",
+        ])
+        .collect();
+
+    for item in &program.items {
+        if let Item::Function(func) = item {
+            if func.is_pure {
+                let verifier = PurityVerifier {
+                    function: &func.name,
+                    pure_functions: &pure_functions,
+                    symbol_table,
+                };
+                verifier.block(&func.body)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+struct PurityVerifier<'a> {
+    function: &'a str,
+    pure_functions: &'a BTreeSet<&'a str>,
+    symbol_table: &'a SymbolTable,
+}
+
+impl PurityVerifier<'_> {
+    fn violation(&self, span: Span, reason: String) -> ZvarError {
+        ZvarError::ImpureFunction {
+            span,
+            name: self.function.to_string(),
+            reason,
+        }
+    }
+
+    fn block(&self, block: &Block) -> ZvarResult<()> {
+        for stmt in &block.statements {
+            self.statement(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn statement(&self, stmt: &Statement) -> ZvarResult<()> {
+        match stmt {
+            Statement::VariableDeclaration(var_decl) => {
+                if let Some(init) = &var_decl.initializer {
+                    self.expression(init)?;
+                }
+            }
+            Statement::ConstantDeclaration(const_decl) => {
+                self.expression(&const_decl.initializer)?;
+            }
+            Statement::Assignment(assignment) => {
+                if self.symbol_table.is_global_variable(&assignment.target) {
+                    return Err(self.violation(
+                        assignment.span,
+                        format!("assigns global {}", assignment.target),
+                    ));
+                }
+                self.expression(&assignment.value)?;
+            }
+            Statement::ExpressionStatement(expr) => self.expression(expr)?,
+            Statement::Return(ret) => {
+                if let Some(value) = &ret.value {
+                    self.expression(value)?;
+                }
+            }
+            Statement::If(if_stmt) => {
+                self.expression(&if_stmt.condition)?;
+                self.block(&if_stmt.then_block)?;
+                if let Some(else_block) = &if_stmt.else_block {
+                    self.block(else_block)?;
+                }
+            }
+            Statement::While(while_stmt) => {
+                self.expression(&while_stmt.condition)?;
+                self.block(&while_stmt.body)?;
+                if let Some(else_block) = &while_stmt.else_block {
+                    self.block(else_block)?;
+                }
+            }
+            Statement::Match(match_stmt) => {
+                self.expression(&match_stmt.scrutinee)?;
+                for arm in &match_stmt.arms {
+                    self.block(&arm.body)?;
+                }
+            }
+            Statement::Describe(_) | Statement::Break(_) | Statement::Empty(_) => {}
+        }
+        Ok(())
+    }
+
+    fn expression(&self, expr: &Expression) -> ZvarResult<()> {
+        match expr {
+            Expression::FunctionCall(call) => {
+                // Most builtins perform I/O, user functions must be pure as well
+                if !self.pure_functions.contains(call.name.as_str()) {
+                    let reason = if call.name.starts_with("f$") {
+                        format!("calls impure function {}", call.name)
+                    } else {
+                        format!("calls {}", call.name)
+                    };
+                    return Err(self.violation(call.span, reason));
+                }
+                for arg in &call.arguments {
+                    self.expression(arg)?;
+                }
+            }
+            Expression::Binary(binary) => {
+                self.expression(&binary.left)?;
+                self.expression(&binary.right)?;
+            }
+            Expression::Logical(logical) => {
+                self.expression(&logical.left)?;
+                self.expression(&logical.right)?;
+            }
+            Expression::Unary(unary) => self.expression(&unary.operand)?,
+            Expression::Conditional(cond) => {
+                self.expression(&cond.condition)?;
+                self.expression(&cond.then_expr)?;
+                self.expression(&cond.else_expr)?;
+            }
+            Expression::Assignment(assign) => {
+                if self.symbol_table.is_global_variable(&assign.target) {
+                    return Err(
+                        self.violation(assign.span, format!("assigns global {}", assign.target))
+                    );
+                }
+                self.expression(&assign.value)?;
+            }
+            // A global can change between calls with the same arguments
+            Expression::Variable(var) if self.symbol_table.is_global_variable(&var.name) => {
+                return Err(self.violation(var.span, format!("reads global {}", var.name)));
+            }
+            Expression::Integer(_)
+            | Expression::String(_)
+            | Expression::Boolean(_)
+            | Expression::Variable(_) => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn check(source: &str) -> ZvarResult<()> {
+        let mut symbol_table = SymbolTable::new();
+        let mut parser = Parser::new(source, &mut symbol_table).unwrap();
+        let program = parser.parse_program().unwrap();
+        check_purity(&program, &symbol_table)
+    }
+
+    #[test]
+    fn test_pure_function() {
+        let source = r#"
+        pure fn f$0(v$0 int) -> int {
+            ret v$0 * v$0;
+        }
+
+        pure fn f$1(v$0 int) -> int {
+            ret f$0(v$0) + 1;
+        }
+
+        main {
+            print(f$1(3));
+        }
+        "#;
+
+        assert!(check(source).is_ok());
+    }
+
+    #[test]
+    fn test_pure_function_calling_print() {
+        let source = r#"
+        pure fn f$0(v$0 int) -> int {
+            print(v$0);
+            ret v$0;
+        }
+
+        main {
+            print(f$0(3));
+        }
+        "#;
+
+        let Err(ZvarError::ImpureFunction { span, name, reason }) = check(source) else {
+            panic!("Expected an impure function error");
+        };
+        assert_eq!(name, "f$0");
+        assert_eq!(reason, "calls print");
+        assert_eq!(span.start_line, 3);
+    }
+
+    #[test]
+    fn test_pure_function_writing_global() {
+        let source = r#"
+        int v$9 = 0;
+
+        pure fn f$0(v$0 int) -> int {
+            v$9 = v$0;
+            ret v$0;
+        }
+        "#;
+
+        assert!(matches!(
+            check(source),
+            Err(ZvarError::ImpureFunction { ref reason, .. }) if reason == "assigns global v$9"
+        ));
+    }
+
+    #[test]
+    fn test_pure_function_reading_global() {
+        let source = r#"
+        int v$9 = 1;
+
+        pure fn f$0(v$0 int) -> int {
+            ret v$0 + v$9;
+        }
+        "#;
+
+        assert!(matches!(
+            check(source),
+            Err(ZvarError::ImpureFunction { ref reason, .. }) if reason == "reads global v$9"
+        ));
+    }
+}
@@ -2,46 +2,113 @@
 
 use crate::{
     error::{ZvarError, ZvarResult},
+    prelude::*,
+    symbol_table::ValueType,
     vm::{stack::Stack, value::Value},
 };
-use std::collections::HashMap;
 
 /// Type for built-in function implementations
 pub type BuiltinFn = fn(&mut Stack) -> ZvarResult<()>;
 
-/// Registry of built-in functions
+/// A registered built-in function and its signature
+///
+/// A parameter of `None` accepts any type. `func` is `None` for builtins
+/// that compile to their own instruction instead of a call.
+#[derive(Debug, Clone)]
+struct Builtin {
+    params: Vec<Option<ValueType>>,
+    result: Option<ValueType>,
+    func: Option<BuiltinFn>,
+}
+
+/// Registry of built-in functions with the types of their arguments
+/// and of the result they push, if any
 #[derive(Debug)]
 pub struct Builtins {
-    functions: HashMap<String, BuiltinFn>,
+    functions: BTreeMap<String, Builtin>,
 }
 
+/// Parameter or result type of the string builtins
+const STR: Option<ValueType> = Some(ValueType::Str);
+
 impl Builtins {
     /// Create new builtins registry with default functions
     pub fn new() -> Self {
         let mut builtins = Builtins {
-            functions: HashMap::new(),
+            functions: BTreeMap::new(),
         };
 
         // Register built-in functions
-        builtins.register("print".to_string(), builtin_print);
+        builtins.register("print".to_string(), vec![None], None, builtin_print);
+        builtins.register(
+            "eq_ignore_case".to_string(),
+            vec![STR, STR],
+            Some(ValueType::Bool),
+            builtin_eq_ignore_case,
+        );
+        builtins.register("to_upper".to_string(), vec![STR], STR, builtin_to_upper);
+        builtins.register("to_lower".to_string(), vec![STR], STR, builtin_to_lower);
+        builtins.register("trim".to_string(), vec![STR], STR, builtin_trim);
+        builtins.register(
+            "len".to_string(),
+            vec![STR],
+            Some(ValueType::Int),
+            builtin_len,
+        );
+        builtins.register(
+            "byte_len".to_string(),
+            vec![STR],
+            Some(ValueType::Int),
+            builtin_byte_len,
+        );
+
+        // random needs the VM's generator state and compiles to RANDOM
+        builtins.functions.insert(
+            "random".to_string(),
+            Builtin {
+                params: vec![Some(ValueType::Int)],
+                result: Some(ValueType::Int),
+                func: None,
+            },
+        );
 
         builtins
     }
 
     /// Register a built-in function
-    pub fn register(&mut self, name: String, func: BuiltinFn) {
-        self.functions.insert(name, func);
+    ///
+    /// A parameter type of `None` accepts any value. `result` is the type
+    /// the function leaves on the stack after consuming its arguments, or
+    /// `None` if it leaves nothing.
+    pub fn register(
+        &mut self,
+        name: String,
+        params: Vec<Option<ValueType>>,
+        result: Option<ValueType>,
+        func: BuiltinFn,
+    ) {
+        self.functions.insert(
+            name,
+            Builtin {
+                params,
+                result,
+                func: Some(func),
+            },
+        );
     }
 
     /// Call a built-in function
     pub fn call(&self, name: &str, stack: &mut Stack) -> ZvarResult<()> {
-        if let Some(&func) = self.functions.get(name) {
-            func(stack)
-        } else {
-            Err(ZvarError::runtime(format!(
+        match self.functions.get(name).map(|builtin| builtin.func) {
+            Some(Some(func)) => func(stack),
+            Some(None) => Err(ZvarError::runtime(format!(
+                "Built-in function {} has its own instruction and cannot be called",
+                name
+            ))),
+            None => Err(ZvarError::runtime(format!(
                 "Unknown built-in function: {}",
                 name
-            )))
+            ))),
         }
     }
 
@@ -50,6 +117,30 @@ impl Builtins {
         self.functions.contains_key(name)
     }
 
+    /// Number of arguments a built-in function takes
+    pub fn arity(&self, name: &str) -> Option<usize> {
+        self.functions.get(name).map(|builtin| builtin.params.len())
+    }
+
+    /// Parameter types of a built-in function, `None` accepting any type
+    pub fn params(&self, name: &str) -> Option<&[Option<ValueType>]> {
+        self.functions
+            .get(name)
+            .map(|builtin| builtin.params.as_slice())
+    }
+
+    /// Type of the result a built-in function pushes, if it pushes one
+    pub fn result(&self, name: &str) -> Option<Option<ValueType>> {
+        self.functions
+            .get(name)
+            .map(|builtin| builtin.result.clone())
+    }
+
+    /// Whether a built-in function pushes a result
+    pub fn returns_value(&self, name: &str) -> Option<bool> {
+        self.result(name).map(|result| result.is_some())
+    }
+
     /// Get list of all built-in function names
     pub fn function_names(&self) -> Vec<&String> {
         self.functions.keys().collect()
@@ -73,6 +164,57 @@ fn builtin_print(stack: &mut Stack) -> ZvarResult<()> {
     Ok(())
 }
 
+/// Built-in eq_ignore_case function
+/// Pops two strings and pushes whether they are equal up to ASCII case
+fn builtin_eq_ignore_case(stack: &mut Stack) -> ZvarResult<()> {
+    let b = stack.pop()?;
+    let a = stack.pop()?;
+    let equal = a.as_str()?.eq_ignore_ascii_case(b.as_str()?);
+
+    stack.push(Value::Bool(equal))?;
+    Ok(())
+}
+
+/// Built-in to_upper function
+/// Pops a string and pushes it with ASCII letters uppercased; other characters are kept
+fn builtin_to_upper(stack: &mut Stack) -> ZvarResult<()> {
+    let value = stack.pop()?;
+    stack.push(Value::from(value.as_str()?.to_ascii_uppercase()))?;
+    Ok(())
+}
+
+/// Built-in to_lower function
+/// Pops a string and pushes it with ASCII letters lowercased; other characters are kept
+fn builtin_to_lower(stack: &mut Stack) -> ZvarResult<()> {
+    let value = stack.pop()?;
+    stack.push(Value::from(value.as_str()?.to_ascii_lowercase()))?;
+    Ok(())
+}
+
+/// Built-in trim function
+/// Pops a string and pushes it without leading and trailing whitespace
+fn builtin_trim(stack: &mut Stack) -> ZvarResult<()> {
+    let value = stack.pop()?;
+    stack.push(Value::from(value.as_str()?.trim()))?;
+    Ok(())
+}
+
+/// Built-in len function
+/// Pops a string and pushes its length in characters
+fn builtin_len(stack: &mut Stack) -> ZvarResult<()> {
+    let value = stack.pop()?;
+    stack.push(Value::Int(value.as_str()?.chars().count() as i64))?;
+    Ok(())
+}
+
+/// Built-in byte_len function
+/// Pops a string and pushes the number of bytes it takes in UTF-8
+fn builtin_byte_len(stack: &mut Stack) -> ZvarResult<()> {
+    let value = stack.pop()?;
+    stack.push(Value::Int(value.as_str()?.len() as i64))?;
+    Ok(())
+}
+
 // Future built-in functions can be added here:
 
 /// Built-in debug function (prints stack state)
@@ -114,6 +256,45 @@ mod tests {
         assert!(names.contains(&&"print".to_string()));
     }
 
+    #[test]
+    fn test_void_builtins() {
+        let mut builtins = Builtins::new();
+        assert_eq!(builtins.returns_value("print"), Some(false));
+        assert_eq!(builtins.returns_value("trim"), Some(true));
+        assert_eq!(builtins.returns_value("nonexistent"), None);
+
+        builtins.register("discard".to_string(), vec![None], None, |stack| {
+            stack.pop()?;
+            Ok(())
+        });
+        assert_eq!(builtins.returns_value("discard"), Some(false));
+        assert_eq!(builtins.arity("discard"), Some(1));
+
+        let mut stack = Stack::new();
+        stack.push(Value::Int(1)).unwrap();
+        builtins.call("discard", &mut stack).unwrap();
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn test_signatures() {
+        let builtins = Builtins::new();
+        assert_eq!(builtins.params("print"), Some(&[None][..]));
+        assert_eq!(builtins.result("print"), Some(None));
+        assert_eq!(
+            builtins.params("eq_ignore_case"),
+            Some(&[Some(ValueType::Str), Some(ValueType::Str)][..])
+        );
+        assert_eq!(builtins.result("len"), Some(Some(ValueType::Int)));
+        assert_eq!(builtins.result("random"), Some(Some(ValueType::Int)));
+        assert_eq!(builtins.params("nonexistent"), None);
+
+        // random runs as its own instruction, never through the registry
+        let mut stack = Stack::new();
+        stack.push(Value::Int(6)).unwrap();
+        assert!(builtins.call("random", &mut stack).is_err());
+    }
+
     #[test]
     fn test_print_function() {
         let builtins = Builtins::new();
@@ -128,6 +309,76 @@ mod tests {
         assert!(stack.is_empty()); // Print should consume the value
     }
 
+    #[test]
+    fn test_eq_ignore_case() {
+        let builtins = Builtins::new();
+        assert_eq!(builtins.arity("eq_ignore_case"), Some(2));
+
+        let mut stack = Stack::new();
+        for (a, b, expected) in [("Hello", "hello", true), ("Hello", "help", false)] {
+            stack.push(Value::from(a)).unwrap();
+            stack.push(Value::from(b)).unwrap();
+            builtins.call("eq_ignore_case", &mut stack).unwrap();
+            assert_eq!(stack.pop().unwrap(), Value::Bool(expected));
+        }
+
+        stack.push(Value::from("1")).unwrap();
+        stack.push(Value::Int(1)).unwrap();
+        assert!(builtins.call("eq_ignore_case", &mut stack).is_err());
+    }
+
+    #[test]
+    fn test_case_conversion() {
+        let builtins = Builtins::new();
+        let mut stack = Stack::new();
+
+        stack.push(Value::from("Hello, Wörld")).unwrap();
+        builtins.call("to_upper", &mut stack).unwrap();
+        assert_eq!(stack.pop().unwrap(), Value::from("HELLO, WöRLD"));
+
+        stack.push(Value::from("Hello, WÖRLD")).unwrap();
+        builtins.call("to_lower", &mut stack).unwrap();
+        assert_eq!(stack.pop().unwrap(), Value::from("hello, wÖrld"));
+
+        stack.push(Value::Int(1)).unwrap();
+        assert!(matches!(
+            builtins.call("to_upper", &mut stack),
+            Err(ZvarError::RuntimeError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_trim() {
+        let builtins = Builtins::new();
+        let mut stack = Stack::new();
+
+        stack.push(Value::from("  hi ")).unwrap();
+        builtins.call("trim", &mut stack).unwrap();
+        assert_eq!(stack.pop().unwrap(), Value::from("hi"));
+
+        stack.push(Value::Bool(true)).unwrap();
+        assert!(builtins.call("trim", &mut stack).is_err());
+    }
+
+    #[test]
+    fn test_len_and_byte_len() {
+        let builtins = Builtins::new();
+        let mut stack = Stack::new();
+
+        for (text, chars, bytes) in [("abc", 3, 3), ("h\u{e9}llo", 5, 6), ("hi \u{1F600}", 4, 7)] {
+            stack.push(Value::from(text)).unwrap();
+            builtins.call("len", &mut stack).unwrap();
+            assert_eq!(stack.pop().unwrap(), Value::Int(chars));
+
+            stack.push(Value::from(text)).unwrap();
+            builtins.call("byte_len", &mut stack).unwrap();
+            assert_eq!(stack.pop().unwrap(), Value::Int(bytes));
+        }
+
+        stack.push(Value::Int(1)).unwrap();
+        assert!(builtins.call("len", &mut stack).is_err());
+    }
+
     #[test]
     fn test_unknown_function() {
         let builtins = Builtins::new();
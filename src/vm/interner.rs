@@ -0,0 +1,63 @@
+//! String interning for runtime string values
+
+use crate::prelude::*;
+
+/// Keeps one shared buffer per distinct string
+///
+/// Interning a string that was seen before returns the existing `Rc`, so
+/// programs that build the same strings over and over don't pay for a new
+/// allocation each time.
+#[derive(Debug, Clone, Default)]
+pub struct StringInterner {
+    strings: BTreeSet<Rc<str>>,
+}
+
+impl StringInterner {
+    /// Create an empty interner
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the shared buffer for a string, storing it on first use
+    pub fn intern(&mut self, s: &str) -> Rc<str> {
+        if let Some(existing) = self.strings.get(s) {
+            return Rc::clone(existing);
+        }
+
+        let interned: Rc<str> = Rc::from(s);
+        self.strings.insert(Rc::clone(&interned));
+        interned
+    }
+
+    /// Number of distinct strings stored
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Check if no string has been interned yet
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interning_shares_buffers() {
+        let mut interner = StringInterner::new();
+        assert!(interner.is_empty());
+
+        let first = interner.intern("hello");
+        let second = interner.intern("hello");
+        let other = interner.intern("world");
+
+        assert!(Rc::ptr_eq(&first, &second));
+        assert!(!Rc::ptr_eq(&first, &other));
+        assert_eq!(interner.len(), 2);
+
+        // The interner keeps one reference of its own
+        assert_eq!(Rc::strong_count(&first), 3);
+    }
+}
@@ -1,6 +1,7 @@
 //! Virtual machine for executing zvar bytecode
 
 pub mod builtins;
+pub mod interner;
 pub mod stack;
 pub mod value;
 
@@ -10,12 +11,22 @@ use crate::{
         instruction::{Bytecode, Instruction},
     },
     error::{ZvarError, ZvarResult},
+    prelude::*,
+    span::Span,
 };
 
 use builtins::Builtins;
+#[cfg(all(feature = "no_std", not(test)))]
+use core::fmt::Write;
+use core::{fmt, ops::Range};
+use interner::StringInterner;
 use stack::Stack;
-use std::collections::HashMap;
-use value::Value;
+#[cfg(not(all(feature = "no_std", not(test))))]
+use std::io::Write;
+use value::{OverflowMode, Value};
+
+/// Number of innermost call frames reported in a stack overflow
+const MAX_BACKTRACE_FRAMES: usize = 16;
 
 /// Virtual machine state
 #[derive(Debug)]
@@ -24,6 +35,10 @@ pub struct VM {
     stack: Stack,
     /// Variable storage (indexed by slot number)
     variables: Vec<Option<Value>>,
+    // Top-level variables; unlike `variables` these are never saved or restored by calls
+    globals: Vec<Option<Value>>,
+    // Results of memoized pure functions, keyed by function name and arguments
+    memo_cache: BTreeMap<(String, Vec<Value>), Value>,
     /// Built-in functions
     builtins: Builtins,
     /// Function call stack for tracking returns
@@ -35,9 +50,62 @@ pub struct VM {
     /// Debug information
     debug_info: Option<DebugInfo>,
     /// Entity documentation (for runtime describe() calls)
-    entity_docs: HashMap<String, String>,
+    entity_docs: BTreeMap<String, String>,
     /// Debug mode flag
     debug_mode: bool,
+    /// Print one line per executed instruction
+    trace: bool,
+    /// Destination of trace lines (stderr if not set)
+    trace_writer: Option<TraceWriter>,
+    // State of the xorshift generator behind `random`, never zero
+    rng_state: u64,
+    // Shared buffers for runtime strings, when interning is enabled
+    interner: Option<StringInterner>,
+    // Print booleans as 1/0 instead of true/false
+    bool_numeric_output: bool,
+    // Print integers with thousands separators, e.g. 1,000,000
+    group_digits: bool,
+    // Destination of printed values (stdout if not set)
+    output_writer: Option<TraceWriter>,
+    // Executed describes (entity, text, span) in order, when collection is enabled
+    describe_log: Option<Vec<(String, String, Option<Span>)>>,
+    // What integer +, - and * do when the result doesn't fit
+    overflow_mode: OverflowMode,
+}
+
+/// Output for instruction traces and printed values
+struct TraceWriter(Box<dyn Write>);
+
+impl fmt::Debug for TraceWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TraceWriter")
+    }
+}
+
+/// Execution state of a VM, taken with `VM::snapshot`
+///
+/// Holds everything a run changes except output and the memo cache, so a
+/// debugger can rewind to a saved point and execute from there again.
+#[derive(Debug, Clone)]
+pub struct VmState {
+    stack: Stack,
+    variables: Vec<Option<Value>>,
+    globals: Vec<Option<Value>>,
+    call_stack: Vec<CallFrame>,
+    ip: usize,
+    rng_state: u64,
+}
+
+impl VmState {
+    /// Instruction pointer at the time of the snapshot
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    /// Number of active function calls at the time of the snapshot
+    pub fn call_depth(&self) -> usize {
+        self.call_stack.len()
+    }
 }
 
 /// Call frame for function calls
@@ -46,6 +114,13 @@ struct CallFrame {
     return_address: usize,
     function_name: String,
     saved_variables: Vec<Option<Value>>,
+    /// Stack height of the caller once the arguments were popped
+    stack_height: usize,
+    /// Arguments of a memoized call, used to cache its result
+    memo_arguments: Option<Vec<Value>>,
+    /// Instructions of the called function; leaving them without a return is an error
+    code: Range<usize>,
+    #[allow(dead_code)]
     variable_base: usize,
 }
 
@@ -55,13 +130,141 @@ impl VM {
         VM {
             stack: Stack::new(),
             variables: Vec::new(),
+            globals: Vec::new(),
+            memo_cache: BTreeMap::new(),
             builtins: Builtins::new(),
             call_stack: Vec::new(),
             ip: 0,
             bytecode: None,
             debug_info: None,
-            entity_docs: HashMap::new(),
+            entity_docs: BTreeMap::new(),
             debug_mode: false,
+            trace: false,
+            trace_writer: None,
+            rng_state: seed_state(clock_seed()),
+            interner: None,
+            bool_numeric_output: false,
+            group_digits: false,
+            output_writer: None,
+            describe_log: None,
+            overflow_mode: OverflowMode::Checked,
+        }
+    }
+
+    /// Print booleans C-style as `1`/`0`
+    ///
+    /// Only output of `print` changes; booleans still display as
+    /// `true`/`false` everywhere else.
+    pub fn set_bool_numeric_output(&mut self, numeric: bool) {
+        self.bool_numeric_output = numeric;
+    }
+
+    /// Print integers with `,` between groups of three digits
+    ///
+    /// Only output of `print` changes; integers still display without
+    /// separators everywhere else.
+    pub fn set_group_digits(&mut self, group: bool) {
+        self.group_digits = group;
+    }
+
+    /// Choose how integer addition, subtraction and multiplication overflow
+    ///
+    /// Defaults to [`OverflowMode::Checked`]. Division and `**` always check.
+    pub fn set_overflow_mode(&mut self, mode: OverflowMode) {
+        self.overflow_mode = mode;
+    }
+
+    /// Send printed values to a writer instead of stdout
+    ///
+    /// The writer is a `core::fmt::Write` with the `no_std` feature, where
+    /// printed values are dropped unless a writer is set.
+    pub fn set_output_writer(&mut self, writer: impl Write + 'static) {
+        self.output_writer = Some(TraceWriter(Box::new(writer)));
+    }
+
+    /// Pop the top value and print it on its own line
+    fn print_top(&mut self) -> ZvarResult<()> {
+        let text = match self.stack.pop()? {
+            Value::Bool(b) if self.bool_numeric_output => (b as i64).to_string(),
+            Value::Int(n) if self.group_digits => group_digits(n),
+            value => value.to_string(),
+        };
+
+        match &mut self.output_writer {
+            Some(TraceWriter(writer)) => {
+                writeln!(writer, "{}", text).map_err(|e| ZvarError::IoError {
+                    message: format!("Failed to write output: {}", e),
+                })
+            }
+            None => {
+                println!("{}", text);
+                Ok(())
+            }
+        }
+    }
+
+    /// Record every executed `describe` with its location
+    ///
+    /// Meant for tooling (e.g. editor hovers) that needs the order and
+    /// position of descriptions, not just the latest text per entity.
+    pub fn set_describe_log(&mut self, enabled: bool) {
+        self.describe_log = enabled.then(Vec::new);
+    }
+
+    /// Describe events collected so far; empty unless collection is enabled
+    pub fn describe_log(&self) -> &[(String, String, Option<Span>)] {
+        self.describe_log.as_deref().unwrap_or_default()
+    }
+
+    /// Let equal runtime strings share one buffer
+    ///
+    /// String literals and concatenation results are interned, which saves
+    /// memory for programs that produce the same strings repeatedly.
+    pub fn set_string_interning(&mut self, enabled: bool) {
+        self.interner = enabled.then(StringInterner::new);
+    }
+
+    /// Convert a bytecode value to a runtime value, interning strings if enabled
+    fn runtime_value(&mut self, value: &crate::codegen::instruction::Value) -> Value {
+        match (&mut self.interner, value) {
+            (Some(interner), crate::codegen::instruction::Value::Str(s)) => {
+                Value::Str(interner.intern(s))
+            }
+            _ => value.clone().into(),
+        }
+    }
+
+    /// Swap a freshly built string for its interned copy, if interning is enabled
+    fn intern_value(&mut self, value: Value) -> Value {
+        match (&mut self.interner, value) {
+            (Some(interner), Value::Str(s)) => Value::Str(interner.intern(&s)),
+            (_, value) => value,
+        }
+    }
+
+    /// Seed the generator behind `random`, making its sequence reproducible
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng_state = seed_state(seed);
+    }
+
+    /// Advance the xorshift64* generator
+    fn next_random(&mut self) -> u64 {
+        let mut x = self.rng_state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.rng_state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Create a virtual machine whose value and call stacks hold at most `limit` entries
+    ///
+    /// For embedders that want deep recursion to fail fast: hitting the limit
+    /// stops execution with a `StackOverflow` carrying the call backtrace.
+    pub fn with_stack_limit(limit: usize) -> Self {
+        VM {
+            stack: Stack::with_capacity(limit),
+            ..Self::new()
         }
     }
 
@@ -69,9 +272,94 @@ impl VM {
         self.debug_mode = debug_mode;
     }
 
+    /// Print `ip: INSTRUCTION | stack top` for every executed instruction
+    ///
+    /// Unlike debug mode this is one compact line per instruction, showing
+    /// up to three values from the top of the stack after it ran.
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    /// Send trace lines to a writer instead of stderr
+    pub fn set_trace_writer(&mut self, writer: impl Write + 'static) {
+        self.trace_writer = Some(TraceWriter(Box::new(writer)));
+    }
+
+    /// Write the trace line for the instruction at the current IP
+    fn trace_instruction(&mut self, instruction: &Instruction) -> ZvarResult<()> {
+        let mut top: Vec<String> = (0..self.stack.len().min(3))
+            .filter_map(|depth| self.stack.get(depth).ok())
+            .map(Value::display_quoted)
+            .collect();
+        if self.stack.len() > 3 {
+            top.push("...".to_string());
+        } else if top.is_empty() {
+            top.push("-".to_string());
+        }
+
+        let line = format!("{:04}: {} | {}", self.ip, instruction, top.join(", "));
+        match &mut self.trace_writer {
+            Some(TraceWriter(writer)) => {
+                writeln!(writer, "{}", line).map_err(|e| ZvarError::IoError {
+                    message: format!("Failed to write trace: {}", e),
+                })
+            }
+            None => {
+                eprintln!("{}", line);
+                Ok(())
+            }
+        }
+    }
+
+    /// Make sure a called function has not run past its last instruction
+    ///
+    /// Well-formed functions always end in a return; without one, execution
+    /// would continue into whatever code follows the function.
+    fn check_within_function(&self) -> ZvarResult<()> {
+        match self.call_stack.last() {
+            Some(frame) if !frame.code.contains(&self.ip) => Err(ZvarError::runtime(format!(
+                "Execution fell off the end of function {} at IP {}",
+                frame.function_name, self.ip
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Verify that a returning function leaves exactly its return value behind
+    ///
+    /// Runs in debug mode only. The stack must hold the caller's values plus
+    /// one for `RETVAL` (none for `RET`); anything else means the generated
+    /// code leaked or consumed values, e.g. an expression statement that is
+    /// no longer followed by `POP`.
+    fn check_return_stack_height(&self, frame: &CallFrame) -> ZvarResult<()> {
+        let arity = match self
+            .bytecode
+            .as_ref()
+            .and_then(|bytecode| bytecode.instructions.get(self.ip))
+        {
+            Some(Instruction::ReturnValue) => 1,
+            _ => 0,
+        };
+
+        let expected = frame.stack_height + arity;
+        if self.stack.len() != expected {
+            return Err(ZvarError::CodegenError {
+                message: format!(
+                    "Stack height {} after returning from {} at IP {}, expected {}",
+                    self.stack.len(),
+                    frame.function_name,
+                    self.ip,
+                    expected
+                ),
+            });
+        }
+
+        Ok(())
+    }
+
     /// Debug method to show stack state
     pub fn debug_stack_state(&self, instruction: &str) {
-        let stack_preview = if self.stack.len() > 0 {
+        let stack_preview = if !self.stack.is_empty() {
             let items: Vec<String> = (0..self.stack.len().min(5))
                 .map(|i| match self.stack.get(self.stack.len() - 1 - i) {
                     Ok(val) => format!("{}", val),
@@ -97,20 +385,111 @@ impl VM {
 
     /// Load bytecode and debug info into the VM
     pub fn load(&mut self, bytecode: Bytecode, debug_info: Option<DebugInfo>) {
-        // Calculate required variable slots
-        let max_var_slot = bytecode
+        // Initialize variable storage
+        self.variables = vec![None; Self::required_variable_slots(&bytecode)];
+        self.globals = vec![None; Self::required_global_slots(&bytecode)];
+
+        self.install(bytecode, debug_info);
+    }
+
+    /// Load new bytecode while keeping previously stored variables and docs
+    ///
+    /// Used for incremental evaluation (e.g. the REPL), where every input is
+    /// compiled on its own but must see the variables left by earlier inputs.
+    /// The caller is responsible for keeping slot numbers stable between loads.
+    pub fn load_and_continue(&mut self, bytecode: Bytecode, debug_info: Option<DebugInfo>) {
+        let required_slots = Self::required_variable_slots(&bytecode);
+        if self.variables.len() < required_slots {
+            self.variables.resize(required_slots, None);
+        }
+        let required_globals = Self::required_global_slots(&bytecode);
+        if self.globals.len() < required_globals {
+            self.globals.resize(required_globals, None);
+        }
+
+        // Nothing from the previous execution should leak into the new one
+        self.clear_transient_state();
+
+        self.install(bytecode, debug_info);
+    }
+
+    /// Capture the current execution state
+    pub fn snapshot(&self) -> VmState {
+        VmState {
+            stack: self.stack.clone(),
+            variables: self.variables.clone(),
+            globals: self.globals.clone(),
+            call_stack: self.call_stack.clone(),
+            ip: self.ip,
+            rng_state: self.rng_state,
+        }
+    }
+
+    /// Return to a state captured by `snapshot`
+    ///
+    /// The loaded bytecode is kept; the state must come from a VM running
+    /// the same bytecode for the instruction pointer to make sense.
+    pub fn restore(&mut self, state: VmState) {
+        self.stack = state.stack;
+        self.variables = state.variables;
+        self.globals = state.globals;
+        self.call_stack = state.call_stack;
+        self.ip = state.ip;
+        self.rng_state = state.rng_state;
+    }
+
+    /// Reset the execution state left behind by an interrupted run
+    ///
+    /// Clears the stack, the call stack and the instruction pointer but keeps
+    /// the variables, so the REPL can recover from a runtime error without
+    /// losing user state. Parameter slots overwritten by calls that never
+    /// returned are restored from their call frames.
+    pub fn clear_transient_state(&mut self) {
+        while let Some(frame) = self.call_stack.pop() {
+            for (i, saved_var) in frame.saved_variables.into_iter().enumerate() {
+                if i < self.variables.len() {
+                    self.variables[i] = saved_var;
+                }
+            }
+        }
+
+        self.stack.clear();
+        self.ip = 0;
+    }
+
+    /// Calculate the number of variable slots the bytecode refers to
+    fn required_variable_slots(bytecode: &Bytecode) -> usize {
+        bytecode
             .instructions
             .iter()
             .filter_map(|inst| match inst {
-                Instruction::LoadVar(slot) | Instruction::StoreVar(slot) => Some(*slot),
+                Instruction::LoadVar(slot)
+                | Instruction::StoreVar(slot)
+                | Instruction::StoreVarKeep(slot)
+                | Instruction::IncVar(slot, _) => Some(*slot as usize + 1),
                 _ => None,
             })
             .max()
-            .unwrap_or(0);
+            .unwrap_or(0)
+    }
 
-        // Initialize variable storage
-        self.variables = vec![None; (max_var_slot + 1) as usize];
+    /// Calculate the number of global slots the bytecode refers to
+    fn required_global_slots(bytecode: &Bytecode) -> usize {
+        bytecode
+            .instructions
+            .iter()
+            .filter_map(|inst| match inst {
+                Instruction::LoadGlobal(slot) | Instruction::StoreGlobal(slot) => {
+                    Some(*slot as usize + 1)
+                }
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
 
+    /// Set the entry point and take ownership of bytecode and debug info
+    fn install(&mut self, bytecode: Bytecode, debug_info: Option<DebugInfo>) {
         // Set entry point
         self.ip = bytecode.entry_point;
 
@@ -121,113 +500,182 @@ impl VM {
             }
         }
 
+        // Functions of the new bytecode may have the same names but different bodies
+        self.memo_cache.clear();
+
         self.bytecode = Some(bytecode);
         self.debug_info = debug_info;
     }
 
+    /// Check if calls to a function are cached by the loaded bytecode
+    fn is_memoized(&self, name: &str) -> bool {
+        self.bytecode
+            .as_ref()
+            .is_some_and(|bytecode| bytecode.memoized_functions.contains(name))
+    }
+
     /// Execute the loaded bytecode
     pub fn run(&mut self) -> ZvarResult<()> {
-        loop {
-            // Check if we're at the end or past the end
-            let instruction_count = self
-                .bytecode
-                .as_ref()
-                .ok_or_else(|| ZvarError::runtime("No bytecode loaded"))?
-                .instructions
-                .len();
+        self.bytecode
+            .as_ref()
+            .ok_or_else(|| ZvarError::runtime("No bytecode loaded"))?
+            .validate()?;
 
-            if self.ip >= instruction_count {
-                break;
-            }
+        while self.step()? == StepOutcome::Continue {}
+
+        Ok(())
+    }
+
+    /// Execute exactly one instruction
+    ///
+    /// Unlike `run`, the bytecode is not validated first; a jump past the
+    /// end simply halts.
+    pub fn step(&mut self) -> ZvarResult<StepOutcome> {
+        // Check if we're at the end or past the end
+        let instruction_count = self
+            .bytecode
+            .as_ref()
+            .ok_or_else(|| ZvarError::runtime("No bytecode loaded"))?
+            .instructions
+            .len();
+
+        if self.ip >= instruction_count {
+            return Ok(StepOutcome::Halted);
+        }
 
-            // Clone the instruction to avoid borrowing issues
-            let instruction = self.bytecode.as_ref().unwrap().instructions[self.ip].clone();
+        // Clone the instruction to avoid borrowing issues
+        let instruction = self.bytecode.as_ref().unwrap().instructions[self.ip].clone();
 
-            // DEBUG: Show state before execution
-            if self.debug_mode {
-                println!("DEBUG: Before executing {} at IP {}", instruction, self.ip);
-                self.debug_stack_state("BEFORE");
+        // DEBUG: Show state before execution
+        if self.debug_mode {
+            println!("DEBUG: Before executing {} at IP {}", instruction, self.ip);
+            self.debug_stack_state("BEFORE");
+        }
+
+        let result = match self.execute_instruction(&instruction) {
+            Err(ZvarError::StackOverflow { depth, .. }) => {
+                return Err(self.stack_overflow(depth));
+            }
+            Err(ZvarError::IntegerOverflow { span: None }) => {
+                return Err(ZvarError::IntegerOverflow {
+                    span: self
+                        .debug_info
+                        .as_ref()
+                        .and_then(|debug_info| debug_info.get_instruction_span(self.ip)),
+                });
             }
+            result => result?,
+        };
+
+        if self.trace {
+            self.trace_instruction(&instruction)?;
+        }
 
-            match self.execute_instruction(&instruction)? {
-                ExecutionResult::Continue => {
-                    self.ip += 1;
+        match result {
+            ExecutionResult::Continue => {
+                self.ip += 1;
+                self.check_within_function()?;
+            }
+            ExecutionResult::Jump(new_ip) => {
+                if self.debug_mode {
+                    println!("DEBUG: Jumping from {} to {}", self.ip, new_ip);
                 }
-                ExecutionResult::Jump(new_ip) => {
-                    if self.debug_mode {
-                        println!("DEBUG: Jumping from {} to {}", self.ip, new_ip);
-                    }
-                    self.ip = new_ip;
+                self.ip = new_ip;
+                self.check_within_function()?;
+            }
+            ExecutionResult::Return => {
+                if self.debug_mode {
+                    println!("DEBUG: Function return triggered");
+                    self.debug_stack_state("BEFORE RETURN");
                 }
-                ExecutionResult::Return => {
+                if let Some(frame) = self.call_stack.pop() {
                     if self.debug_mode {
-                        println!("DEBUG: Function return triggered");
-                        self.debug_stack_state("BEFORE RETURN");
+                        self.check_return_stack_height(&frame)?;
                     }
-                    if let Some(frame) = self.call_stack.pop() {
-                        // Save return value BEFORE restoring variables
-                        let return_value = if !self.stack.is_empty() {
-                            let val = self.stack.pop()?;
-                            if self.debug_mode {
-                                println!("DEBUG: Saved return value: {}", val);
-                            }
-                            Some(val)
-                        } else {
-                            if self.debug_mode {
-                                println!("DEBUG: No return value on stack");
-                            }
-                            None
-                        };
 
-                        // Restore the saved variables
+                    // Save return value BEFORE restoring variables
+                    let return_value = if !self.stack.is_empty() {
+                        let val = self.stack.pop()?;
                         if self.debug_mode {
-                            println!(
-                                "DEBUG: Restoring {} saved variables",
-                                frame.saved_variables.len()
-                            );
+                            println!("DEBUG: Saved return value: {}", val);
                         }
-                        for (i, saved_var) in frame.saved_variables.iter().enumerate() {
-                            if i < self.variables.len() {
-                                self.variables[i] = saved_var.clone();
-                            }
+                        Some(val)
+                    } else {
+                        if self.debug_mode {
+                            println!("DEBUG: No return value on stack");
                         }
+                        None
+                    };
 
-                        // Put return value back AFTER restoring variables
-                        if let Some(value) = return_value {
-                            self.stack.push(value.clone())?;
-                            if self.debug_mode {
-                                println!("DEBUG: Restored return value to stack: {}", value);
-                            }
-                        }
+                    // Drop anything the callee left below its return value
+                    self.stack.truncate_to(frame.stack_height);
 
-                        if self.debug_mode {
-                            println!("DEBUG: Returning to IP {}", frame.return_address);
+                    // Restore the saved variables
+                    if self.debug_mode {
+                        println!(
+                            "DEBUG: Restoring {} saved variables",
+                            frame.saved_variables.len()
+                        );
+                    }
+                    for (i, saved_var) in frame.saved_variables.iter().enumerate() {
+                        if i < self.variables.len() {
+                            self.variables[i] = saved_var.clone();
                         }
-                        self.ip = frame.return_address;
-                    } else {
-                        // Return from main, halt execution
+                    }
+
+                    if let (Some(arguments), Some(value)) = (frame.memo_arguments, &return_value) {
+                        self.memo_cache
+                            .insert((frame.function_name.clone(), arguments), value.clone());
+                    }
+
+                    // Put return value back AFTER restoring variables
+                    if let Some(value) = return_value {
+                        self.stack.push(value.clone())?;
                         if self.debug_mode {
-                            println!("DEBUG: Main function return - halting");
+                            println!("DEBUG: Restored return value to stack: {}", value);
                         }
-                        break;
                     }
-                }
-                ExecutionResult::Halt => {
+
+                    if self.debug_mode {
+                        println!(
+                            "DEBUG: Returning from {} to IP {}",
+                            frame.function_name, frame.return_address
+                        );
+                    }
+                    self.ip = frame.return_address;
+                } else {
+                    // Return from main, halt execution
                     if self.debug_mode {
-                        println!("DEBUG: HALT instruction - stopping execution");
+                        println!("DEBUG: Main function return - halting");
                     }
-                    break;
+                    return Ok(StepOutcome::Returned);
                 }
             }
-
-            if self.debug_mode {
-                // DEBUG: Show state after execution
-                self.debug_stack_state("AFTER");
-                println!("DEBUG: ----------------------------------------");
+            ExecutionResult::Halt => {
+                if self.debug_mode {
+                    println!("DEBUG: HALT instruction - stopping execution");
+                }
+                return Ok(StepOutcome::Halted);
             }
         }
 
-        Ok(())
+        if self.debug_mode {
+            // DEBUG: Show state after execution
+            self.debug_stack_state("AFTER");
+            println!("DEBUG: ----------------------------------------");
+        }
+
+        Ok(StepOutcome::Continue)
+    }
+
+    /// Address the jump at the current instruction pointer goes to
+    fn jump_target(&self, instruction: &Instruction) -> ZvarResult<usize> {
+        instruction.jump_target(self.ip).ok_or_else(|| {
+            ZvarError::runtime(format!(
+                "{} at IP {} jumps before the first instruction",
+                instruction, self.ip
+            ))
+        })
     }
 
     /// Execute a single instruction
@@ -246,7 +694,8 @@ impl VM {
             }
 
             Instruction::Push(value) => {
-                self.stack.push(value.clone().into())?;
+                let value = self.runtime_value(value);
+                self.stack.push(value)?;
                 Ok(ExecutionResult::Continue)
             }
 
@@ -255,6 +704,11 @@ impl VM {
                 Ok(ExecutionResult::Continue)
             }
 
+            Instruction::Swap => {
+                self.stack.swap()?;
+                Ok(ExecutionResult::Continue)
+            }
+
             Instruction::Add => {
                 if self.stack.len() < 2 {
                     return Err(ZvarError::runtime(format!(
@@ -265,7 +719,8 @@ impl VM {
                 }
                 let b = self.stack.pop()?;
                 let a = self.stack.pop()?;
-                let result = a.add(&b)?;
+                let result = a.add_with(&b, self.overflow_mode)?;
+                let result = self.intern_value(result);
                 self.stack.push(result)?;
                 Ok(ExecutionResult::Continue)
             }
@@ -280,7 +735,7 @@ impl VM {
                 }
                 let b = self.stack.pop()?;
                 let a = self.stack.pop()?;
-                let result = a.sub(&b)?;
+                let result = a.sub_with(&b, self.overflow_mode)?;
                 self.stack.push(result)?;
                 Ok(ExecutionResult::Continue)
             }
@@ -295,7 +750,7 @@ impl VM {
                 }
                 let b = self.stack.pop()?;
                 let a = self.stack.pop()?;
-                let result = a.mul(&b)?;
+                let result = a.mul_with(&b, self.overflow_mode)?;
                 self.stack.push(result)?;
                 Ok(ExecutionResult::Continue)
             }
@@ -315,6 +770,21 @@ impl VM {
                 Ok(ExecutionResult::Continue)
             }
 
+            Instruction::Pow => {
+                if self.stack.len() < 2 {
+                    return Err(ZvarError::runtime(format!(
+                        "Stack underflow: POW needs 2 values, only {} available at IP {}",
+                        self.stack.len(),
+                        self.ip
+                    )));
+                }
+                let b = self.stack.pop()?;
+                let a = self.stack.pop()?;
+                let result = a.pow(&b)?;
+                self.stack.push(result)?;
+                Ok(ExecutionResult::Continue)
+            }
+
             // NEW: Comparison operations
             Instruction::Equal => {
                 if self.stack.len() < 2 {
@@ -485,13 +955,73 @@ impl VM {
                 Ok(ExecutionResult::Continue)
             }
 
+            Instruction::StoreVarKeep(slot) => {
+                if *slot as usize >= self.variables.len() {
+                    return Err(ZvarError::runtime(format!(
+                        "Invalid variable slot: {}",
+                        slot
+                    )));
+                }
+
+                let value = self.stack.peek().map_err(|_| {
+                    ZvarError::runtime(format!(
+                        "Stack underflow: STOREVAR_KEEP needs 1 value, stack is empty at IP {}",
+                        self.ip
+                    ))
+                })?;
+                self.variables[*slot as usize] = Some(value.clone());
+                Ok(ExecutionResult::Continue)
+            }
+
+            Instruction::IncVar(slot, amount) => {
+                if *slot as usize >= self.variables.len() {
+                    return Err(ZvarError::runtime(format!(
+                        "Invalid variable slot: {}",
+                        slot
+                    )));
+                }
+
+                let value = self.variables[*slot as usize].as_ref().ok_or_else(|| {
+                    ZvarError::runtime(format!("Uninitialized variable v${}", slot))
+                })?;
+
+                let result = value.add_with(&Value::Int(*amount), self.overflow_mode)?;
+                self.variables[*slot as usize] = Some(result);
+                Ok(ExecutionResult::Continue)
+            }
+
+            Instruction::LoadGlobal(slot) => {
+                let value = self
+                    .globals
+                    .get(*slot as usize)
+                    .ok_or_else(|| ZvarError::runtime(format!("Invalid global slot: {}", slot)))?
+                    .clone()
+                    .ok_or_else(|| {
+                        ZvarError::runtime(format!("Uninitialized global slot {}", slot))
+                    })?;
+
+                self.stack.push(value)?;
+                Ok(ExecutionResult::Continue)
+            }
+
+            Instruction::StoreGlobal(slot) => {
+                if *slot as usize >= self.globals.len() {
+                    return Err(ZvarError::runtime(format!("Invalid global slot: {}", slot)));
+                }
+
+                let value = self.stack.pop()?;
+                self.globals[*slot as usize] = Some(value);
+                Ok(ExecutionResult::Continue)
+            }
+
             Instruction::LoadConst(index) => {
                 let bytecode = self.bytecode.as_ref().unwrap();
-                let value = bytecode.get_constant(*index).ok_or_else(|| {
+                let value = bytecode.get_constant(*index).cloned().ok_or_else(|| {
                     ZvarError::runtime(format!("Invalid constant index: {}", index))
                 })?;
 
-                self.stack.push(value.clone().into())?;
+                let value = self.runtime_value(&value);
+                self.stack.push(value)?;
                 Ok(ExecutionResult::Continue)
             }
 
@@ -501,54 +1031,81 @@ impl VM {
                     self.builtins.call(name, &mut self.stack)?;
                     Ok(ExecutionResult::Continue)
                 } else {
-                    // User-defined function call
-                    if let Some(debug) = &self.debug_info {
-                        if let Some(func_start) = debug.get_function_start(name) {
-                            // Save the current values of variables that will be overwritten
-                            let mut saved_vars = Vec::new();
-                            for i in 0..*argc {
-                                if (i as usize) < self.variables.len() {
-                                    saved_vars.push(self.variables[i as usize].clone());
-                                } else {
-                                    saved_vars.push(None);
+                    // User-defined function call, resolved through the function table
+                    let func_range = self
+                        .bytecode
+                        .as_ref()
+                        .and_then(|bytecode| bytecode.function_range(name));
+
+                    if let Some(code) = func_range {
+                        let func_start = code.start;
+                        // Memoized functions skip the body for arguments seen before
+                        let memo_arguments = if self.is_memoized(name) {
+                            let arguments = (0..*argc as usize)
+                                .rev()
+                                .map(|depth| self.stack.get(depth).cloned())
+                                .collect::<ZvarResult<Vec<_>>>()?;
+
+                            let key = (name.clone(), arguments);
+                            if let Some(result) = self.memo_cache.get(&key).cloned() {
+                                for _ in 0..*argc {
+                                    self.stack.pop()?;
                                 }
+                                self.stack.push(result)?;
+                                return Ok(ExecutionResult::Continue);
                             }
+                            Some(key.1)
+                        } else {
+                            None
+                        };
 
-                            // Ensure we have enough variable slots
-                            if (*argc as usize) > self.variables.len() {
-                                self.variables.resize(*argc as usize, None);
-                            }
+                        if self.call_stack.len() >= self.stack.limit() {
+                            return Err(ZvarError::stack_overflow(self.call_stack.len()));
+                        }
 
-                            // Store function arguments into parameter variables (v$0, v$1, etc.)
-                            let mut args = Vec::new();
-                            for _ in 0..*argc {
-                                args.push(self.stack.pop()?);
+                        // Save the current values of variables that will be overwritten
+                        let mut saved_vars = Vec::new();
+                        for i in 0..*argc {
+                            if (i as usize) < self.variables.len() {
+                                saved_vars.push(self.variables[i as usize].clone());
+                            } else {
+                                saved_vars.push(None);
                             }
-                            args.reverse(); // Put them in correct order
+                        }
 
-                            // Store each argument in slots 0, 1, 2, etc.
-                            for (i, arg) in args.iter().enumerate() {
-                                self.variables[i] = Some(arg.clone());
-                            }
+                        // Ensure we have enough variable slots
+                        if (*argc as usize) > self.variables.len() {
+                            self.variables.resize(*argc as usize, None);
+                        }
 
-                            // Push call frame with saved variables
-                            // FIX: Set return address to current IP + 1 (the instruction after CALL)
-                            self.call_stack.push(CallFrame {
-                                return_address: self.ip + 1,
-                                function_name: name.clone(),
-                                saved_variables: saved_vars,
-                                variable_base: 0,
-                            });
-
-                            // Jump to function
-                            Ok(ExecutionResult::Jump(func_start))
-                        } else {
-                            Err(ZvarError::runtime(format!("Unknown function: {}", name)))
+                        // Store function arguments into parameter variables (v$0, v$1, etc.)
+                        let mut args = Vec::new();
+                        for _ in 0..*argc {
+                            args.push(self.stack.pop()?);
+                        }
+                        args.reverse(); // Put them in correct order
+
+                        // Store each argument in slots 0, 1, 2, etc.
+                        for (i, arg) in args.iter().enumerate() {
+                            self.variables[i] = Some(arg.clone());
                         }
+
+                        // Push call frame with saved variables
+                        // FIX: Set return address to current IP + 1 (the instruction after CALL)
+                        self.call_stack.push(CallFrame {
+                            return_address: self.ip + 1,
+                            function_name: name.clone(),
+                            saved_variables: saved_vars,
+                            stack_height: self.stack.len(),
+                            memo_arguments,
+                            code,
+                            variable_base: 0,
+                        });
+
+                        // Jump to function
+                        Ok(ExecutionResult::Jump(func_start))
                     } else {
-                        Err(ZvarError::runtime(
-                            "No debug info available for function calls",
-                        ))
+                        Err(ZvarError::runtime(format!("Unknown function: {}", name)))
                     }
                 }
             }
@@ -558,9 +1115,11 @@ impl VM {
                 Ok(ExecutionResult::Return)
             }
 
-            Instruction::Jump(address) => Ok(ExecutionResult::Jump(*address)),
+            Instruction::Jump(_) | Instruction::JumpRel(_) => {
+                Ok(ExecutionResult::Jump(self.jump_target(instruction)?))
+            }
 
-            Instruction::JumpIfFalse(address) => {
+            Instruction::JumpIfFalse(_) | Instruction::JumpIfFalseRel(_) => {
                 if self.stack.is_empty() {
                     return Err(ZvarError::runtime(format!(
                         "Stack underflow: JUMP_IF_FALSE needs 1 value, stack is empty at IP {}",
@@ -569,7 +1128,22 @@ impl VM {
                 }
                 let value = self.stack.pop()?;
                 if !value.is_truthy() {
-                    Ok(ExecutionResult::Jump(*address))
+                    Ok(ExecutionResult::Jump(self.jump_target(instruction)?))
+                } else {
+                    Ok(ExecutionResult::Continue)
+                }
+            }
+
+            Instruction::JumpIfTrue(_) | Instruction::JumpIfTrueRel(_) => {
+                if self.stack.is_empty() {
+                    return Err(ZvarError::runtime(format!(
+                        "Stack underflow: JUMP_IF_TRUE needs 1 value, stack is empty at IP {}",
+                        self.ip
+                    )));
+                }
+                let value = self.stack.pop()?;
+                if value.is_truthy() {
+                    Ok(ExecutionResult::Jump(self.jump_target(instruction)?))
                 } else {
                     Ok(ExecutionResult::Continue)
                 }
@@ -582,13 +1156,38 @@ impl VM {
                         self.ip
                     )));
                 }
-                self.builtins.call("print", &mut self.stack)?;
+                self.print_top()?;
+                Ok(ExecutionResult::Continue)
+            }
+
+            Instruction::Random => {
+                let max = self.stack.pop()?.as_int()?;
+                if max <= 0 {
+                    return Err(ZvarError::runtime(format!(
+                        "random() needs a positive upper bound, found {} at IP {}",
+                        max, self.ip
+                    )));
+                }
+                let value = (self.next_random() % max as u64) as i64;
+                self.stack.push(Value::Int(value))?;
                 Ok(ExecutionResult::Continue)
             }
 
             Instruction::Describe(entity, description) => {
-                // Store documentation for runtime access
-                self.entity_docs.insert(entity.clone(), description.clone());
+                // Store documentation for runtime access, alongside what the
+                // compiler already collected for the entity
+                let doc = crate::docs::append_description(
+                    self.entity_docs.get(entity).map(String::as_str),
+                    description,
+                );
+                self.entity_docs.insert(entity.clone(), doc);
+                if let Some(log) = &mut self.describe_log {
+                    let span = self
+                        .debug_info
+                        .as_ref()
+                        .and_then(|debug_info| debug_info.get_instruction_span(self.ip));
+                    log.push((entity.clone(), description.clone(), span));
+                }
                 if self.debug_mode {
                     println!("Debug: {} - {}", entity, description);
                 }
@@ -611,7 +1210,7 @@ impl VM {
         println!("Variables:");
         for (i, var) in self.variables.iter().enumerate() {
             match var {
-                Some(value) => println!("  v${}: {}", i, value),
+                Some(value) => println!("  v${}: {}", i, value.display_quoted()),
                 None => println!("  v${}: <uninitialized>", i),
             }
         }
@@ -622,16 +1221,93 @@ impl VM {
         self.entity_docs.get(entity)
     }
 
-    /// Reset the VM state
-    pub fn reset(&mut self) {
-        self.stack.clear();
-        self.variables.clear();
-        self.call_stack.clear();
-        self.ip = 0;
-        self.entity_docs.clear();
+    /// Attach the active call frames to a stack overflow, innermost first
+    fn stack_overflow(&self, depth: usize) -> ZvarError {
+        let mut backtrace: Vec<String> = self
+            .call_stack
+            .iter()
+            .rev()
+            .take(MAX_BACKTRACE_FRAMES)
+            .map(|frame| self.backtrace_frame(&frame.function_name))
+            .collect();
+
+        if self.call_stack.len() > MAX_BACKTRACE_FRAMES {
+            backtrace.push(format!(
+                "... {} more",
+                self.call_stack.len() - MAX_BACKTRACE_FRAMES
+            ));
+        }
+        backtrace.push("main".to_string());
+
+        ZvarError::StackOverflow { depth, backtrace }
+    }
+
+    /// Name of a function in a backtrace, followed by the first line of its docs
+    fn backtrace_frame(&self, function_name: &str) -> String {
+        let summary = self
+            .entity_docs
+            .get(function_name)
+            .and_then(|doc| doc.lines().map(str::trim).find(|line| !line.is_empty()));
+
+        match summary {
+            Some(summary) => format!("{} ({})", function_name, summary),
+            None => function_name.to_string(),
+        }
+    }
+
+    /// Current stack, for debuggers and other tools that show its contents
+    pub fn stack(&self) -> &Stack {
+        &self.stack
+    }
+
+    /// Value on top of the stack, e.g. the result left by a finished program
+    pub fn stack_top(&self) -> Option<&Value> {
+        self.stack.peek().ok()
+    }
+
+    /// Reset the VM state
+    pub fn reset(&mut self) {
+        self.stack.clear();
+        self.variables.clear();
+        self.call_stack.clear();
+        self.ip = 0;
+        self.entity_docs.clear();
     }
 }
 
+/// Seed for `random` taken from the system clock
+#[cfg(not(all(feature = "no_std", not(test))))]
+fn clock_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+}
+
+/// Without std there is no clock; embedders call `VM::set_seed` instead
+#[cfg(all(feature = "no_std", not(test)))]
+fn clock_seed() -> u64 {
+    0
+}
+
+/// Turn a seed into a valid xorshift state, which must not be zero
+fn seed_state(seed: u64) -> u64 {
+    match seed ^ 0x9E37_79B9_7F4A_7C15 {
+        0 => 0x9E37_79B9_7F4A_7C15,
+        state => state,
+    }
+}
+
+/// What happened in a single `VM::step`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// More instructions remain to be executed
+    Continue,
+    /// Execution stopped at `HALT` or the end of the bytecode
+    Halted,
+    /// Main returned, which also ends execution
+    Returned,
+}
+
 /// Result of executing an instruction
 #[derive(Debug, PartialEq)]
 enum ExecutionResult {
@@ -647,10 +1323,315 @@ impl Default for VM {
     }
 }
 
+/// Format an integer with `,` between groups of three digits
+fn group_digits(n: i64) -> String {
+    let digits = n.unsigned_abs().to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3 + 1);
+    if n < 0 {
+        grouped.push('-');
+    }
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::codegen::instruction::{Bytecode, Instruction, Value as InstValue};
+    use crate::test_util::SharedBuffer;
+
+    /// Compile and run a program, returning the VM for inspection
+    fn run_program(source: &str) -> VM {
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+        let mut vm = VM::new();
+        vm.load(bytecode, Some(debug_info));
+        vm.run().unwrap();
+        vm
+    }
+
+    #[test]
+    fn test_bool_numeric_output() {
+        let source = "main { print(1 < 2); print(false); print(7); }";
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+
+        let buffer = SharedBuffer::default();
+        let mut vm = VM::new();
+        vm.set_output_writer(buffer.clone());
+        vm.load(bytecode.clone(), Some(debug_info.clone()));
+        vm.run().unwrap();
+        assert_eq!(buffer.contents(), "true\nfalse\n7\n");
+
+        let buffer = SharedBuffer::default();
+        let mut vm = VM::new();
+        vm.set_output_writer(buffer.clone());
+        vm.set_bool_numeric_output(true);
+        vm.load(bytecode, Some(debug_info));
+        vm.run().unwrap();
+        assert_eq!(buffer.contents(), "1\n0\n7\n");
+    }
+
+    #[test]
+    fn test_group_digits() {
+        let source = r#"
+        main {
+            print(1000000);
+            print(0 - 1234567);
+            print(999);
+            print(0 - 9223372036854775807 - 1);
+            print("1000");
+        }
+        "#;
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+
+        let buffer = SharedBuffer::default();
+        let mut vm = VM::new();
+        vm.set_output_writer(buffer.clone());
+        vm.load(bytecode.clone(), Some(debug_info.clone()));
+        vm.run().unwrap();
+        assert_eq!(
+            buffer.contents(),
+            "1000000\n-1234567\n999\n-9223372036854775808\n1000\n"
+        );
+
+        let buffer = SharedBuffer::default();
+        let mut vm = VM::new();
+        vm.set_output_writer(buffer.clone());
+        vm.set_group_digits(true);
+        vm.load(bytecode, Some(debug_info));
+        vm.run().unwrap();
+        assert_eq!(
+            buffer.contents(),
+            "1,000,000\n-1,234,567\n999\n-9,223,372,036,854,775,808\n1000\n"
+        );
+    }
+
+    #[test]
+    fn test_overflow_mode() {
+        let source = "main { int v$0 = 9223372036854775807; v$0 = v$0 + 1; print(v$0); }";
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+
+        let mut vm = VM::new();
+        vm.load(bytecode.clone(), Some(debug_info.clone()));
+        assert!(matches!(
+            vm.run(),
+            Err(ZvarError::IntegerOverflow { span: Some(_) })
+        ));
+
+        for (mode, expected) in [
+            (OverflowMode::Wrapping, "-9223372036854775808\n"),
+            (OverflowMode::Saturating, "9223372036854775807\n"),
+        ] {
+            let buffer = SharedBuffer::default();
+            let mut vm = VM::new();
+            vm.set_output_writer(buffer.clone());
+            vm.set_overflow_mode(mode);
+            vm.load(bytecode.clone(), Some(debug_info.clone()));
+            vm.run().unwrap();
+            assert_eq!(buffer.contents(), expected);
+        }
+    }
+
+    #[test]
+    fn test_empty_main() {
+        let (bytecode, debug_info) = crate::compile_source("main {}").unwrap();
+        assert_eq!(bytecode.instructions, vec![Instruction::Halt]);
+
+        let mut vm = VM::new();
+        vm.load(bytecode, Some(debug_info));
+        assert!(vm.variables.is_empty());
+        assert!(vm.run().is_ok());
+
+        // An empty program compiles to no instructions at all
+        let (bytecode, debug_info) = crate::compile_source("").unwrap();
+        assert!(bytecode.is_empty());
+        vm.load(bytecode, Some(debug_info));
+        assert!(vm.run().is_ok());
+    }
+
+    #[test]
+    fn test_program_without_main_runs_nothing() {
+        let source = r#"
+        fn f$0() -> int {
+            ret 1 / 0;
+        }
+        "#;
+
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+        assert_eq!(
+            bytecode.instructions[bytecode.entry_point],
+            Instruction::Halt
+        );
+
+        let mut vm = VM::new();
+        vm.load(bytecode, Some(debug_info));
+        assert!(vm.run().is_ok());
+
+        // Globals alone are initialized, then the program halts
+        let (bytecode, debug_info) = crate::compile_source("int v$0 = 5;").unwrap();
+        assert!(bytecode.instructions.contains(&Instruction::Halt));
+
+        let mut vm = VM::new();
+        vm.load(bytecode, Some(debug_info));
+        assert!(vm.run().is_ok());
+        assert_eq!(vm.globals, vec![Some(Value::Int(5))]);
+    }
+
+    #[test]
+    fn test_string_interning() {
+        let source = r#"
+        main {
+            str v$0 = "zvar";
+            str v$1 = "zvar";
+            str v$2 = "zv" + "ar";
+        }
+        "#;
+
+        let string_at = |vm: &VM, slot: usize| match &vm.variables[slot] {
+            Some(Value::Str(s)) => std::rc::Rc::clone(s),
+            other => panic!("Expected a string, found {:?}", other),
+        };
+
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+        let mut vm = VM::new();
+        vm.set_string_interning(true);
+        vm.load(bytecode.clone(), Some(debug_info.clone()));
+        vm.run().unwrap();
+
+        let first = string_at(&vm, 0);
+        assert!(std::rc::Rc::ptr_eq(&first, &string_at(&vm, 1)));
+        assert!(std::rc::Rc::ptr_eq(&first, &string_at(&vm, 2)));
+        // Three variables, the interner and `first` share one buffer
+        assert_eq!(std::rc::Rc::strong_count(&first), 5);
+
+        // Without interning every literal gets its own buffer
+        let mut vm = VM::new();
+        vm.load(bytecode, Some(debug_info));
+        vm.run().unwrap();
+        assert!(!std::rc::Rc::ptr_eq(&string_at(&vm, 0), &string_at(&vm, 1)));
+    }
+
+    #[test]
+    fn test_describe_log() {
+        let source = r#"
+        main {
+            int v$0 = 1;
+            describe(v$0, "The counter");
+            str v$1 = "x";
+            describe(v$1, "The label");
+        }
+        "#;
+
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+        let mut vm = VM::new();
+        vm.load(bytecode.clone(), Some(debug_info.clone()));
+        vm.run().unwrap();
+        assert!(vm.describe_log().is_empty());
+
+        let mut vm = VM::new();
+        vm.set_describe_log(true);
+        vm.load(bytecode, Some(debug_info));
+        vm.run().unwrap();
+
+        let log = vm.describe_log();
+        assert_eq!(log.len(), 2);
+        assert_eq!(
+            (log[0].0.as_str(), log[0].1.as_str()),
+            ("v$0", "The counter")
+        );
+        assert_eq!((log[1].0.as_str(), log[1].1.as_str()), ("v$1", "The label"));
+        let (first, second) = (log[0].2.unwrap(), log[1].2.unwrap());
+        assert!(first.start_line < second.start_line);
+    }
+
+    #[test]
+    fn test_while_else() {
+        let run = |start: i64| {
+            let source = format!(
+                r#"
+                main {{
+                    int v$0 = {};
+                    int v$1 = 0;
+                    int v$2 = 0;
+                    while (v$0 > 0) {{
+                        v$0 = v$0 - 1;
+                        v$2 = v$2 + 1;
+                        if (v$0 == 3) {{
+                            break;
+                        }}
+                    }} else {{
+                        v$1 = 1;
+                    }}
+                }}
+                "#,
+                start
+            );
+
+            let (bytecode, debug_info) = crate::compile_source(&source).unwrap();
+            let mut vm = VM::new();
+            vm.load(bytecode, Some(debug_info));
+            vm.run().unwrap();
+            (
+                vm.variables[0].clone(),
+                vm.variables[1].clone(),
+                vm.variables[2].clone(),
+            )
+        };
+
+        // Zero iterations: the else block runs
+        assert_eq!(
+            run(0),
+            (
+                Some(Value::Int(0)),
+                Some(Value::Int(1)),
+                Some(Value::Int(0))
+            )
+        );
+
+        // Several iterations without break: the else block is skipped
+        assert_eq!(
+            run(2),
+            (
+                Some(Value::Int(0)),
+                Some(Value::Int(0)),
+                Some(Value::Int(2))
+            )
+        );
+
+        // Leaving through break skips the else block as well
+        assert_eq!(
+            run(6),
+            (
+                Some(Value::Int(3)),
+                Some(Value::Int(0)),
+                Some(Value::Int(3))
+            )
+        );
+    }
+
+    #[test]
+    fn test_eq_ignore_case() {
+        let source = r#"
+        main {
+            bool v$0 = eq_ignore_case("Hello", "hello");
+            bool v$1 = eq_ignore_case("Hello", "world");
+            eq_ignore_case("a", "A");
+        }
+        "#;
+
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+        let mut vm = VM::new();
+        vm.load(bytecode, Some(debug_info));
+        vm.run().unwrap();
+
+        assert_eq!(vm.variables[0], Some(Value::Bool(true)));
+        assert_eq!(vm.variables[1], Some(Value::Bool(false)));
+        assert!(vm.stack.is_empty());
+    }
 
     #[test]
     fn test_basic_arithmetic() {
@@ -716,7 +1697,11 @@ mod tests {
         vm.load(bytecode, None);
         let result = vm.run();
 
-        assert!(matches!(result, Err(ZvarError::StackUnderflow)));
+        assert!(matches!(
+            result,
+            Err(ZvarError::RuntimeError { ref message, .. })
+                if message == "Stack underflow: tried to POP from empty stack at IP 0"
+        ));
     }
 
     #[test]
@@ -734,4 +1719,643 @@ mod tests {
 
         assert!(matches!(result, Err(ZvarError::DivisionByZero { .. })));
     }
+
+    #[test]
+    fn test_load_and_continue_keeps_variables() {
+        let mut vm = VM::new();
+
+        // First input: v$0 = 42
+        let mut first = Bytecode::new();
+        first.emit(Instruction::Push(InstValue::Int(42)));
+        first.emit(Instruction::StoreVar(0));
+        first.emit(Instruction::Halt);
+
+        vm.load(first, None);
+        vm.run().unwrap();
+
+        // Second input: v$1 = v$0 + 1, then load v$1
+        let mut second = Bytecode::new();
+        second.emit(Instruction::LoadVar(0));
+        second.emit(Instruction::Push(InstValue::Int(1)));
+        second.emit(Instruction::Add);
+        second.emit(Instruction::StoreVar(1));
+        second.emit(Instruction::LoadVar(1));
+        second.emit(Instruction::Halt);
+
+        vm.load_and_continue(second, None);
+        vm.run().unwrap();
+
+        assert_eq!(vm.stack.pop().unwrap(), Value::Int(43));
+        assert_eq!(vm.variables[0], Some(Value::Int(42)));
+    }
+
+    #[test]
+    fn test_recovers_after_runtime_error() {
+        let mut vm = VM::new();
+
+        // First input: v$0 = 42
+        let mut first = Bytecode::new();
+        first.emit(Instruction::Push(InstValue::Int(42)));
+        first.emit(Instruction::StoreVar(0));
+        first.emit(Instruction::Halt);
+
+        vm.load(first, None);
+        vm.run().unwrap();
+
+        // Second input fails halfway: v$0 / 0
+        let mut second = Bytecode::new();
+        second.emit(Instruction::Push(InstValue::Int(7)));
+        second.emit(Instruction::LoadVar(0));
+        second.emit(Instruction::Push(InstValue::Int(0)));
+        second.emit(Instruction::Div);
+        second.emit(Instruction::Halt);
+
+        vm.load_and_continue(second, None);
+        assert!(vm.run().is_err());
+        vm.clear_transient_state();
+
+        assert!(vm.stack.is_empty());
+        assert!(vm.call_stack.is_empty());
+        assert_eq!(vm.ip, 0);
+
+        // Third input: v$0 + 1
+        let mut third = Bytecode::new();
+        third.emit(Instruction::LoadVar(0));
+        third.emit(Instruction::Push(InstValue::Int(1)));
+        third.emit(Instruction::Add);
+        third.emit(Instruction::Halt);
+
+        vm.load_and_continue(third, None);
+        vm.run().unwrap();
+
+        assert_eq!(vm.stack.pop().unwrap(), Value::Int(43));
+        assert!(vm.stack.is_empty());
+        assert_eq!(vm.variables[0], Some(Value::Int(42)));
+    }
+
+    #[test]
+    fn test_conditional_expression_branches() {
+        let vm = run_program(
+            r#"
+            main {
+                int v$0 = true ? 10 : 20;
+                int v$1 = 1 > 2 ? 10 : 20;
+            }
+            "#,
+        );
+
+        // Slots follow declaration order in main
+        assert_eq!(vm.variables[0], Some(Value::Int(10)));
+        assert_eq!(vm.variables[1], Some(Value::Int(20)));
+    }
+
+    #[test]
+    fn test_jump_if_true() {
+        // Program: push <cond>; jump to 4 if true; push 1; halt; push 2; halt
+        let program = |condition: bool| {
+            let mut bytecode = Bytecode::new();
+            bytecode.emit(Instruction::Push(InstValue::Bool(condition)));
+            bytecode.emit(Instruction::JumpIfTrue(4));
+            bytecode.emit(Instruction::Push(InstValue::Int(1)));
+            bytecode.emit(Instruction::Halt);
+            bytecode.emit(Instruction::Push(InstValue::Int(2)));
+            bytecode.emit(Instruction::Halt);
+            bytecode
+        };
+
+        // Branch taken
+        let mut vm = VM::new();
+        vm.load(program(true), None);
+        vm.run().unwrap();
+        assert_eq!(vm.stack.pop().unwrap(), Value::Int(2));
+        assert!(vm.stack.is_empty());
+
+        // Fallthrough
+        let mut vm = VM::new();
+        vm.load(program(false), None);
+        vm.run().unwrap();
+        assert_eq!(vm.stack.pop().unwrap(), Value::Int(1));
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn test_swap_instruction() {
+        let mut vm = VM::new();
+        let mut bytecode = Bytecode::new();
+
+        // Program: push 1, push 2, swap
+        bytecode.emit(Instruction::Push(InstValue::Int(1)));
+        bytecode.emit(Instruction::Push(InstValue::Int(2)));
+        bytecode.emit(Instruction::Swap);
+        bytecode.emit(Instruction::Halt);
+
+        vm.load(bytecode, None);
+        vm.run().unwrap();
+
+        assert_eq!(vm.stack.pop().unwrap(), Value::Int(1));
+        assert_eq!(vm.stack.pop().unwrap(), Value::Int(2));
+    }
+
+    #[test]
+    fn test_stripped_bytecode_runs_without_debug_info() {
+        let source = r#"
+        /// Doubles its input
+        fn f$0(v$0 int) -> int {
+            ret v$0 * 2;
+        }
+
+        main {
+            describe(f$0, "Doubles its input");
+            print(f$0(21));
+        }
+        "#;
+
+        let (mut bytecode, _) = crate::compile_source(source).unwrap();
+        bytecode.strip_docs();
+
+        assert!(!bytecode
+            .instructions
+            .iter()
+            .any(|instruction| matches!(instruction, Instruction::Describe(..))));
+
+        let mut vm = VM::new();
+        vm.load(bytecode, None);
+        assert!(vm.run().is_ok());
+        assert!(vm.debug_info.is_none());
+    }
+
+    #[test]
+    fn test_stack_overflow_backtrace() {
+        // Every level keeps v$0 on the stack while calling the next one
+        let source = r#"
+        fn f$0(v$0 int) -> int {
+            ret v$0 + f$0(v$0 + 1);
+        }
+
+        fn f$1(v$0 int) -> int {
+            ret f$0(v$0);
+        }
+
+        main {
+            print(f$1(0));
+        }
+        "#;
+
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+        let mut vm = VM::with_stack_limit(8);
+        vm.load(bytecode, Some(debug_info));
+
+        match vm.run() {
+            Err(ZvarError::StackOverflow { depth, backtrace }) => {
+                assert_eq!(depth, 8);
+                assert_eq!(backtrace.first().map(String::as_str), Some("f$0"));
+                assert_eq!(&backtrace[backtrace.len() - 2..], ["f$1", "main"]);
+            }
+            other => panic!("Expected stack overflow, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_backtrace_shows_function_docs() {
+        let source = r#"
+        /// Sums all integers from v$0 upwards
+        /// Never terminates, on purpose
+        fn f$0(v$0 int) -> int {
+            ret v$0 + f$0(v$0 + 1);
+        }
+
+        fn f$1(v$0 int) -> int {
+            ret f$0(v$0);
+        }
+
+        main {
+            print(f$1(0));
+        }
+        "#;
+
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+        let mut vm = VM::with_stack_limit(8);
+        vm.load(bytecode, Some(debug_info));
+
+        let error = vm.run().unwrap_err();
+        let ZvarError::StackOverflow { backtrace, .. } = &error else {
+            panic!("Expected stack overflow, got {:?}", error);
+        };
+        assert_eq!(backtrace[0], "f$0 (Sums all integers from v$0 upwards)");
+        assert_eq!(&backtrace[backtrace.len() - 2..], ["f$1", "main"]);
+        assert!(error
+            .to_string()
+            .contains("\n  in f$0 (Sums all integers from v$0 upwards)\n"));
+    }
+
+    #[test]
+    fn test_incvar_updates_slot_in_place() {
+        let mut vm = VM::new();
+        let mut bytecode = Bytecode::new();
+
+        bytecode.emit(Instruction::Push(InstValue::Int(40)));
+        bytecode.emit(Instruction::StoreVar(0));
+        bytecode.emit(Instruction::IncVar(0, 2));
+        bytecode.emit(Instruction::Halt);
+
+        vm.load(bytecode, None);
+        vm.run().unwrap();
+
+        assert_eq!(vm.variables[0], Some(Value::Int(42)));
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn test_trace_lines() {
+        let mut bytecode = Bytecode::new();
+        bytecode.emit(Instruction::Push(InstValue::Int(1)));
+        bytecode.emit(Instruction::Push(InstValue::Int(2)));
+        bytecode.emit(Instruction::Add);
+
+        let buffer = SharedBuffer::default();
+        let mut vm = VM::new();
+        vm.set_trace(true);
+        vm.set_trace_writer(buffer.clone());
+        vm.load(bytecode, None);
+        vm.run().unwrap();
+
+        let output = buffer.contents();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines,
+            ["0000: PUSH 1 | 1", "0001: PUSH 2 | 2, 1", "0002: ADD | 3"]
+        );
+    }
+
+    #[test]
+    fn test_return_from_main_stops_execution() {
+        let source = r#"
+        main {
+            int v$0 = 1;
+            ret 0;
+            v$0 = 2;
+        }
+        "#;
+
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+        let mut vm = VM::new();
+        vm.load(bytecode, Some(debug_info));
+        vm.run().unwrap();
+
+        assert_eq!(vm.variables[0], Some(Value::Int(1)));
+    }
+
+    #[test]
+    fn test_random_is_reproducible_with_a_seed() {
+        let source = r#"
+        main {
+            int v$0 = random(100);
+            int v$1 = random(100);
+            int v$2 = random(100);
+            int v$3 = random(1);
+        }
+        "#;
+        let run = |seed: u64| {
+            let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+            let mut vm = VM::new();
+            vm.set_seed(seed);
+            vm.load(bytecode, Some(debug_info));
+            vm.run().unwrap();
+            vm.variables.clone()
+        };
+
+        let first = run(42);
+        assert_eq!(first, run(42));
+        assert_eq!(first[3], Some(Value::Int(0)));
+        for value in &first[..3] {
+            let Some(Value::Int(n)) = value else {
+                panic!("Expected an int, found {:?}", value);
+            };
+            assert!((0..100).contains(n));
+        }
+
+        let (bytecode, debug_info) = crate::compile_source("main { print(random(0)); }").unwrap();
+        let mut vm = VM::new();
+        vm.load(bytecode, Some(debug_info));
+        assert!(matches!(vm.run(), Err(ZvarError::RuntimeError { .. })));
+    }
+
+    #[test]
+    fn test_step() {
+        let mut bytecode = Bytecode::new();
+        bytecode.emit(Instruction::Push(InstValue::Int(1)));
+        bytecode.emit(Instruction::Pop);
+        bytecode.emit(Instruction::Nop);
+        bytecode.emit(Instruction::Halt);
+
+        let mut vm = VM::new();
+        vm.load(bytecode, None);
+
+        assert_eq!(vm.step().unwrap(), StepOutcome::Continue);
+        assert_eq!(vm.stack_top(), Some(&Value::Int(1)));
+        for ip in 2..4 {
+            assert_eq!(vm.step().unwrap(), StepOutcome::Continue);
+            assert_eq!(vm.ip, ip);
+        }
+        assert_eq!(vm.step().unwrap(), StepOutcome::Halted);
+        assert_eq!(vm.ip, 3);
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let source = r#"
+        fn f$0(v$0 int) -> int {
+            ret v$0 * 2;
+        }
+
+        main {
+            int v$1 = f$0(20);
+            int v$2 = v$1 + 1;
+        }
+        "#;
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+        let mul = bytecode
+            .instructions
+            .iter()
+            .position(|instruction| *instruction == Instruction::Mul)
+            .unwrap();
+
+        // Stop inside f$0 by temporarily replacing MUL with HALT
+        let mut vm = VM::new();
+        vm.load(bytecode, Some(debug_info));
+        vm.bytecode.as_mut().unwrap().instructions[mul] = Instruction::Halt;
+        vm.run().unwrap();
+        vm.bytecode.as_mut().unwrap().instructions[mul] = Instruction::Mul;
+
+        let state = vm.snapshot();
+        assert_eq!(state.ip(), mul);
+        assert_eq!(state.call_depth(), 1);
+
+        vm.run().unwrap();
+        assert_eq!(vm.variables[2], Some(Value::Int(41)));
+        assert!(vm.call_stack.is_empty());
+
+        vm.restore(state.clone());
+        assert_eq!(vm.ip, mul);
+        assert_eq!(vm.call_stack.len(), 1);
+        assert_eq!(vm.variables[2], None);
+
+        vm.run().unwrap();
+        assert_eq!(vm.variables[2], Some(Value::Int(41)));
+    }
+
+    #[test]
+    fn test_restore_replays_random_values() {
+        let source = "main { print(random(1000000)); print(random(1000000)); }";
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+
+        let buffer = SharedBuffer::default();
+        let mut vm = VM::new();
+        vm.set_output_writer(buffer.clone());
+        vm.load(bytecode, Some(debug_info));
+        let state = vm.snapshot();
+
+        vm.run().unwrap();
+        let first = buffer.contents();
+        vm.restore(state);
+        vm.run().unwrap();
+
+        assert_eq!(buffer.contents(), format!("{}{}", first, first));
+    }
+
+    #[test]
+    fn test_return_stack_height_check() {
+        let source = r#"
+        fn f$0(v$0 int, v$1 int) -> int {
+            ret v$0 * v$1;
+        }
+
+        main {
+            print(1 + f$0(2, 3));
+        }
+        "#;
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+        let mut vm = VM::new();
+        vm.set_debug_mode(true);
+        vm.load(bytecode, Some(debug_info));
+        assert!(vm.run().is_ok());
+
+        // A body that leaves an extra value behind is reported
+        let mut bytecode = Bytecode::new();
+        bytecode.mark_function_start("f$0".to_string(), 0);
+        bytecode.emit(Instruction::Push(InstValue::Int(1))); // 0: leaked
+        bytecode.emit(Instruction::Push(InstValue::Int(2))); // 1
+        bytecode.emit(Instruction::ReturnValue); // 2
+        bytecode.emit(Instruction::Call("f$0".to_string(), 0)); // 3
+        bytecode.emit(Instruction::Halt); // 4
+        bytecode.entry_point = 3;
+
+        let mut vm = VM::new();
+        vm.set_debug_mode(true);
+        vm.load(bytecode, None);
+        assert!(matches!(vm.run(), Err(ZvarError::CodegenError { .. })));
+    }
+
+    #[test]
+    fn test_function_without_return_is_an_error() {
+        // f$0 lacks a return and would run on into f$1
+        let mut bytecode = Bytecode::new();
+        bytecode.mark_function_start("f$0".to_string(), 0);
+        bytecode.emit(Instruction::Push(InstValue::Int(1))); // 0
+        bytecode.mark_function_start("f$1".to_string(), 1);
+        bytecode.emit(Instruction::Push(InstValue::Int(2))); // 1
+        bytecode.emit(Instruction::ReturnValue); // 2
+        bytecode.emit(Instruction::Call("f$0".to_string(), 0)); // 3
+        bytecode.emit(Instruction::Print); // 4
+        bytecode.emit(Instruction::Halt); // 5
+        bytecode.entry_point = 3;
+
+        let mut vm = VM::new();
+        vm.load(bytecode, None);
+        match vm.run() {
+            Err(ZvarError::RuntimeError { message }) => {
+                assert_eq!(
+                    message,
+                    "Execution fell off the end of function f$0 at IP 1"
+                )
+            }
+            other => panic!("Expected RuntimeError, got {:?}", other),
+        }
+
+        // The same holds for the last function before main
+        let mut bytecode = Bytecode::new();
+        bytecode.mark_function_start("f$0".to_string(), 0);
+        bytecode.emit(Instruction::Push(InstValue::Int(1))); // 0
+        bytecode.emit(Instruction::Call("f$0".to_string(), 0)); // 1
+        bytecode.emit(Instruction::Halt); // 2
+        bytecode.entry_point = 1;
+
+        let mut vm = VM::new();
+        vm.load(bytecode, None);
+        assert!(matches!(vm.run(), Err(ZvarError::RuntimeError { .. })));
+    }
+
+    #[test]
+    fn test_globals_are_shared_by_main_and_functions() {
+        let source = r#"
+        int v$9 = 10;
+
+        fn f$0(v$0 int) -> int {
+            v$9 = v$9 + v$0;
+            ret v$9;
+        }
+
+        main {
+            v$9 = v$9 * 2;
+            print(f$0(5));
+            v$9 = v$9 + 1;
+        }
+        "#;
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+        let mut vm = VM::new();
+        vm.load(bytecode, Some(debug_info));
+        vm.run().unwrap();
+
+        // 10 * 2 in main, + 5 in f$0, + 1 in main again
+        assert_eq!(vm.globals, vec![Some(Value::Int(26))]);
+    }
+
+    #[test]
+    fn test_integer_overflow_has_span() {
+        let source = r#"
+        main {
+            int v$0 = 9223372036854775807;
+            print(v$0 + 1);
+        }
+        "#;
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+        let mut vm = VM::new();
+        vm.load(bytecode, Some(debug_info));
+
+        let Err(ZvarError::IntegerOverflow { span: Some(span) }) = vm.run() else {
+            panic!("Expected an integer overflow with a span");
+        };
+        assert_eq!(span.start_line, 4);
+    }
+
+    #[test]
+    fn test_power() {
+        let source = r#"
+        main {
+            int v$0 = 2;
+            int v$1 = v$0 ** 10;
+            int v$2 = v$0 ** 3 ** 2;
+            int v$3 = 3 * v$0 ** 2;
+        }
+        "#;
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+        let mut vm = VM::new();
+        vm.load(bytecode, Some(debug_info));
+        vm.run().unwrap();
+
+        assert_eq!(vm.variables[1], Some(Value::Int(1024)));
+        // 2 ** (3 ** 2), not (2 ** 3) ** 2
+        assert_eq!(vm.variables[2], Some(Value::Int(512)));
+        assert_eq!(vm.variables[3], Some(Value::Int(12)));
+
+        let source = r#"
+        main {
+            int v$0 = 2;
+            print(v$0 ** 63);
+        }
+        "#;
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+        let mut vm = VM::new();
+        vm.load(bytecode, Some(debug_info));
+        assert!(matches!(
+            vm.run(),
+            Err(ZvarError::IntegerOverflow { span: Some(_) })
+        ));
+    }
+
+    #[test]
+    fn test_match_runs_one_arm() {
+        let run = |scrutinee: i64| {
+            let source = format!(
+                r#"
+                main {{
+                    int v$0 = {};
+                    int v$1 = 0;
+                    match (v$0) {{
+                        1 => {{ v$1 = v$1 + 10; }}
+                        2 => {{ v$1 = v$1 + 20; }}
+                        1 => {{ v$1 = v$1 + 100; }}
+                        _ => {{ v$1 = v$1 + 30; }}
+                    }}
+                }}
+                "#,
+                scrutinee
+            );
+            let (bytecode, debug_info) = crate::compile_source(&source).unwrap();
+            let mut vm = VM::new();
+            vm.load(bytecode, Some(debug_info));
+            vm.run().unwrap();
+            assert!(vm.stack.is_empty());
+            vm.variables[1].clone()
+        };
+
+        // Only the first matching arm runs, never the later duplicate or the wildcard
+        assert_eq!(run(1), Some(Value::Int(10)));
+        assert_eq!(run(2), Some(Value::Int(20)));
+        assert_eq!(run(7), Some(Value::Int(30)));
+    }
+
+    #[test]
+    fn test_memoized_function_runs_once_per_arguments() {
+        let source = r#"
+        pure memo fn f$0(v$0 int) -> int {
+            ret v$0 * 2;
+        }
+
+        main {
+            print(f$0(1) + f$0(1) + f$0(2) + f$0(1));
+        }
+        "#;
+        let (bytecode, debug_info) = crate::compile_source(source).unwrap();
+        let body_start = bytecode.get_function_start("f$0").unwrap();
+
+        let buffer = SharedBuffer::default();
+        let mut vm = VM::new();
+        vm.set_trace(true);
+        vm.set_trace_writer(buffer.clone());
+        vm.load(bytecode, Some(debug_info));
+        vm.run().unwrap();
+
+        // The body is entered for 1 and 2; the other calls hit the cache
+        let body_runs = buffer
+            .contents()
+            .lines()
+            .filter(|line| line.starts_with(&format!("{:04}:", body_start)))
+            .count();
+        assert_eq!(body_runs, 2);
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn test_memoized_function_cannot_read_globals() {
+        // A cached result would go stale once v$9 changes
+        let source = r#"
+        int v$9 = 1;
+
+        pure memo fn f$0(v$0 int) -> int {
+            ret v$0 + v$9;
+        }
+
+        main {
+            print(f$0(1));
+            v$9 = 100;
+            print(f$0(1));
+        }
+        "#;
+        assert!(matches!(
+            crate::compile_source(source),
+            Err(ZvarError::ImpureFunction { .. })
+        ));
+    }
 }
@@ -2,6 +2,7 @@
 
 use crate::{
     error::{ZvarError, ZvarResult},
+    prelude::*,
     vm::value::Value,
 };
 
@@ -9,7 +10,7 @@ use crate::{
 const STACK_SIZE_LIMIT: usize = 1024;
 
 /// Runtime stack for the virtual machine
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Stack {
     values: Vec<Value>,
     max_size: usize,
@@ -35,7 +36,7 @@ impl Stack {
     /// Push a value onto the stack
     pub fn push(&mut self, value: Value) -> ZvarResult<()> {
         if self.values.len() >= self.max_size {
-            return Err(ZvarError::StackOverflow);
+            return Err(ZvarError::stack_overflow(self.values.len()));
         }
 
         self.values.push(value);
@@ -63,6 +64,17 @@ impl Stack {
         self.push(top)
     }
 
+    /// Exchange the top two values
+    pub fn swap(&mut self) -> ZvarResult<()> {
+        let len = self.values.len();
+        if len < 2 {
+            return Err(ZvarError::StackUnderflow);
+        }
+
+        self.values.swap(len - 1, len - 2);
+        Ok(())
+    }
+
     /// Get the current stack size
     pub fn len(&self) -> usize {
         self.values.len()
@@ -78,6 +90,13 @@ impl Stack {
         self.values.clear();
     }
 
+    /// Discard every value above the given height
+    ///
+    /// Does nothing if the stack is already at or below `height`.
+    pub fn truncate_to(&mut self, height: usize) {
+        self.values.truncate(height);
+    }
+
     /// Get a value at a specific depth (0 = top, 1 = second from top, etc.)
     pub fn get(&self, depth: usize) -> ZvarResult<&Value> {
         if depth >= self.values.len() {
@@ -88,6 +107,14 @@ impl Stack {
         Ok(&self.values[index])
     }
 
+    /// Iterate over the values from the top of the stack to the bottom
+    ///
+    /// The n-th item is the value at depth n, as returned by [`Stack::get`].
+    /// Call `.rev()` to go from the bottom up, in push order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Value> + ExactSizeIterator {
+        self.values.iter().rev()
+    }
+
     /// Set a value at a specific depth
     pub fn set(&mut self, depth: usize, value: Value) -> ZvarResult<()> {
         if depth >= self.values.len() {
@@ -99,6 +126,11 @@ impl Stack {
         Ok(())
     }
 
+    /// Get the maximum number of values the stack can hold
+    pub fn limit(&self) -> usize {
+        self.max_size
+    }
+
     /// Get the maximum stack size reached (for debugging)
     pub fn high_water_mark(&self) -> usize {
         // In a more sophisticated implementation, we'd track this
@@ -108,7 +140,7 @@ impl Stack {
     /// Print the stack contents (for debugging)
     pub fn debug_print(&self) {
         println!("Stack (size: {}):", self.values.len());
-        for (i, value) in self.values.iter().rev().enumerate() {
+        for (i, value) in self.iter().enumerate() {
             let marker = if i == 0 { " -> " } else { "    " };
             println!("{}{}: {}", marker, self.values.len() - 1 - i, value);
         }
@@ -169,7 +201,10 @@ mod tests {
         stack.push(Value::Int(2)).unwrap();
 
         let result = stack.push(Value::Int(3));
-        assert!(matches!(result, Err(ZvarError::StackOverflow)));
+        assert!(matches!(
+            result,
+            Err(ZvarError::StackOverflow { depth: 2, .. })
+        ));
     }
 
     #[test]
@@ -184,6 +219,22 @@ mod tests {
         assert_eq!(stack.pop().unwrap(), Value::Int(42));
     }
 
+    #[test]
+    fn test_iter_goes_from_top_to_bottom() {
+        let mut stack = Stack::new();
+        assert_eq!(stack.iter().next(), None);
+
+        for n in 1..=3 {
+            stack.push(Value::Int(n)).unwrap();
+        }
+
+        let values: Vec<&Value> = stack.iter().collect();
+        assert_eq!(values, [&Value::Int(3), &Value::Int(2), &Value::Int(1)]);
+        assert_eq!(stack.iter().len(), 3);
+        assert_eq!(stack.iter().nth(1), Some(stack.get(1).unwrap()));
+        assert_eq!(stack.iter().next_back(), Some(&Value::Int(1)));
+    }
+
     #[test]
     fn test_indexed_access() {
         let mut stack = Stack::new();
@@ -200,4 +251,34 @@ mod tests {
         stack.set(1, Value::Int(99)).unwrap();
         assert_eq!(stack.get(1).unwrap(), &Value::Int(99));
     }
+
+    #[test]
+    fn test_truncate_to() {
+        let mut stack = Stack::new();
+        for i in 1..=5 {
+            stack.push(Value::Int(i)).unwrap();
+        }
+
+        stack.truncate_to(2);
+        assert_eq!(stack.len(), 2);
+        assert_eq!(stack.peek().unwrap(), &Value::Int(2));
+
+        // Truncating above the current height keeps everything
+        stack.truncate_to(4);
+        assert_eq!(stack.len(), 2);
+    }
+
+    #[test]
+    fn test_swap() {
+        let mut stack = Stack::new();
+        stack.push(Value::Int(1)).unwrap();
+
+        assert!(matches!(stack.swap(), Err(ZvarError::StackUnderflow)));
+
+        stack.push(Value::Int(2)).unwrap();
+        stack.swap().unwrap();
+
+        assert_eq!(stack.pop().unwrap(), Value::Int(1));
+        assert_eq!(stack.pop().unwrap(), Value::Int(2));
+    }
 }
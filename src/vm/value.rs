@@ -1,16 +1,56 @@
 //! Runtime value types for the zvar virtual machine
 
-use crate::error::{ZvarError, ZvarResult};
-use std::fmt;
+use crate::{
+    error::{ZvarError, ZvarResult},
+    prelude::*,
+    symbol_table::ValueType,
+};
+use core::fmt;
 
 /// Runtime values in the zvar VM
-#[derive(Debug, Clone, PartialEq)]
+///
+/// Strings are reference counted, so copying a value onto the stack or into
+/// a variable never copies the text itself.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Value {
     Int(i64),
-    Str(String),
+    Str(Rc<str>),
     Bool(bool),
 }
 
+/// How integer `+`, `-` and `*` handle results that don't fit in an `i64`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum OverflowMode {
+    /// Fail with an integer overflow error
+    #[default]
+    Checked,
+    /// Wrap around in two's complement
+    #[cfg_attr(feature = "cli", value(name = "wrap"))]
+    Wrapping,
+    /// Clamp to the smallest or largest `i64`
+    #[cfg_attr(feature = "cli", value(name = "saturate"))]
+    Saturating,
+}
+
+impl OverflowMode {
+    /// Apply the integer operation variant that matches this mode
+    fn apply(
+        self,
+        a: i64,
+        b: i64,
+        checked: fn(i64, i64) -> Option<i64>,
+        wrapping: fn(i64, i64) -> i64,
+        saturating: fn(i64, i64) -> i64,
+    ) -> ZvarResult<Value> {
+        match self {
+            OverflowMode::Checked => Value::checked_int(a, b, checked),
+            OverflowMode::Wrapping => Ok(Value::Int(wrapping(a, b))),
+            OverflowMode::Saturating => Ok(Value::Int(saturating(a, b))),
+        }
+    }
+}
+
 impl Value {
     /// Get integer value, return error if not an integer
     pub fn as_int(&self) -> ZvarResult<i64> {
@@ -66,12 +106,19 @@ impl Value {
         }
     }
 
-    /// Check if value is truthy
+    /// Check if value is truthy, i.e. converts to `true` with [`Value::coerce_to`]
     pub fn is_truthy(&self) -> bool {
+        matches!(self.coerce_to(ValueType::Bool), Ok(Value::Bool(true)))
+    }
+
+    /// Format the value so its type is visible, quoting and escaping strings
+    ///
+    /// `print` uses the plain `Display` form; this one is for diagnostics
+    /// such as traces, where the string `"1"` must not look like the int `1`.
+    pub fn display_quoted(&self) -> String {
         match self {
-            Value::Int(n) => *n != 0,
-            Value::Str(s) => !s.is_empty(),
-            Value::Bool(b) => *b,
+            Value::Str(s) => format!("{:?}", s),
+            other => other.to_string(),
         }
     }
 
@@ -84,14 +131,62 @@ impl Value {
         }
     }
 
+    /// Convert this value to the given type
+    ///
+    /// The VM's conversion rules; conditional jumps use the `bool` rules
+    /// through [`Value::is_truthy`]:
+    /// - `int -> bool` is `true` for non-zero values
+    /// - `str -> bool` is `true` for non-empty strings
+    /// - `bool -> int` gives `1` or `0`
+    /// - `int`/`bool -> str` use the printed form
+    /// - `str -> int` parses the string and fails if it is not a number
+    ///
+    /// Converting a value to its own type returns it unchanged.
+    pub fn coerce_to(&self, target: ValueType) -> ZvarResult<Value> {
+        match (self, &target) {
+            (Value::Int(_), ValueType::Int)
+            | (Value::Str(_), ValueType::Str)
+            | (Value::Bool(_), ValueType::Bool) => Ok(self.clone()),
+            (Value::Int(n), ValueType::Bool) => Ok(Value::Bool(*n != 0)),
+            (Value::Bool(b), ValueType::Int) => Ok(Value::Int(*b as i64)),
+            (Value::Int(_), ValueType::Str) | (Value::Bool(_), ValueType::Str) => {
+                Ok(Value::Str(self.to_string().into()))
+            }
+            (Value::Str(s), ValueType::Int) => {
+                s.trim().parse::<i64>().map(Value::Int).map_err(|_| {
+                    ZvarError::runtime(format!("Cannot coerce string \"{}\" to int", s))
+                })
+            }
+            (Value::Str(s), ValueType::Bool) => Ok(Value::Bool(!s.is_empty())),
+        }
+    }
+
+    /// Apply a checked integer operation, reporting overflow as `IntegerOverflow`
+    ///
+    /// The span is left empty; the VM fills it in from the debug info of the
+    /// instruction that failed.
+    pub fn checked_int(a: i64, b: i64, op: fn(i64, i64) -> Option<i64>) -> ZvarResult<Value> {
+        op(a, b)
+            .map(Value::Int)
+            .ok_or(ZvarError::IntegerOverflow { span: None })
+    }
+
     /// Perform addition with another value
     pub fn add(&self, other: &Value) -> ZvarResult<Value> {
+        self.add_with(other, OverflowMode::Checked)
+    }
+
+    /// Perform addition, handling integer overflow according to `mode`
+    pub fn add_with(&self, other: &Value, mode: OverflowMode) -> ZvarResult<Value> {
         match (self, other) {
-            (Value::Int(a), Value::Int(b)) => a
-                .checked_add(*b)
-                .map(Value::Int)
-                .ok_or_else(|| ZvarError::runtime("Integer overflow")),
-            (Value::Str(a), Value::Str(b)) => Ok(Value::Str(format!("{}{}", a, b))),
+            (Value::Int(a), Value::Int(b)) => mode.apply(
+                *a,
+                *b,
+                i64::checked_add,
+                i64::wrapping_add,
+                i64::saturating_add,
+            ),
+            (Value::Str(a), Value::Str(b)) => Ok(Value::Str(format!("{}{}", a, b).into())),
             _ => Err(ZvarError::runtime(format!(
                 "Cannot add {} and {}",
                 self.type_name(),
@@ -102,11 +197,19 @@ impl Value {
 
     /// Perform subtraction with another value
     pub fn sub(&self, other: &Value) -> ZvarResult<Value> {
+        self.sub_with(other, OverflowMode::Checked)
+    }
+
+    /// Perform subtraction, handling integer overflow according to `mode`
+    pub fn sub_with(&self, other: &Value, mode: OverflowMode) -> ZvarResult<Value> {
         match (self, other) {
-            (Value::Int(a), Value::Int(b)) => a
-                .checked_sub(*b)
-                .map(Value::Int)
-                .ok_or_else(|| ZvarError::runtime("Integer overflow")),
+            (Value::Int(a), Value::Int(b)) => mode.apply(
+                *a,
+                *b,
+                i64::checked_sub,
+                i64::wrapping_sub,
+                i64::saturating_sub,
+            ),
             _ => Err(ZvarError::runtime(format!(
                 "Cannot subtract {} from {}",
                 other.type_name(),
@@ -117,11 +220,19 @@ impl Value {
 
     /// Perform multiplication with another value
     pub fn mul(&self, other: &Value) -> ZvarResult<Value> {
+        self.mul_with(other, OverflowMode::Checked)
+    }
+
+    /// Perform multiplication, handling integer overflow according to `mode`
+    pub fn mul_with(&self, other: &Value, mode: OverflowMode) -> ZvarResult<Value> {
         match (self, other) {
-            (Value::Int(a), Value::Int(b)) => a
-                .checked_mul(*b)
-                .map(Value::Int)
-                .ok_or_else(|| ZvarError::runtime("Integer overflow")),
+            (Value::Int(a), Value::Int(b)) => mode.apply(
+                *a,
+                *b,
+                i64::checked_mul,
+                i64::wrapping_mul,
+                i64::saturating_mul,
+            ),
             _ => Err(ZvarError::runtime(format!(
                 "Cannot multiply {} and {}",
                 self.type_name(),
@@ -137,9 +248,7 @@ impl Value {
                 if *b == 0 {
                     return Err(ZvarError::DivisionByZero { span: None });
                 }
-                a.checked_div(*b)
-                    .map(Value::Int)
-                    .ok_or_else(|| ZvarError::runtime("Integer overflow"))
+                Value::checked_int(*a, *b, i64::checked_div)
             }
             _ => Err(ZvarError::runtime(format!(
                 "Cannot divide {} by {}",
@@ -149,7 +258,43 @@ impl Value {
         }
     }
 
+    /// Raise to an integer power
+    ///
+    /// Uses exponentiation by squaring, checking every multiplication for
+    /// overflow. Negative exponents have no integer result.
+    pub fn pow(&self, other: &Value) -> ZvarResult<Value> {
+        match (self, other) {
+            (Value::Int(_), Value::Int(exponent)) if *exponent < 0 => Err(ZvarError::runtime(
+                format!("Negative exponent {} is not supported for int", exponent),
+            )),
+            (Value::Int(base), Value::Int(exponent)) => {
+                let overflow = || ZvarError::IntegerOverflow { span: None };
+                let (mut result, mut base, mut exponent) = (1i64, *base, *exponent);
+
+                while exponent > 0 {
+                    if exponent & 1 == 1 {
+                        result = result.checked_mul(base).ok_or_else(overflow)?;
+                    }
+                    exponent >>= 1;
+                    if exponent > 0 {
+                        base = base.checked_mul(base).ok_or_else(overflow)?;
+                    }
+                }
+
+                Ok(Value::Int(result))
+            }
+            _ => Err(ZvarError::runtime(format!(
+                "Cannot raise {} to {}",
+                self.type_name(),
+                other.type_name()
+            ))),
+        }
+    }
+
     /// Perform equality comparison
+    ///
+    /// Values of different types are never equal. `int` is currently the only
+    /// numeric type, so there is no numeric promotion to apply here.
     pub fn equal(&self, other: &Value) -> ZvarResult<Value> {
         let result = match (self, other) {
             (Value::Int(a), Value::Int(b)) => a == b,
@@ -167,10 +312,14 @@ impl Value {
     }
 
     /// Perform less-than comparison
+    ///
+    /// Booleans are ordered `false < true`. Values of different types cannot
+    /// be compared.
     pub fn less(&self, other: &Value) -> ZvarResult<Value> {
         match (self, other) {
             (Value::Int(a), Value::Int(b)) => Ok(Value::Bool(a < b)),
             (Value::Str(a), Value::Str(b)) => Ok(Value::Bool(a < b)),
+            (Value::Bool(a), Value::Bool(b)) => Ok(Value::Bool(a < b)),
             _ => Err(ZvarError::runtime(format!(
                 "Cannot compare {} < {}",
                 self.type_name(),
@@ -184,6 +333,7 @@ impl Value {
         match (self, other) {
             (Value::Int(a), Value::Int(b)) => Ok(Value::Bool(a > b)),
             (Value::Str(a), Value::Str(b)) => Ok(Value::Bool(a > b)),
+            (Value::Bool(a), Value::Bool(b)) => Ok(Value::Bool(a > b)),
             _ => Err(ZvarError::runtime(format!(
                 "Cannot compare {} > {}",
                 self.type_name(),
@@ -197,6 +347,7 @@ impl Value {
         match (self, other) {
             (Value::Int(a), Value::Int(b)) => Ok(Value::Bool(a <= b)),
             (Value::Str(a), Value::Str(b)) => Ok(Value::Bool(a <= b)),
+            (Value::Bool(a), Value::Bool(b)) => Ok(Value::Bool(a <= b)),
             _ => Err(ZvarError::runtime(format!(
                 "Cannot compare {} <= {}",
                 self.type_name(),
@@ -210,6 +361,7 @@ impl Value {
         match (self, other) {
             (Value::Int(a), Value::Int(b)) => Ok(Value::Bool(a >= b)),
             (Value::Str(a), Value::Str(b)) => Ok(Value::Bool(a >= b)),
+            (Value::Bool(a), Value::Bool(b)) => Ok(Value::Bool(a >= b)),
             _ => Err(ZvarError::runtime(format!(
                 "Cannot compare {} >= {}",
                 self.type_name(),
@@ -272,13 +424,13 @@ impl From<i64> for Value {
 
 impl From<String> for Value {
     fn from(s: String) -> Self {
-        Value::Str(s)
+        Value::Str(s.into())
     }
 }
 
 impl From<&str> for Value {
     fn from(s: &str) -> Self {
-        Value::Str(s.to_string())
+        Value::Str(s.into())
     }
 }
 
@@ -288,20 +440,68 @@ impl From<bool> for Value {
     }
 }
 
+impl TryFrom<Value> for i64 {
+    type Error = ZvarError;
+
+    fn try_from(value: Value) -> ZvarResult<Self> {
+        value.as_int()
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = ZvarError;
+
+    fn try_from(value: Value) -> ZvarResult<Self> {
+        value.as_str().map(str::to_string)
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = ZvarError;
+
+    fn try_from(value: Value) -> ZvarResult<Self> {
+        value.as_bool()
+    }
+}
+
 impl From<crate::codegen::instruction::Value> for Value {
     fn from(val: crate::codegen::instruction::Value) -> Self {
         match val {
             crate::codegen::instruction::Value::Int(n) => Value::Int(n),
-            crate::codegen::instruction::Value::Str(s) => Value::Str(s),
+            crate::codegen::instruction::Value::Str(s) => Value::Str(s.into()),
             crate::codegen::instruction::Value::Bool(b) => Value::Bool(b),
         }
     }
 }
 
+impl From<Value> for crate::codegen::instruction::Value {
+    fn from(val: Value) -> Self {
+        match val {
+            Value::Int(n) => crate::codegen::instruction::Value::Int(n),
+            Value::Str(s) => crate::codegen::instruction::Value::Str(s.to_string()),
+            Value::Bool(b) => crate::codegen::instruction::Value::Bool(b),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_host_conversions() {
+        assert_eq!(i64::try_from(Value::from(42)).unwrap(), 42);
+        assert_eq!(String::try_from(Value::from("zvar")).unwrap(), "zvar");
+        assert!(bool::try_from(Value::from(true)).unwrap());
+
+        assert!(matches!(
+            i64::try_from(Value::from("42")),
+            Err(ZvarError::RuntimeError { .. })
+        ));
+        assert!(String::try_from(Value::Int(1)).is_err());
+        assert!(bool::try_from(Value::Int(1)).is_err());
+    }
+
     #[test]
     fn test_value_operations() {
         let a = Value::Int(10);
@@ -342,15 +542,12 @@ mod tests {
 
     #[test]
     fn test_string_operations() {
-        let a = Value::Str("hello".to_string());
-        let b = Value::Str(" world".to_string());
+        let a = Value::from("hello");
+        let b = Value::from(" world");
 
-        assert_eq!(a.add(&b).unwrap(), Value::Str("hello world".to_string()));
+        assert_eq!(a.add(&b).unwrap(), Value::from("hello world"));
         assert_eq!(a.equal(&b).unwrap(), Value::Bool(false));
-        assert_eq!(
-            a.equal(&Value::Str("hello".to_string())).unwrap(),
-            Value::Bool(true)
-        );
+        assert_eq!(a.equal(&Value::from("hello")).unwrap(), Value::Bool(true));
     }
 
     #[test]
@@ -362,6 +559,83 @@ mod tests {
         assert!(matches!(result, Err(ZvarError::DivisionByZero { .. })));
     }
 
+    #[test]
+    fn test_power() {
+        let two = Value::Int(2);
+        assert_eq!(two.pow(&Value::Int(10)).unwrap(), Value::Int(1024));
+        assert_eq!(two.pow(&Value::Int(0)).unwrap(), Value::Int(1));
+        assert_eq!(two.pow(&Value::Int(62)).unwrap(), Value::Int(1 << 62));
+        assert_eq!(
+            Value::Int(-2).pow(&Value::Int(63)).unwrap(),
+            Value::Int(i64::MIN)
+        );
+        assert!(matches!(
+            two.pow(&Value::Int(63)),
+            Err(ZvarError::IntegerOverflow { .. })
+        ));
+        assert!(two.pow(&Value::Int(-1)).is_err());
+        assert!(Value::Bool(true).pow(&two).is_err());
+    }
+
+    #[test]
+    fn test_integer_overflow() {
+        let max = Value::Int(i64::MAX);
+        assert!(matches!(
+            max.add(&Value::Int(1)),
+            Err(ZvarError::IntegerOverflow { span: None })
+        ));
+        assert!(matches!(
+            Value::Int(i64::MIN).sub(&Value::Int(1)),
+            Err(ZvarError::IntegerOverflow { .. })
+        ));
+        assert!(matches!(
+            max.mul(&Value::Int(2)),
+            Err(ZvarError::IntegerOverflow { .. })
+        ));
+        assert!(matches!(
+            Value::Int(i64::MIN).div(&Value::Int(-1)),
+            Err(ZvarError::IntegerOverflow { .. })
+        ));
+    }
+
+    #[test]
+    fn test_overflow_modes() {
+        let max = Value::Int(i64::MAX);
+        let one = Value::Int(1);
+
+        assert!(matches!(
+            max.add_with(&one, OverflowMode::Checked),
+            Err(ZvarError::IntegerOverflow { .. })
+        ));
+        assert_eq!(
+            max.add_with(&one, OverflowMode::Wrapping).unwrap(),
+            Value::Int(i64::MIN)
+        );
+        assert_eq!(
+            max.add_with(&one, OverflowMode::Saturating).unwrap(),
+            Value::Int(i64::MAX)
+        );
+
+        let min = Value::Int(i64::MIN);
+        assert_eq!(
+            min.sub_with(&one, OverflowMode::Saturating).unwrap(),
+            Value::Int(i64::MIN)
+        );
+        assert_eq!(
+            min.mul_with(&Value::Int(2), OverflowMode::Wrapping)
+                .unwrap(),
+            Value::Int(0)
+        );
+
+        // Results in range are the same in every mode
+        assert_eq!(
+            Value::Int(6)
+                .mul_with(&Value::Int(7), OverflowMode::Saturating)
+                .unwrap(),
+            Value::Int(42)
+        );
+    }
+
     #[test]
     fn test_truthiness() {
         assert!(Value::Int(1).is_truthy());
@@ -371,14 +645,28 @@ mod tests {
         assert!(Value::Bool(true).is_truthy());
         assert!(!Value::Bool(false).is_truthy());
 
-        assert!(Value::Str("hello".to_string()).is_truthy());
-        assert!(!Value::Str("".to_string()).is_truthy());
+        assert!(Value::from("hello").is_truthy());
+        assert!(!Value::from("").is_truthy());
+    }
+
+    #[test]
+    fn test_display_quoted() {
+        assert_eq!(Value::from("1").display_quoted(), "\"1\"");
+        assert_eq!(Value::Int(1).display_quoted(), "1");
+        assert_eq!(Value::Bool(true).display_quoted(), "true");
+        assert_eq!(
+            Value::from("say \"hi\"").display_quoted(),
+            r#""say \"hi\"""#
+        );
+
+        // Display keeps the raw text for print
+        assert_eq!(Value::from("1").to_string(), "1");
     }
 
     #[test]
     fn test_type_checking() {
         let int_val = Value::Int(42);
-        let str_val = Value::Str("hello".to_string());
+        let str_val = Value::from("hello");
         let bool_val = Value::Bool(true);
 
         assert_eq!(int_val.type_name(), "int");
@@ -387,7 +675,7 @@ mod tests {
 
         assert_eq!(int_val.unwrap_int(), 42);
         assert_eq!(str_val.unwrap_str(), "hello");
-        assert_eq!(bool_val.unwrap_bool(), true);
+        assert!(bool_val.unwrap_bool());
     }
 
     #[test]
@@ -399,16 +687,16 @@ mod tests {
         assert_eq!(bool_val, Value::Bool(true));
 
         let str_val: Value = "hello".into();
-        assert_eq!(str_val, Value::Str("hello".to_string()));
+        assert_eq!(str_val, Value::from("hello"));
 
         let string_val: Value = "world".to_string().into();
-        assert_eq!(string_val, Value::Str("world".to_string()));
+        assert_eq!(string_val, Value::from("world"));
     }
 
     #[test]
     fn test_type_errors() {
         let int_val = Value::Int(42);
-        let str_val = Value::Str("hello".to_string());
+        let str_val = Value::from("hello");
 
         // Test arithmetic type errors
         let result = int_val.add(&str_val);
@@ -422,4 +710,100 @@ mod tests {
         let result = int_val.less(&str_val);
         assert!(matches!(result, Err(ZvarError::RuntimeError { .. })));
     }
+
+    #[test]
+    fn test_cross_type_equality() {
+        let int_val = Value::Int(1);
+        let str_val = Value::from("1");
+        let bool_val = Value::Bool(true);
+
+        assert_eq!(int_val.equal(&str_val).unwrap(), Value::Bool(false));
+        assert_eq!(int_val.equal(&bool_val).unwrap(), Value::Bool(false));
+        assert_eq!(str_val.equal(&bool_val).unwrap(), Value::Bool(false));
+        assert_eq!(int_val.not_equal(&bool_val).unwrap(), Value::Bool(true));
+    }
+
+    #[test]
+    fn test_coercions() {
+        // Identity
+        assert_eq!(
+            Value::Int(7).coerce_to(ValueType::Int).unwrap(),
+            Value::Int(7)
+        );
+        assert_eq!(
+            Value::from("a").coerce_to(ValueType::Str).unwrap(),
+            Value::from("a")
+        );
+        assert_eq!(
+            Value::Bool(false).coerce_to(ValueType::Bool).unwrap(),
+            Value::Bool(false)
+        );
+
+        // int <-> bool
+        assert_eq!(
+            Value::Int(3).coerce_to(ValueType::Bool).unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            Value::Int(0).coerce_to(ValueType::Bool).unwrap(),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            Value::Bool(true).coerce_to(ValueType::Int).unwrap(),
+            Value::Int(1)
+        );
+        assert_eq!(
+            Value::Bool(false).coerce_to(ValueType::Int).unwrap(),
+            Value::Int(0)
+        );
+
+        // To string
+        assert_eq!(
+            Value::Int(-12).coerce_to(ValueType::Str).unwrap(),
+            Value::from("-12")
+        );
+        assert_eq!(
+            Value::Bool(true).coerce_to(ValueType::Str).unwrap(),
+            Value::from("true")
+        );
+
+        // str -> int
+        assert_eq!(
+            Value::from("42").coerce_to(ValueType::Int).unwrap(),
+            Value::Int(42)
+        );
+
+        // str -> bool agrees with truthiness
+        for text in ["", "false", "x"] {
+            let value = Value::from(text);
+            assert_eq!(
+                value.coerce_to(ValueType::Bool).unwrap(),
+                Value::Bool(value.is_truthy())
+            );
+        }
+        assert!(!Value::from("").is_truthy());
+        assert!(Value::from("false").is_truthy());
+    }
+
+    #[test]
+    fn test_rejected_coercions() {
+        let result = Value::from("forty").coerce_to(ValueType::Int);
+        assert!(matches!(result, Err(ZvarError::RuntimeError { .. })));
+    }
+
+    #[test]
+    fn test_boolean_ordering() {
+        let t = Value::Bool(true);
+        let f = Value::Bool(false);
+
+        assert_eq!(f.less(&t).unwrap(), Value::Bool(true));
+        assert_eq!(t.less(&f).unwrap(), Value::Bool(false));
+        assert_eq!(t.greater(&f).unwrap(), Value::Bool(true));
+        assert_eq!(t.less_equal(&t).unwrap(), Value::Bool(true));
+        assert_eq!(f.greater_equal(&t).unwrap(), Value::Bool(false));
+
+        // Cross-type ordering is still rejected
+        let result = t.less(&Value::Int(1));
+        assert!(matches!(result, Err(ZvarError::RuntimeError { .. })));
+    }
 }
@@ -0,0 +1,26 @@
+//! Non-fatal diagnostics reported during compilation
+
+use crate::{prelude::*, span::Span};
+use core::fmt;
+
+/// A warning that does not stop compilation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Warning {
+    pub fn new(span: Span, message: impl Into<String>) -> Self {
+        Warning {
+            span,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.span)
+    }
+}
@@ -0,0 +1,59 @@
+//! Runs programs on the library built with only `core` and `alloc`
+//!
+//! cargo test --no-default-features --features no_std --test no_std
+
+#![cfg(feature = "no_std")]
+
+use std::{cell::RefCell, fmt, rc::Rc};
+use zvar_lang::{compile_source, vm::VM};
+
+/// `core::fmt::Write` output that the test can read back
+struct SharedString(Rc<RefCell<String>>);
+
+impl fmt::Write for SharedString {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.borrow_mut().push_str(s);
+        Ok(())
+    }
+}
+
+fn run(source: &str, seed: Option<u64>) -> String {
+    let (bytecode, debug_info) = compile_source(source).unwrap();
+    let output = Rc::new(RefCell::new(String::new()));
+
+    let mut vm = VM::new();
+    vm.set_output_writer(SharedString(Rc::clone(&output)));
+    if let Some(seed) = seed {
+        vm.set_seed(seed);
+    }
+    vm.load(bytecode, Some(debug_info));
+    vm.run().unwrap();
+
+    let printed = output.borrow().clone();
+    printed
+}
+
+#[test]
+fn test_prints_to_fmt_writer() {
+    let source = r#"
+    fn f$0(v$0 int) -> int {
+        ret v$0 * 2;
+    }
+
+    main {
+        print(f$0(21));
+        print(to_upper("zvar"));
+    }
+    "#;
+
+    assert_eq!(run(source, None), "42\nZVAR\n");
+}
+
+#[test]
+fn test_random_without_clock() {
+    let source = "main { print(random(100) < 100); }";
+    assert_eq!(run(source, None), "true\n");
+
+    let seeded = "main { print(random(1000)); print(random(1000)); }";
+    assert_eq!(run(seeded, Some(7)), run(seeded, Some(7)));
+}